@@ -22,11 +22,12 @@ struct ObjectInfo {
     addr: usize,
     refcnt: i64,
     code: String,
+    kind: i64,
 }
 
 #[no_mangle]
 // Returns reserved object id.
-pub extern "C" fn report_malloc(address: *const i8, name: *const i8) -> i64 {
+pub extern "C" fn report_malloc(address: *const i8, name: *const i8, kind: i64) -> i64 {
     let name_c_str = unsafe { CStr::from_ptr(name) };
     let name_c_str = name_c_str.to_str();
     if name_c_str.is_err() {
@@ -48,6 +49,7 @@ pub extern "C" fn report_malloc(address: *const i8, name: *const i8) -> i64 {
         addr: address as usize,
         refcnt: 1,
         code: String::from(name_c_str),
+        kind,
     };
     object_table.insert(obj_id, info);
     obj_id
@@ -123,6 +125,21 @@ pub extern "C" fn report_release(address: *const i8, obj_id: i64, refcnt: i64) -
     }
 }
 
+#[no_mangle]
+pub extern "C" fn report_trace(address: *const i8, obj_id: i64, refcnt: i64) -> () {
+    let object_table = (*OBJECT_TABLE).lock().unwrap();
+    assert!(
+        object_table.contains_key(&obj_id),
+        "Trace of object id={} is reported but it isn't registered to sanitizer.",
+        obj_id
+    );
+    let info = object_table.get(&obj_id).unwrap();
+    println!(
+        "Object id={} is traced. refcnt={}, addr={:#X}, code = {}",
+        obj_id, refcnt, address as usize, info.code
+    );
+}
+
 #[no_mangle]
 pub extern "C" fn check_leak() -> () {
     let object_info = (*OBJECT_TABLE).lock().unwrap();
@@ -138,4 +155,110 @@ pub extern "C" fn check_leak() -> () {
     panic!("Some objects leaked!");
 }
 
+#[no_mangle]
+// Returns the table of live objects as a JSON array of objects with fields id, name, refcnt and kind, sorted by id.
+// The returned string should be freed by free_object_table_dump.
+pub extern "C" fn dump_object_table() -> *mut c_char {
+    let object_table = (*OBJECT_TABLE).lock().unwrap();
+    let mut infos: Vec<&ObjectInfo> = object_table.values().collect();
+    infos.sort_by_key(|info| info.id);
+    let entries: Vec<String> = infos
+        .iter()
+        .map(|info| {
+            format!(
+                "{{\"id\":{},\"name\":\"{}\",\"refcnt\":{},\"kind\":\"{}\"}}",
+                info.id,
+                escape_json(&info.code),
+                info.refcnt,
+                kind_name(info.kind)
+            )
+        })
+        .collect();
+    let json = format!("[{}]", entries.join(","));
+    CString::new(json).unwrap().into_raw()
+}
+
+#[no_mangle]
+// Frees a string returned by dump_object_table.
+pub extern "C" fn free_object_table_dump(json: *mut c_char) -> () {
+    if !json.is_null() {
+        unsafe { drop(CString::from_raw(json)) };
+    }
+}
+
+#[no_mangle]
+// Forgets all live objects, so that leaked objects of a run don't affect leak checks of later runs.
+pub extern "C" fn clear_object_table() -> () {
+    let mut object_table = (*OBJECT_TABLE).lock().unwrap();
+    object_table.clear();
+}
+
+// Escape a string to be embedded in a JSON string literal.
+fn escape_json(s: &str) -> String {
+    let mut ret = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+#[no_mangle]
+// Returns the kind of object reported in report_malloc.
+pub extern "C" fn get_kind(obj_id: i64) -> i64 {
+    let object_table = (*OBJECT_TABLE).lock().unwrap();
+    assert!(
+        object_table.contains_key(&obj_id),
+        "Kind of object id={} is queried but it isn't registered to sanitizer.",
+        obj_id
+    );
+    object_table.get(&obj_id).unwrap().kind
+}
+
+#[no_mangle]
+// Returns the number of objects which are not deallocated yet.
+pub extern "C" fn get_number_of_live_objects() -> i64 {
+    let object_table = (*OBJECT_TABLE).lock().unwrap();
+    object_table.len() as i64
+}
+
+#[no_mangle]
+// Returns the number of objects allocated so far.
+pub extern "C" fn get_number_of_allocations() -> i64 {
+    *(*OBJECT_ID).lock().unwrap()
+}
+
+// Name of kind of object. Keep in sync with ObjectKind of fixlang.
+fn kind_name(kind: i64) -> &'static str {
+    match kind {
+        1 => "Int",
+        2 => "Bool",
+        3 => "Array",
+        4 => "String",
+        5 => "Option",
+        6 => "Pair",
+        7 => "Lambda",
+        8 => "ArrayView",
+        9 => "Float",
+        10 => "Weak",
+        _ => "Unknown",
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn report_kind_mismatch(expected: i64, actual: i64) -> () {
+    panic!(
+        "An object of kind {} is written into an array of objects of kind {}!",
+        kind_name(actual),
+        kind_name(expected)
+    );
+}
+
 const VERBOSE: bool = false;
@@ -21,37 +21,136 @@ use super::*;
 //   | FunTy Type Type
 //   | ForAllTy Var Type
 
+#[derive(Debug)]
 pub struct ExprInfo {
     pub expr: Arc<Expr>,
     pub free_vars: HashSet<String>,
+    // Span in the source from which the expression is parsed (None for expressions built otherwise).
+    pub source: Option<Span>,
+}
+
+// Span of a source as a range [start, end) of byte offsets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    // Get 1-based line and column numbers of the start of the span.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+        let col = source[line_start..self.start].chars().count() + 1;
+        (line, col)
+    }
+
+    // Render the span as "line:col" followed by the line of the source with the span underlined.
+    // A span over multiple lines is underlined up to the end of its first line.
+    pub fn render(&self, source: &str) -> String {
+        let (line, col) = self.line_col(source);
+        let line_start = source[..self.start].rfind('\n').map_or(0, |pos| pos + 1);
+        let line_end = source[self.start..]
+            .find('\n')
+            .map_or(source.len(), |pos| self.start + pos);
+        let underline_end = self.end.min(line_end).max(self.start);
+        let underline_len = source[self.start..underline_end].chars().count().max(1);
+        format!(
+            "{}:{}\n{}\n{}{}",
+            line,
+            col,
+            &source[line_start..line_end],
+            " ".repeat(col - 1),
+            "^".repeat(underline_len)
+        )
+    }
+}
+
+// Structural equality of expressions, which ignores auxiliary information such as free variables.
+impl PartialEq for ExprInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr
+    }
 }
 
 impl ExprInfo {
     fn with_free_vars(self: &Arc<Self>, free_vars: HashSet<String>) -> Arc<ExprInfo> {
+        self.with_free_vars_and_source(free_vars, self.source)
+    }
+
+    fn with_free_vars_and_source(
+        self: &Arc<Self>,
+        free_vars: HashSet<String>,
+        source: Option<Span>,
+    ) -> Arc<ExprInfo> {
         Arc::new(ExprInfo {
             expr: self.expr.clone(),
             free_vars,
+            source,
+        })
+    }
+
+    // Set free variables of the node from those of its subexpressions, which must have been calculated.
+    pub fn with_free_vars_of_subexprs(self: &Arc<Self>) -> Arc<ExprInfo> {
+        self.with_free_vars(free_vars_of_node(self))
+    }
+
+    // Set the span of the expression from which this expression is rebuilt (e.g., by a pass), if it has one.
+    pub fn with_source_of(self: &Arc<Self>, orig: &ExprInfo) -> Arc<ExprInfo> {
+        match orig.source {
+            Some(source) if self.source != Some(source) => self.with_source(source),
+            _ => self.clone(),
+        }
+    }
+
+    pub fn with_source(self: &Arc<Self>, source: Span) -> Arc<ExprInfo> {
+        Arc::new(ExprInfo {
+            expr: self.expr.clone(),
+            free_vars: self.free_vars.clone(),
+            source: Some(source),
         })
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Expr {
     Var(Arc<Var>),
     Lit(Arc<Literal>),
     App(Arc<ExprInfo>, Arc<ExprInfo>),
     Lam(Arc<Var>, Arc<ExprInfo>),
     Let(Arc<Var>, Arc<ExprInfo>, Arc<ExprInfo>),
-    // TODO: Implement case
     If(Arc<ExprInfo>, Arc<ExprInfo>, Arc<ExprInfo>),
+    // Case of scrutinee and arms. The first arm whose pattern matches is taken.
+    Case(Arc<ExprInfo>, Vec<(Arc<Pattern>, Arc<ExprInfo>)>),
     Type(Arc<Type>),
 }
 
+// Pattern of an arm of case.
+// TODO: Add patterns binding variables.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    Int(i64),
+    Bool(bool),
+    Wildcard,
+}
+
+impl Pattern {
+    pub fn to_string(&self) -> String {
+        match self {
+            Pattern::Int(val) => val.to_string(),
+            Pattern::Bool(val) => val.to_string(),
+            Pattern::Wildcard => String::from("_"),
+        }
+    }
+}
+
 impl Expr {
     fn into_expr_info(self: &Arc<Self>) -> Arc<ExprInfo> {
         Arc::new(ExprInfo {
             expr: self.clone(),
             free_vars: Default::default(),
+            source: None,
         })
     }
     pub fn to_string(&self) -> String {
@@ -72,6 +171,14 @@ impl Expr {
                 t.expr.to_string(),
                 e.expr.to_string()
             ),
+            Expr::Case(s, arms) => format!(
+                "case {} of {{{}}}",
+                s.expr.to_string(),
+                arms.iter()
+                    .map(|(p, e)| format!("{} -> ({})", p.to_string(), e.expr.to_string()))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
             Expr::Type(_) => todo!(),
         }
     }
@@ -84,9 +191,23 @@ pub struct Literal {
     pub generator: Arc<LiteralGenerator>,
     pub free_vars: Vec<String>, // e.g. "+" literal has two free variables.
     name: String,
+    pub ty: Option<Arc<Type>>, // Type of literal if it is known, e.g., int literals.
+}
+
+impl std::fmt::Debug for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Literal").field("name", &self.name).finish()
+    }
 }
 
-#[derive(Eq, PartialEq)]
+// Literals are compared by their names, since generators cannot be compared.
+impl PartialEq for Literal {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+#[derive(Eq, PartialEq, Hash, Debug)]
 pub enum Var {
     TermVar { name: String },
     TyVar { name: String },
@@ -101,18 +222,18 @@ impl Var {
     }
 }
 
-#[derive(Eq, PartialEq)]
+#[derive(Eq, PartialEq, Hash, Debug)]
 enum Kind {
     Star,
     Arrow(Arc<Kind>, Arc<Kind>),
 }
 
-#[derive(Eq, PartialEq)]
+#[derive(Eq, PartialEq, Hash, Debug)]
 struct TyLit {
     value: String,
 }
 
-#[derive(Eq, PartialEq)]
+#[derive(Eq, PartialEq, Hash, Debug, Clone)]
 pub enum Type {
     TyVar(Arc<Var>),
     LitTy(Arc<TyLit>),
@@ -122,16 +243,100 @@ pub enum Type {
     ForAllTy(Arc<Var>, Arc<Type>),
 }
 
-#[derive(Eq, PartialEq)]
+#[derive(Eq, PartialEq, Hash, Debug)]
 enum TyCon {
     Pair,
 }
 
+impl Type {
+    pub fn to_string(&self) -> String {
+        match self {
+            Type::TyVar(v) => v.name().clone(),
+            Type::LitTy(l) => l.value.clone(),
+            Type::AppTy(f, a) => format!("({}) ({})", f.to_string(), a.to_string()),
+            Type::TyConApp(c, args) => {
+                let mut ret = String::from(match **c {
+                    TyCon::Pair => "Pair",
+                });
+                for arg in args {
+                    ret += &format!(" ({})", arg.to_string());
+                }
+                ret
+            }
+            Type::FunTy(src, dst) => format!("({}) -> ({})", src.to_string(), dst.to_string()),
+            Type::ForAllTy(v, t) => format!("forall {}. ({})", v.name(), t.to_string()),
+        }
+    }
+}
+
+// Table of types, which makes structurally equal types share an Arc.
+#[derive(Default)]
+pub struct TypeInterner {
+    types: HashSet<Arc<Type>>,
+}
+
+impl TypeInterner {
+    // Get the Arc of a type equal to the given one in the table, adding the given one if there is no such type.
+    pub fn intern(&mut self, ty: Arc<Type>) -> Arc<Type> {
+        if let Some(interned) = self.types.get(&ty) {
+            return interned.clone();
+        }
+        self.types.insert(ty.clone());
+        ty
+    }
+}
+
+fn lit_ty(value: &str) -> Arc<Type> {
+    Arc::new(Type::LitTy(Arc::new(TyLit {
+        value: String::from(value),
+    })))
+}
+
+pub fn int_lit_ty() -> Arc<Type> {
+    lit_ty("Int")
+}
+
+pub fn float_lit_ty() -> Arc<Type> {
+    lit_ty("Float")
+}
+
+pub fn bool_lit_ty() -> Arc<Type> {
+    lit_ty("Bool")
+}
+
+pub fn string_lit_ty() -> Arc<Type> {
+    lit_ty("String")
+}
+
+// Types of arrays, options and views of arrays, whose type constructors take an element type.
+pub fn array_ty(elem: Arc<Type>) -> Arc<Type> {
+    Arc::new(Type::AppTy(lit_ty("Array"), elem))
+}
+
+pub fn option_ty(elem: Arc<Type>) -> Arc<Type> {
+    Arc::new(Type::AppTy(lit_ty("Option"), elem))
+}
+
+pub fn weak_ty(elem: Arc<Type>) -> Arc<Type> {
+    Arc::new(Type::AppTy(lit_ty("Weak"), elem))
+}
+
+pub fn view_ty(elem: Arc<Type>) -> Arc<Type> {
+    Arc::new(Type::AppTy(lit_ty("View"), elem))
+}
+
+pub fn pair_ty(first: Arc<Type>, second: Arc<Type>) -> Arc<Type> {
+    Arc::new(Type::TyConApp(
+        Arc::new(TyCon::Pair),
+        vec![(*first).clone(), (*second).clone()],
+    ))
+}
+
 fn star_kind() -> Arc<Kind> {
     Arc::new(Kind::Star)
 }
 
-fn lambda_ty(src: Arc<Type>, dst: Arc<Type>) -> Arc<Type> {
+pub fn lambda_ty(src: Arc<Type>, dst: Arc<Type>) -> Arc<Type> {
     Arc::new(Type::FunTy(src, dst))
 }
 
@@ -141,11 +346,11 @@ fn tyvar_var(var_name: &str) -> Arc<Var> {
     })
 }
 
-fn tyvar_ty(var_name: &str) -> Arc<Type> {
+pub fn tyvar_ty(var_name: &str) -> Arc<Type> {
     Arc::new(Type::TyVar(tyvar_var(var_name)))
 }
 
-fn forall_ty(var_name: &str, ty: Arc<Type>) -> Arc<Type> {
+pub fn forall_ty(var_name: &str, ty: Arc<Type>) -> Arc<Type> {
     Arc::new(Type::ForAllTy(tyvar_var(var_name), ty))
 }
 
@@ -164,6 +369,22 @@ pub fn lit(
         generator,
         free_vars,
         name,
+        ty: None,
+    })))
+    .into_expr_info()
+}
+
+pub fn typed_lit(
+    generator: Arc<LiteralGenerator>,
+    free_vars: Vec<String>,
+    name: String,
+    ty: Arc<Type>,
+) -> Arc<ExprInfo> {
+    Arc::new(Expr::Lit(Arc::new(Literal {
+        generator,
+        free_vars,
+        name,
+        ty: Some(ty),
     })))
     .into_expr_info()
 }
@@ -192,51 +413,526 @@ pub fn conditional(
     Arc::new(Expr::If(cond, then_expr, else_expr)).into_expr_info()
 }
 
-// TODO: use persistent binary search tree as ExprAuxInfo to avoid O(n^2) complexity of calculate_aux_info.
-pub fn calculate_free_vars(ei: Arc<ExprInfo>) -> Arc<ExprInfo> {
+pub fn case_of(
+    scrutinee: Arc<ExprInfo>,
+    arms: Vec<(Arc<Pattern>, Arc<ExprInfo>)>,
+) -> Arc<ExprInfo> {
+    Arc::new(Expr::Case(scrutinee, arms)).into_expr_info()
+}
+
+// Visitor of expressions.
+// Default implementations traverse all subexpressions, so a pass only overrides the cases it cares about.
+pub trait Visitor {
+    fn visit_expr(&mut self, ei: &Arc<ExprInfo>) {
+        walk_expr(self, ei)
+    }
+    fn visit_var(&mut self, _var: &Arc<Var>) {}
+    fn visit_lit(&mut self, _lit: &Arc<Literal>) {}
+    fn visit_app(&mut self, func: &Arc<ExprInfo>, arg: &Arc<ExprInfo>) {
+        self.visit_expr(func);
+        self.visit_expr(arg);
+    }
+    fn visit_lam(&mut self, _var: &Arc<Var>, val: &Arc<ExprInfo>) {
+        self.visit_expr(val);
+    }
+    fn visit_let(&mut self, _var: &Arc<Var>, bound: &Arc<ExprInfo>, val: &Arc<ExprInfo>) {
+        self.visit_expr(bound);
+        self.visit_expr(val);
+    }
+    fn visit_if(&mut self, cond: &Arc<ExprInfo>, then: &Arc<ExprInfo>, else_expr: &Arc<ExprInfo>) {
+        self.visit_expr(cond);
+        self.visit_expr(then);
+        self.visit_expr(else_expr);
+    }
+    fn visit_case(&mut self, scrutinee: &Arc<ExprInfo>, arms: &[(Arc<Pattern>, Arc<ExprInfo>)]) {
+        self.visit_expr(scrutinee);
+        for (_, arm) in arms {
+            self.visit_expr(arm);
+        }
+    }
+    fn visit_type(&mut self, _ty: &Arc<Type>) {}
+}
+
+// Dispatch an expression to the method of visitor for its variant.
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, ei: &Arc<ExprInfo>) {
     match &*ei.expr {
-        Expr::Var(var) => {
-            let free_vars = vec![var.name().clone()].into_iter().collect();
-            ei.with_free_vars(free_vars)
+        Expr::Var(var) => visitor.visit_var(var),
+        Expr::Lit(lit) => visitor.visit_lit(lit),
+        Expr::App(func, arg) => visitor.visit_app(func, arg),
+        Expr::Lam(var, val) => visitor.visit_lam(var, val),
+        Expr::Let(var, bound, val) => visitor.visit_let(var, bound, val),
+        Expr::If(cond, then, else_expr) => visitor.visit_if(cond, then, else_expr),
+        Expr::Case(scrutinee, arms) => visitor.visit_case(scrutinee, arms),
+        Expr::Type(ty) => visitor.visit_type(ty),
+    }
+}
+
+// Folder of expressions, which rebuilds an expression bottom-up.
+// Each fold_* method receives the original node and its already folded subexpressions.
+// Default implementations rebuild the node from folded subexpressions with the source span of the original node
+// (other auxiliary information such as free variables is not kept).
+pub trait Folder {
+    fn fold_expr(&mut self, ei: Arc<ExprInfo>) -> Arc<ExprInfo> {
+        fold_subexprs(self, ei)
+    }
+    // Called before and after folding the scope of a variable bound by a lambda or a let (e.g., to track shadowing).
+    fn enter_binder(&mut self, _var: &Arc<Var>) {}
+    fn exit_binder(&mut self, _var: &Arc<Var>) {}
+    fn fold_var(&mut self, ei: Arc<ExprInfo>, _var: &Arc<Var>) -> Arc<ExprInfo> {
+        ei
+    }
+    fn fold_lit(&mut self, ei: Arc<ExprInfo>, _lit: &Arc<Literal>) -> Arc<ExprInfo> {
+        ei
+    }
+    fn fold_app(
+        &mut self,
+        ei: Arc<ExprInfo>,
+        func: Arc<ExprInfo>,
+        arg: Arc<ExprInfo>,
+    ) -> Arc<ExprInfo> {
+        app(func, arg).with_source_of(&ei)
+    }
+    fn fold_lam(&mut self, ei: Arc<ExprInfo>, var: &Arc<Var>, val: Arc<ExprInfo>) -> Arc<ExprInfo> {
+        lam(var.clone(), val).with_source_of(&ei)
+    }
+    fn fold_let(
+        &mut self,
+        ei: Arc<ExprInfo>,
+        var: &Arc<Var>,
+        bound: Arc<ExprInfo>,
+        val: Arc<ExprInfo>,
+    ) -> Arc<ExprInfo> {
+        let_in(var.clone(), bound, val).with_source_of(&ei)
+    }
+    fn fold_if(
+        &mut self,
+        ei: Arc<ExprInfo>,
+        cond: Arc<ExprInfo>,
+        then: Arc<ExprInfo>,
+        else_expr: Arc<ExprInfo>,
+    ) -> Arc<ExprInfo> {
+        conditional(cond, then, else_expr).with_source_of(&ei)
+    }
+    fn fold_case(
+        &mut self,
+        ei: Arc<ExprInfo>,
+        scrutinee: Arc<ExprInfo>,
+        arms: Vec<(Arc<Pattern>, Arc<ExprInfo>)>,
+    ) -> Arc<ExprInfo> {
+        case_of(scrutinee, arms).with_source_of(&ei)
+    }
+    fn fold_type(&mut self, ei: Arc<ExprInfo>, _ty: &Arc<Type>) -> Arc<ExprInfo> {
+        ei
+    }
+}
+
+// Fold subexpressions of an expression and pass them to the method of folder for its variant.
+pub fn fold_subexprs<F: Folder + ?Sized>(folder: &mut F, ei: Arc<ExprInfo>) -> Arc<ExprInfo> {
+    let expr = ei.expr.clone();
+    match &*expr {
+        Expr::Var(var) => folder.fold_var(ei, var),
+        Expr::Lit(lit) => folder.fold_lit(ei, lit),
+        Expr::App(func, arg) => {
+            let func = folder.fold_expr(func.clone());
+            let arg = folder.fold_expr(arg.clone());
+            folder.fold_app(ei, func, arg)
         }
-        Expr::Lit(lit) => {
-            let free_vars = lit.free_vars.clone().into_iter().collect();
-            ei.with_free_vars(free_vars)
+        Expr::Lam(var, val) => {
+            folder.enter_binder(var);
+            let val = folder.fold_expr(val.clone());
+            folder.exit_binder(var);
+            folder.fold_lam(ei, var, val)
         }
-        Expr::App(func, arg) => {
-            let func = calculate_free_vars(func.clone());
-            let arg = calculate_free_vars(arg.clone());
-            let mut free_vars = func.free_vars.clone();
-            free_vars.extend(arg.free_vars.clone());
-            app(func, arg).with_free_vars(free_vars)
-        }
-        Expr::Lam(arg, val) => {
-            let val = calculate_free_vars(val.clone());
+        Expr::Let(var, bound, val) => {
+            let bound = folder.fold_expr(bound.clone());
+            folder.enter_binder(var);
+            let val = folder.fold_expr(val.clone());
+            folder.exit_binder(var);
+            folder.fold_let(ei, var, bound, val)
+        }
+        Expr::If(cond, then, else_expr) => {
+            let cond = folder.fold_expr(cond.clone());
+            let then = folder.fold_expr(then.clone());
+            let else_expr = folder.fold_expr(else_expr.clone());
+            folder.fold_if(ei, cond, then, else_expr)
+        }
+        Expr::Case(scrutinee, arms) => {
+            let scrutinee = folder.fold_expr(scrutinee.clone());
+            let arms = arms
+                .iter()
+                .map(|(pat, arm)| (pat.clone(), folder.fold_expr(arm.clone())))
+                .collect();
+            folder.fold_case(ei, scrutinee, arms)
+        }
+        Expr::Type(ty) => folder.fold_type(ei, ty),
+    }
+}
+
+// Pending work of the traversal in `calculate_free_vars`.
+enum FreeVarsTask {
+    // Calculate free variables of an expression and push the result.
+    Visit(Arc<ExprInfo>),
+    // Pop results of subexpressions and push the node rebuilt from them.
+    // Each variant has the source span(s) of the node to be rebuilt.
+    BuildApp(Vec<Option<Span>>), // Spans of applications of the spine, from the innermost one.
+    BuildLam(Arc<Var>, Option<Span>),
+    BuildLet(Arc<Var>, Option<Span>),
+    BuildIf(Option<Span>),
+    BuildCase(Vec<Arc<Pattern>>, Option<Span>),
+}
+
+// Union of free variables of nodes. The largest set is cloned and the others are added to it.
+fn union_free_vars(nodes: &[&Arc<ExprInfo>]) -> HashSet<String> {
+    let largest = nodes
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, node)| node.free_vars.len())
+        .map(|(i, _)| i);
+    let mut free_vars = match largest {
+        Some(i) => nodes[i].free_vars.clone(),
+        None => HashSet::default(),
+    };
+    for (i, node) in nodes.iter().enumerate() {
+        if Some(i) != largest {
+            free_vars.extend(node.free_vars.iter().cloned());
+        }
+    }
+    free_vars
+}
+
+// Free variables of a node, calculated from those of its subexpressions.
+fn free_vars_of_node(ei: &Arc<ExprInfo>) -> HashSet<String> {
+    match &*ei.expr {
+        Expr::Var(var) => vec![var.name().clone()].into_iter().collect(),
+        Expr::Lit(lit) => lit.free_vars.iter().cloned().collect(),
+        Expr::Type(_) => HashSet::default(),
+        Expr::App(func, arg) => union_free_vars(&[func, arg]),
+        Expr::Lam(var, val) => {
             let mut free_vars = val.free_vars.clone();
-            free_vars.remove(arg.name());
+            free_vars.remove(var.name());
             free_vars.remove(SELF_NAME);
-            lam(arg.clone(), val).with_free_vars(free_vars)
+            free_vars
         }
         Expr::Let(var, bound, val) => {
             // NOTE: Our Let is non-recursive let, i.e.,
             // "let x = f x in g x" is equal to "let y = f x in g y",
             // and x ∈ FreeVars("let x = f x in g x") = (FreeVars(g x) - {x}) + FreeVars(f x) != (FreeVars(g x) + FreeVars(f x)) - {x}.
-            let bound = calculate_free_vars(bound.clone());
-            let val = calculate_free_vars(val.clone());
-            let mut free_vars = val.free_vars.clone();
-            free_vars.remove(var.name());
-            free_vars.extend(bound.free_vars.clone());
-            let_in(var.clone(), bound, val).with_free_vars(free_vars)
+            if bound.free_vars.len() > val.free_vars.len() {
+                let mut free_vars = bound.free_vars.clone();
+                let val_free_vars = val.free_vars.iter().filter(|v| *v != var.name());
+                free_vars.extend(val_free_vars.cloned());
+                free_vars
+            } else {
+                let mut free_vars = val.free_vars.clone();
+                free_vars.remove(var.name());
+                free_vars.extend(bound.free_vars.iter().cloned());
+                free_vars
+            }
         }
-        Expr::If(cond, then, else_expr) => {
-            let cond = calculate_free_vars(cond.clone());
-            let then = calculate_free_vars(then.clone());
-            let else_expr = calculate_free_vars(else_expr.clone());
-            let mut free_vars = cond.free_vars.clone();
-            free_vars.extend(then.free_vars.clone());
-            free_vars.extend(else_expr.free_vars.clone());
-            conditional(cond, then, else_expr).with_free_vars(free_vars)
+        Expr::If(cond, then, else_expr) => union_free_vars(&[cond, then, else_expr]),
+        // NOTE: No pattern binds variables for now.
+        Expr::Case(scrutinee, arms) => union_free_vars(
+            &std::iter::once(scrutinee)
+                .chain(arms.iter().map(|(_, arm)| arm))
+                .collect::<Vec<_>>(),
+        ),
+    }
+}
+
+// Set free variables of each node, by a post-order traversal with an explicit stack so that deeply nested expressions don't overflow the stack.
+// Applications are handled by flattening the spine, e.g., `f a b c` into f and [a, b, c], so that a long spine doesn't make deep nesting of tasks.
+// When the spine is rebuilt, the free variables of each application are the union of those of its function and its argument.
+// NOTE: Each node owns its set, so the total size of sets can still be quadratic for a program where many variables are live across deep nesting.
+pub fn calculate_free_vars(ei: Arc<ExprInfo>) -> Arc<ExprInfo> {
+    let mut tasks = vec![FreeVarsTask::Visit(ei)];
+    let mut results: Vec<Arc<ExprInfo>> = vec![];
+    while let Some(task) = tasks.pop() {
+        match task {
+            FreeVarsTask::Visit(ei) => match &*ei.expr {
+                Expr::Var(_) | Expr::Lit(_) => results.push(ei.with_free_vars_of_subexprs()),
+                Expr::Type(_) => results.push(ei),
+                Expr::App(_, _) => {
+                    let mut args = vec![];
+                    let mut sources = vec![];
+                    let mut head = ei;
+                    while let Expr::App(func, arg) = &*head.expr.clone() {
+                        args.push(arg.clone());
+                        sources.push(head.source);
+                        head = func.clone();
+                    }
+                    sources.reverse();
+                    // Arguments were collected from the last one, so the head and then the first argument are visited first.
+                    tasks.push(FreeVarsTask::BuildApp(sources));
+                    tasks.extend(args.into_iter().map(FreeVarsTask::Visit));
+                    tasks.push(FreeVarsTask::Visit(head));
+                }
+                Expr::Lam(var, val) => {
+                    tasks.push(FreeVarsTask::BuildLam(var.clone(), ei.source));
+                    tasks.push(FreeVarsTask::Visit(val.clone()));
+                }
+                Expr::Let(var, bound, val) => {
+                    tasks.push(FreeVarsTask::BuildLet(var.clone(), ei.source));
+                    tasks.push(FreeVarsTask::Visit(val.clone()));
+                    tasks.push(FreeVarsTask::Visit(bound.clone()));
+                }
+                Expr::If(cond, then, else_expr) => {
+                    tasks.push(FreeVarsTask::BuildIf(ei.source));
+                    tasks.push(FreeVarsTask::Visit(else_expr.clone()));
+                    tasks.push(FreeVarsTask::Visit(then.clone()));
+                    tasks.push(FreeVarsTask::Visit(cond.clone()));
+                }
+                Expr::Case(scrutinee, arms) => {
+                    tasks.push(FreeVarsTask::BuildCase(
+                        arms.iter().map(|(pat, _)| pat.clone()).collect(),
+                        ei.source,
+                    ));
+                    tasks.extend(
+                        arms.iter()
+                            .rev()
+                            .map(|(_, arm)| FreeVarsTask::Visit(arm.clone())),
+                    );
+                    tasks.push(FreeVarsTask::Visit(scrutinee.clone()));
+                }
+            },
+            FreeVarsTask::BuildApp(sources) => {
+                let args = results.split_off(results.len() - sources.len());
+                let mut ei = results.pop().unwrap();
+                for (arg, source) in args.into_iter().zip(sources) {
+                    let node = app(ei, arg);
+                    ei = node.with_free_vars_and_source(free_vars_of_node(&node), source);
+                }
+                results.push(ei);
+            }
+            FreeVarsTask::BuildLam(var, source) => {
+                let val = results.pop().unwrap();
+                let node = lam(var, val);
+                results.push(node.with_free_vars_and_source(free_vars_of_node(&node), source));
+            }
+            FreeVarsTask::BuildLet(var, source) => {
+                let val = results.pop().unwrap();
+                let bound = results.pop().unwrap();
+                let node = let_in(var, bound, val);
+                results.push(node.with_free_vars_and_source(free_vars_of_node(&node), source));
+            }
+            FreeVarsTask::BuildIf(source) => {
+                let else_expr = results.pop().unwrap();
+                let then = results.pop().unwrap();
+                let cond = results.pop().unwrap();
+                let node = conditional(cond, then, else_expr);
+                results.push(node.with_free_vars_and_source(free_vars_of_node(&node), source));
+            }
+            FreeVarsTask::BuildCase(pats, source) => {
+                let arms = results.split_off(results.len() - pats.len());
+                let scrutinee = results.pop().unwrap();
+                let node = case_of(scrutinee, pats.into_iter().zip(arms).collect());
+                results.push(node.with_free_vars_and_source(free_vars_of_node(&node), source));
+            }
+        }
+    }
+    results.pop().unwrap()
+}
+
+// Dump free variables of each node of an expression whose free variables are calculated, one node per line.
+// Each line is the node indented by its depth, followed by its free variables in sorted order, e.g., "  add x : {add, x}".
+pub fn dump_free_vars(ei: &ExprInfo) -> String {
+    fn dump(ei: &ExprInfo, depth: usize, out: &mut String) {
+        let mut free_vars: Vec<&String> = ei.free_vars.iter().collect();
+        free_vars.sort();
+        let free_vars: Vec<&str> = free_vars.into_iter().map(|v| v.as_str()).collect();
+        out.push_str(&format!(
+            "{}{} : {{{}}}\n",
+            "  ".repeat(depth),
+            ei.expr.to_string(),
+            free_vars.join(", ")
+        ));
+        match &*ei.expr {
+            Expr::Var(_) | Expr::Lit(_) | Expr::Type(_) => {}
+            Expr::App(func, arg) => {
+                dump(func, depth + 1, out);
+                dump(arg, depth + 1, out);
+            }
+            Expr::Lam(_, val) => dump(val, depth + 1, out),
+            Expr::Let(_, bound, val) => {
+                dump(bound, depth + 1, out);
+                dump(val, depth + 1, out);
+            }
+            Expr::If(cond, then_expr, else_expr) => {
+                dump(cond, depth + 1, out);
+                dump(then_expr, depth + 1, out);
+                dump(else_expr, depth + 1, out);
+            }
+            Expr::Case(scrutinee, arms) => {
+                dump(scrutinee, depth + 1, out);
+                for (_, arm) in arms {
+                    dump(arm, depth + 1, out);
+                }
+            }
+        }
+    }
+    let mut out = String::new();
+    dump(ei, 0, &mut out);
+    out
+}
+
+// Get type of an expression if it is obvious without type inference (e.g., literals).
+fn obvious_type(ei: &Arc<ExprInfo>) -> Option<Arc<Type>> {
+    match &*ei.expr {
+        Expr::Lit(lit) => lit.ty.clone(),
+        Expr::Let(_, _, val) => obvious_type(val),
+        Expr::If(_, then_expr, else_expr) => {
+            obvious_type(then_expr).or_else(|| obvious_type(else_expr))
+        }
+        Expr::Case(_, arms) => arms.iter().find_map(|(_, arm)| obvious_type(arm)),
+        _ => None,
+    }
+}
+
+// Check that two branches of each if expression have the same type as far as it is obvious.
+// This is a lightweight diagnostics performed before type inference is implemented.
+pub fn check_if_branch_types(ei: &Arc<ExprInfo>) -> Result<(), String> {
+    match &*ei.expr {
+        Expr::Var(_) => Ok(()),
+        Expr::Lit(_) => Ok(()),
+        Expr::App(func, arg) => {
+            check_if_branch_types(func)?;
+            check_if_branch_types(arg)
+        }
+        Expr::Lam(_, val) => check_if_branch_types(val),
+        Expr::Let(_, bound, val) => {
+            check_if_branch_types(bound)?;
+            check_if_branch_types(val)
+        }
+        Expr::If(cond, then_expr, else_expr) => {
+            check_if_branch_types(cond)?;
+            check_if_branch_types(then_expr)?;
+            check_if_branch_types(else_expr)?;
+            if let (Some(then_ty), Some(else_ty)) =
+                (obvious_type(then_expr), obvious_type(else_expr))
+            {
+                if then_ty != else_ty {
+                    return Err(format!(
+                        "if branches have incompatible types: {} and {} in \"{}\"",
+                        then_ty.to_string(),
+                        else_ty.to_string(),
+                        ei.expr.to_string()
+                    ));
+                }
+            }
+            Ok(())
+        }
+        Expr::Case(scrutinee, arms) => {
+            check_if_branch_types(scrutinee)?;
+            for (_, arm) in arms {
+                check_if_branch_types(arm)?;
+            }
+            Ok(())
+        }
+        Expr::Type(_) => Ok(()),
+    }
+}
+
+// Checker of patterns of case expressions, which records the first error found.
+struct CasePatternChecker {
+    error: Option<String>,
+}
+
+impl Visitor for CasePatternChecker {
+    fn visit_case(&mut self, scrutinee: &Arc<ExprInfo>, arms: &[(Arc<Pattern>, Arc<ExprInfo>)]) {
+        let has_int = arms.iter().any(|(pat, _)| matches!(**pat, Pattern::Int(_)));
+        let has_bool = arms
+            .iter()
+            .any(|(pat, _)| matches!(**pat, Pattern::Bool(_)));
+        if has_int && has_bool && self.error.is_none() {
+            self.error = Some(format!(
+                "case patterns have incompatible types: Int and Bool in case on \"{}\"",
+                scrutinee.expr.to_string()
+            ));
         }
-        Expr::Type(_) => ei.clone(),
+        self.visit_expr(scrutinee);
+        for (_, arm) in arms {
+            self.visit_expr(arm);
+        }
+    }
+}
+
+// Check that patterns of each case expression have the same type, since code generation reads the scrutinee by it.
+pub fn check_case_patterns(ei: &Arc<ExprInfo>) -> Result<(), String> {
+    let mut checker = CasePatternChecker { error: None };
+    checker.visit_expr(ei);
+    match checker.error {
+        Some(msg) => Err(msg),
+        None => Ok(()),
+    }
+}
+
+// Linter which finds suspicious parts of a program which are not errors.
+struct Linter<'a> {
+    // Names of library functions, which should not be shadowed.
+    builtin_names: &'a [String],
+    warnings: Vec<String>,
+}
+
+impl<'a> Linter<'a> {
+    fn check_shadowing(&mut self, var: &Arc<Var>) {
+        if self.builtin_names.contains(var.name()) {
+            self.warnings
+                .push(format!("`{}` shadows a library function", var.name()));
+        }
+    }
+}
+
+impl<'a> Visitor for Linter<'a> {
+    fn visit_app(&mut self, func: &Arc<ExprInfo>, arg: &Arc<ExprInfo>) {
+        if let Some(ty) = obvious_type(func) {
+            self.warnings.push(format!(
+                "a value of type {} is applied as a function in \"{}\"",
+                ty.to_string(),
+                app(func.clone(), arg.clone()).expr.to_string()
+            ));
+        }
+        self.visit_expr(func);
+        self.visit_expr(arg);
+    }
+    fn visit_lam(&mut self, var: &Arc<Var>, val: &Arc<ExprInfo>) {
+        self.check_shadowing(var);
+        self.visit_expr(val);
+    }
+    fn visit_let(&mut self, var: &Arc<Var>, bound: &Arc<ExprInfo>, val: &Arc<ExprInfo>) {
+        self.check_shadowing(var);
+        if !val.free_vars.contains(var.name()) {
+            self.warnings
+                .push(format!("let binding of `{}` is unused", var.name()));
+        }
+        self.visit_expr(bound);
+        self.visit_expr(val);
+    }
+}
+
+// Find suspicious parts of a program: unused let bindings, shadowed library functions,
+// applications of non-functions and a non-scalar result, as far as they are obvious.
+pub fn lint_program(program: &Arc<ExprInfo>, builtin_names: &[String]) -> Vec<String> {
+    let mut linter = Linter {
+        builtin_names,
+        warnings: vec![],
+    };
+    // Free variables are calculated once here, and unused let bindings are found by those of their bodies.
+    let program = &calculate_free_vars(program.clone());
+    linter.visit_expr(program);
+
+    // The result of a program should be Int or Bool.
+    let mut result = program.clone();
+    while let Expr::Let(_, _, val) = &*result.expr.clone() {
+        result = val.clone();
+    }
+    let is_non_scalar = match &*result.expr {
+        Expr::Lam(_, _) => true,
+        _ => obvious_type(&result).map_or(false, |ty| ty != int_lit_ty() && ty != bool_lit_ty()),
+    };
+    if is_non_scalar {
+        linter.warnings.push(format!(
+            "the result of the program is not Int or Bool: \"{}\"",
+            result.expr.to_string()
+        ));
     }
+    linter.warnings
 }
@@ -3,30 +3,77 @@ use super::*;
 
 pub fn int(val: i64) -> Arc<ExprInfo> {
     let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
-        let ptr_to_int_obj =
-            ObjectType::int_obj_type().create_obj(gc, Some(val.to_string().as_str()));
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, val.to_string().as_str());
         let value = gc.context.i64_type().const_int(val as u64, false);
         gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, value);
         ptr_to_int_obj
     });
-    lit(generator, vec![], val.to_string())
+    typed_lit(generator, vec![], val.to_string(), int_lit_ty())
+}
+
+pub fn float(val: f64) -> Arc<ExprInfo> {
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let ptr_to_float_obj =
+            ObjectType::float_obj_type().create_obj(gc, val.to_string().as_str());
+        let value = gc.context.f64_type().const_float(val);
+        gc.store_obj_field(ptr_to_float_obj, float_type(gc.context), 1, value);
+        ptr_to_float_obj
+    });
+    typed_lit(generator, vec![], val.to_string(), float_lit_ty())
 }
 
 pub fn bool(val: bool) -> Arc<ExprInfo> {
     let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
-        let ptr_to_obj = ObjectType::bool_obj_type().create_obj(gc, Some(val.to_string().as_str()));
+        let ptr_to_obj = ObjectType::bool_obj_type().create_obj(gc, val.to_string().as_str());
         let value = gc.context.i8_type().const_int(val as u64, false);
         gc.store_obj_field(ptr_to_obj, bool_type(gc.context), 1, value);
         ptr_to_obj
     });
-    lit(generator, vec![], val.to_string())
+    typed_lit(generator, vec![], val.to_string(), bool_lit_ty())
+}
+
+pub fn string(val: &str) -> Arc<ExprInfo> {
+    let val = String::from(val);
+    let name = format!("\"{}\"", val);
+    let name_cloned = name.clone();
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // String = [ControlBlock, StringField] where StringField = [Size, PtrToBuffer].
+        let string_ptr_ty = ptr_type(ObjectType::string_type().to_struct_type(gc.context));
+        let string = ObjectType::string_type().create_obj(gc, name_cloned.as_str());
+        let string = gc.cast_pointer(string, string_ptr_ty);
+        let string_field = gc
+            .builder()
+            .build_struct_gep(string, 1, "string_field")
+            .unwrap();
+        let size = gc.context.i64_type().const_int(val.len() as u64, false);
+        let buffer = ObjectFieldType::allocate_string_buffer(gc, string_field, size);
+        let src = gc
+            .builder()
+            .build_global_string_ptr(val.as_str(), "string_literal")
+            .as_pointer_value();
+        let src = gc.cast_pointer(src, gc.context.i8_type().ptr_type(AddressSpace::Generic));
+        gc.builder().build_memcpy(buffer, 1, src, 1, size).unwrap();
+        string
+    });
+    typed_lit(generator, vec![], name, string_lit_ty())
 }
 
-fn add_lit(lhs: &str, rhs: &str) -> Arc<ExprInfo> {
+// Implementation of built-in functions of binary operations on integers.
+// NOTE: Integer arithmetic wraps around on overflow (two's complement).
+// Instructions are built without nsw / nuw flags so that overflow is well-defined (not poison).
+// If check_zero is true, rhs is a divisor, and the program aborts if it is zero,
+// or if lhs is the minimum and rhs is -1 (whose quotient overflows, and sdiv / srem of which is undefined).
+fn int_binop_lit(
+    func_name: &str,
+    build: for<'c> fn(&Builder<'c>, IntValue<'c>, IntValue<'c>) -> IntValue<'c>,
+    check_zero: bool,
+    lhs: &str,
+    rhs: &str,
+) -> Arc<ExprInfo> {
     let lhs_str = String::from(lhs);
     let rhs_str = String::from(rhs);
     let free_vars = vec![lhs_str.clone(), rhs_str.clone()];
-    let name = format!("add {} {}", lhs, rhs);
+    let name = format!("{} {} {}", func_name, lhs, rhs);
     let name_cloned = name.clone();
     let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
         let lhs_val = gc
@@ -35,25 +82,242 @@ fn add_lit(lhs: &str, rhs: &str) -> Arc<ExprInfo> {
         let rhs_val = gc
             .scope_get_field(&rhs_str, 1, int_type(gc.context))
             .into_int_value();
-        let value = gc.builder().build_int_add(lhs_val, rhs_val, "add");
-        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, Some(name_cloned.as_str()));
-        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, value);
         gc.release(gc.scope_get(&lhs_str).ptr);
         gc.release(gc.scope_get(&rhs_str).ptr);
+        if check_zero {
+            let is_zero = gc.builder().build_int_compare(
+                IntPredicate::EQ,
+                rhs_val,
+                rhs_val.get_type().const_zero(),
+                "is_zero",
+            );
+            panic_if(gc, is_zero, "Division by zero!\n");
+            let int_ty = lhs_val.get_type();
+            let is_lhs_min = gc.builder().build_int_compare(
+                IntPredicate::EQ,
+                lhs_val,
+                int_ty.const_int(i64::MIN as u64, false),
+                "is_lhs_min",
+            );
+            let is_rhs_minus_one = gc.builder().build_int_compare(
+                IntPredicate::EQ,
+                rhs_val,
+                int_ty.const_all_ones(),
+                "is_rhs_minus_one",
+            );
+            let is_overflow = gc
+                .builder()
+                .build_and(is_lhs_min, is_rhs_minus_one, "is_overflow");
+            panic_if(gc, is_overflow, "Overflow in division!\n");
+        }
+        let value = build(&gc.builder(), lhs_val, rhs_val);
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, value);
         ptr_to_int_obj
     });
     lit(generator, free_vars, name)
 }
 
 pub fn add() -> Arc<ExprInfo> {
-    lam(var_var("lhs"), lam(var_var("rhs"), add_lit("lhs", "rhs")))
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_binop_lit(
+                "add",
+                |builder, lhs, rhs| builder.build_int_add(lhs, rhs, "add"),
+                false,
+                "lhs",
+                "rhs",
+            ),
+        ),
+    )
+}
+
+pub fn sub() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_binop_lit(
+                "sub",
+                |builder, lhs, rhs| builder.build_int_sub(lhs, rhs, "sub"),
+                false,
+                "lhs",
+                "rhs",
+            ),
+        ),
+    )
+}
+
+fn neg_lit(val: &str) -> Arc<ExprInfo> {
+    let val_str = String::from(val);
+    let free_vars = vec![val_str.clone()];
+    let name = format!("neg {}", val);
+    let name_cloned = name.clone();
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let val = gc
+            .scope_get_field(&val_str, 1, int_type(gc.context))
+            .into_int_value();
+        let value = gc.builder().build_int_neg(val, "neg");
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, value);
+        gc.release(gc.scope_get(&val_str).ptr);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+pub fn neg() -> Arc<ExprInfo> {
+    lam(var_var("val"), neg_lit("val"))
+}
+
+pub fn mul() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_binop_lit(
+                "mul",
+                |builder, lhs, rhs| builder.build_int_mul(lhs, rhs, "mul"),
+                false,
+                "lhs",
+                "rhs",
+            ),
+        ),
+    )
+}
+
+// Division rounds toward zero. Division by zero aborts the program.
+pub fn div() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_binop_lit(
+                "div",
+                |builder, lhs, rhs| builder.build_int_signed_div(lhs, rhs, "div"),
+                true,
+                "lhs",
+                "rhs",
+            ),
+        ),
+    )
+}
+
+// The remainder has the sign of lhs (as `%` of C and Rust), e.g., `mod -17 5` is -2.
+// Modulo by zero (and the minimum modulo -1, as div) aborts the program.
+pub fn mod_() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_binop_lit(
+                "mod",
+                |builder, lhs, rhs| builder.build_int_signed_rem(lhs, rhs, "mod"),
+                true,
+                "lhs",
+                "rhs",
+            ),
+        ),
+    )
+}
+
+// Power by square-and-multiply (wrapping on overflow). A negative exponent is treated as zero, so the result is 1.
+fn pow_lit(base: &str, exp: &str) -> Arc<ExprInfo> {
+    let base_str = String::from(base);
+    let exp_str = String::from(exp);
+    let free_vars = vec![base_str.clone(), exp_str.clone()];
+    let name = format!("pow {} {}", base, exp);
+    let name_cloned = name.clone();
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let base_val = gc
+            .scope_get_field(&base_str, 1, int_type(gc.context))
+            .into_int_value();
+        let exp_val = gc
+            .scope_get_field(&exp_str, 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(&base_str).ptr);
+        gc.release(gc.scope_get(&exp_str).ptr);
+
+        // The loop state (accumulator, power of base and remaining exponent) is kept in phi nodes, so no object is allocated in the loop.
+        let i64_type = gc.context.i64_type();
+        let pre_bb = gc.builder().get_insert_block().unwrap();
+        let curr_func = pre_bb.get_parent().unwrap();
+        let loop_check_bb = gc.context.append_basic_block(curr_func, "pow_loop_check");
+        let loop_body_bb = gc.context.append_basic_block(curr_func, "pow_loop_body");
+        let after_loop_bb = gc.context.append_basic_block(curr_func, "pow_after_loop");
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        // Loop while the remaining exponent is positive.
+        gc.builder().position_at_end(loop_check_bb);
+        let acc = gc.builder().build_phi(i64_type, "pow_acc");
+        let power = gc.builder().build_phi(i64_type, "pow_power");
+        let rest = gc.builder().build_phi(i64_type, "pow_rest");
+        let acc_val = acc.as_basic_value().into_int_value();
+        let power_val = power.as_basic_value().into_int_value();
+        let rest_val = rest.as_basic_value().into_int_value();
+        let is_end = gc.builder().build_int_compare(
+            IntPredicate::SLE,
+            rest_val,
+            i64_type.const_zero(),
+            "is_end",
+        );
+        gc.builder()
+            .build_conditional_branch(is_end, after_loop_bb, loop_body_bb);
+
+        // Multiply accumulator by the power if the lowest bit of the remaining exponent is set, square the power and shift the exponent.
+        gc.builder().position_at_end(loop_body_bb);
+        let one = i64_type.const_int(1, false);
+        let bit = gc.builder().build_and(rest_val, one, "pow_bit");
+        let is_odd =
+            gc.builder()
+                .build_int_compare(IntPredicate::NE, bit, i64_type.const_zero(), "is_odd");
+        let multiplied = gc.builder().build_int_mul(acc_val, power_val, "pow_mul");
+        let next_acc = gc
+            .builder()
+            .build_select(is_odd, multiplied, acc_val, "pow_next_acc")
+            .into_int_value();
+        let next_power = gc
+            .builder()
+            .build_int_mul(power_val, power_val, "pow_next_power");
+        let next_rest = gc
+            .builder()
+            .build_right_shift(rest_val, one, false, "pow_next_rest");
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        acc.add_incoming(&[
+            (&one as &dyn BasicValue, pre_bb),
+            (&next_acc as &dyn BasicValue, loop_body_bb),
+        ]);
+        power.add_incoming(&[
+            (&base_val as &dyn BasicValue, pre_bb),
+            (&next_power as &dyn BasicValue, loop_body_bb),
+        ]);
+        rest.add_incoming(&[
+            (&exp_val as &dyn BasicValue, pre_bb),
+            (&next_rest as &dyn BasicValue, loop_body_bb),
+        ]);
+
+        gc.builder().position_at_end(after_loop_bb);
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, acc_val);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+pub fn pow() -> Arc<ExprInfo> {
+    lam(var_var("base"), lam(var_var("exp"), pow_lit("base", "exp")))
 }
 
-fn eq_lit(lhs: &str, rhs: &str) -> Arc<ExprInfo> {
+// Implementation of built-in functions comparing two integers.
+fn int_cmp_lit(func_name: &str, pred: IntPredicate, lhs: &str, rhs: &str) -> Arc<ExprInfo> {
     let lhs_str = String::from(lhs);
     let rhs_str = String::from(rhs);
-    let name = format!("eq {} {}", lhs, rhs);
+    let name = format!("{} {} {}", func_name, lhs, rhs);
     let name_cloned = name.clone();
+    let func_name = String::from(func_name);
     let free_vars = vec![lhs_str.clone(), rhs_str.clone()];
     let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
         let lhs_val = gc
@@ -64,15 +328,15 @@ fn eq_lit(lhs: &str, rhs: &str) -> Arc<ExprInfo> {
             .into_int_value();
         let value = gc
             .builder()
-            .build_int_compare(IntPredicate::EQ, lhs_val, rhs_val, "eq");
+            .build_int_compare(pred, lhs_val, rhs_val, &func_name);
         let value = gc.builder().build_int_cast(
             value,
             ObjectFieldType::Bool
                 .to_basic_type(gc.context)
                 .into_int_type(),
-            "eq_bool",
+            &format!("{}_bool", func_name),
         );
-        let ptr_to_obj = ObjectType::bool_obj_type().create_obj(gc, Some(name_cloned.as_str()));
+        let ptr_to_obj = ObjectType::bool_obj_type().create_obj(gc, name_cloned.as_str());
         gc.store_obj_field(ptr_to_obj, bool_type(gc.context), 1, value);
         gc.release(gc.scope_get(&lhs_str).ptr);
         gc.release(gc.scope_get(&rhs_str).ptr);
@@ -82,7 +346,53 @@ fn eq_lit(lhs: &str, rhs: &str) -> Arc<ExprInfo> {
 }
 
 pub fn eq() -> Arc<ExprInfo> {
-    lam(var_var("lhs"), lam(var_var("rhs"), eq_lit("lhs", "rhs")))
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_cmp_lit("eq", IntPredicate::EQ, "lhs", "rhs"),
+        ),
+    )
+}
+
+pub fn lt() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_cmp_lit("lt", IntPredicate::SLT, "lhs", "rhs"),
+        ),
+    )
+}
+
+pub fn le() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_cmp_lit("le", IntPredicate::SLE, "lhs", "rhs"),
+        ),
+    )
+}
+
+pub fn gt() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_cmp_lit("gt", IntPredicate::SGT, "lhs", "rhs"),
+        ),
+    )
+}
+
+pub fn ge() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(
+            var_var("rhs"),
+            int_cmp_lit("ge", IntPredicate::SGE, "lhs", "rhs"),
+        ),
+    )
 }
 
 fn fix_lit(f: &str, x: &str) -> Arc<ExprInfo> {
@@ -119,7 +429,7 @@ fn new_array_lit(size: &str, value: &str) -> Arc<ExprInfo> {
             .into_int_value();
         gc.release(gc.scope_get(&size_str).ptr);
         let value = gc.scope_get(&value_str).ptr;
-        let array = ObjectType::array_type().create_obj(gc, Some(name_cloned.as_str()));
+        let array = ObjectType::array_type().create_obj(gc, name_cloned.as_str());
         let array_ptr_ty = ptr_type(ObjectType::array_type().to_struct_type(gc.context));
         let array = gc.cast_pointer(array, array_ptr_ty);
         let array_field = gc
@@ -174,6 +484,74 @@ pub fn read_array() -> Arc<ExprInfo> {
     )
 }
 
+// Get an array which is not shared with others and its array field, cloning the given array if it is shared.
+// The given array is consumed (released if cloned).
+// panic_if_shared - if true, generate code that calls abort when given array is shared.
+fn make_array_unique<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    array: PointerValue<'c>,
+    name: &str,
+    func_name: &str,
+    panic_if_shared: bool,
+) -> (PointerValue<'c>, PointerValue<'c>) {
+    // Get array field.
+    let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+    let array = gc.cast_pointer(array, ptr_type(array_str_ty));
+    let array_field = gc.builder().build_struct_gep(array, 1, "").unwrap();
+
+    // Get refcnt.
+    let refcnt = gc
+        .load_obj_field(array, control_block_type(gc.context), 0)
+        .into_int_value();
+
+    // Add unique / shared / cont bbs.
+    let current_bb = gc.builder().get_insert_block().unwrap();
+    let current_func = current_bb.get_parent().unwrap();
+    let shared_bb = gc.context.append_basic_block(current_func, "shared_bb");
+    let cont_bb = gc.context.append_basic_block(current_func, "cont_bb");
+
+    // Jump to shared_bb if refcnt > 1.
+    let one = refcnt_type(gc.context).const_int(1, false);
+    let is_unique = gc
+        .builder()
+        .build_int_compare(IntPredicate::EQ, refcnt, one, "is_unique");
+    gc.builder()
+        .build_conditional_branch(is_unique, cont_bb, shared_bb);
+
+    // In shared_bb, create new array and clone array field.
+    gc.builder().position_at_end(shared_bb);
+    if panic_if_shared {
+        // In case of unique version, panic in this case.
+        gc.panic(format!("The argument of {} is shared!\n", func_name).as_str());
+    }
+    let cloned_array = ObjectType::array_type().create_obj(gc, name);
+    let cloned_array = gc.cast_pointer(cloned_array, ptr_type(array_str_ty));
+    let cloned_array_field = gc.builder().build_struct_gep(cloned_array, 1, "").unwrap();
+    ObjectFieldType::clone_array(gc, array_field, cloned_array_field);
+    gc.release(array); // Given array should be released here.
+    let succ_of_shared_bb = gc.builder().get_insert_block().unwrap();
+    gc.builder().build_unconditional_branch(cont_bb);
+
+    // Implement cont_bb
+    gc.builder().position_at_end(cont_bb);
+
+    // Build phi value of array and array_field.
+    let array_phi = gc.builder().build_phi(array.get_type(), "array_phi");
+    assert_eq!(array.get_type(), cloned_array.get_type());
+    array_phi.add_incoming(&[(&array, current_bb), (&cloned_array, succ_of_shared_bb)]);
+    let array = array_phi.as_basic_value().into_pointer_value();
+    let array_field_phi = gc
+        .builder()
+        .build_phi(array_field.get_type(), "array_field_phi");
+    assert_eq!(array_field.get_type(), cloned_array_field.get_type());
+    array_field_phi.add_incoming(&[
+        (&array_field, current_bb),
+        (&cloned_array_field, succ_of_shared_bb),
+    ]);
+    let array_field = array_field_phi.as_basic_value().into_pointer_value();
+    (array, array_field)
+}
+
 // Implementation of writeArray / writeArray! built-in function.
 // is_unique_mode - if true, generate code that calls abort when given array is shared.
 fn write_array_lit(array: &str, idx: &str, value: &str, is_unique_version: bool) -> Arc<ExprInfo> {
@@ -201,61 +579,14 @@ fn write_array_lit(array: &str, idx: &str, value: &str, is_unique_version: bool)
         gc.release(gc.scope_get(idx_str.as_str()).ptr);
         let value = gc.scope_get(value_str.as_str()).ptr;
 
-        // Get array field.
-        let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
-        let array = gc.cast_pointer(array, ptr_type(array_str_ty));
-        let array_field = gc.builder().build_struct_gep(array, 1, "").unwrap();
-
-        // Get refcnt.
-        let refcnt = gc
-            .load_obj_field(array, control_block_type(gc.context), 0)
-            .into_int_value();
-
-        // Add unique / shared / cont bbs.
-        let current_bb = gc.builder().get_insert_block().unwrap();
-        let current_func = current_bb.get_parent().unwrap();
-        let shared_bb = gc.context.append_basic_block(current_func, "shared_bb");
-        let cont_bb = gc.context.append_basic_block(current_func, "cont_bb");
-
-        // Jump to shared_bb if refcnt > 1.
-        let one = refcnt_type(gc.context).const_int(1, false);
-        let is_unique = gc
-            .builder()
-            .build_int_compare(IntPredicate::EQ, refcnt, one, "is_unique");
-        gc.builder()
-            .build_conditional_branch(is_unique, cont_bb, shared_bb);
-
-        // In shared_bb, create new array and clone array field.
-        gc.builder().position_at_end(shared_bb);
-        if is_unique_version {
-            // In case of unique version, panic in this case.
-            gc.panic(format!("The argument of {} is shared!\n", func_name.as_str()).as_str());
-        }
-        let cloned_array = ObjectType::array_type().create_obj(gc, Some(name_cloned.as_str()));
-        let cloned_array = gc.cast_pointer(cloned_array, ptr_type(array_str_ty));
-        let cloned_array_field = gc.builder().build_struct_gep(cloned_array, 1, "").unwrap();
-        ObjectFieldType::clone_array(gc, array_field, cloned_array_field);
-        gc.release(array); // Given array should be released here.
-        let succ_of_shared_bb = gc.builder().get_insert_block().unwrap();
-        gc.builder().build_unconditional_branch(cont_bb);
-
-        // Implement cont_bb
-        gc.builder().position_at_end(cont_bb);
-
-        // Build phi value of array and array_field.
-        let array_phi = gc.builder().build_phi(array.get_type(), "array_phi");
-        assert_eq!(array.get_type(), cloned_array.get_type());
-        array_phi.add_incoming(&[(&array, current_bb), (&cloned_array, succ_of_shared_bb)]);
-        let array = array_phi.as_basic_value().into_pointer_value();
-        let array_field_phi = gc
-            .builder()
-            .build_phi(array_field.get_type(), "array_field_phi");
-        assert_eq!(array_field.get_type(), cloned_array_field.get_type());
-        array_field_phi.add_incoming(&[
-            (&array_field, current_bb),
-            (&cloned_array_field, succ_of_shared_bb),
-        ]);
-        let array_field = array_field_phi.as_basic_value().into_pointer_value();
+        // Clone array if it is shared.
+        let (array, array_field) = make_array_unique(
+            gc,
+            array,
+            name_cloned.as_str(),
+            func_name.as_str(),
+            is_unique_version,
+        );
 
         // Perform write and return.
         ObjectFieldType::write_array(gc, array_field, idx, value);
@@ -291,3 +622,2183 @@ pub fn write_array_unique() -> Arc<ExprInfo> {
         ),
     )
 }
+
+// Implementation of modifyArray built-in function.
+fn modify_array_lit(array: &str, idx: &str, func: &str) -> Arc<ExprInfo> {
+    let array_str = String::from(array);
+    let idx_str = String::from(idx);
+    let func_str = String::from(func);
+    let name = format!("modifyArray {} {} {}", array, idx, func);
+    let name_cloned = name.clone();
+    let free_vars = vec![array_str.clone(), idx_str.clone(), func_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get argments
+        let array = gc.scope_get(array_str.as_str()).ptr;
+        let idx = gc
+            .scope_get_field(idx_str.as_str(), 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(idx_str.as_str()).ptr);
+        let func = gc.scope_get(func_str.as_str()).ptr;
+
+        // Clone array if it is shared.
+        let (array, array_field) =
+            make_array_unique(gc, array, name_cloned.as_str(), "modifyArray", false);
+
+        // Get ptr to the place at idx.
+        ObjectFieldType::panic_if_out_of_array(gc, array_field, idx);
+        let (_size, ptr_to_buffer) = ObjectFieldType::get_size_and_buffer_of_array(gc, array_field);
+        let place = unsafe {
+            gc.builder()
+                .build_gep(ptr_to_buffer, &[idx.into()], "ptr_to_elem_of_array")
+        };
+
+        // Move the element out to func and store the result in place of it.
+        // Since the array owns the element, no retain / release is needed.
+        let elem = gc.builder().build_load(place, "elem").into_pointer_value();
+        let new_elem = gc.apply_lambda(func, elem);
+        gc.builder().build_store(place, new_elem);
+        array
+    });
+    lit(generator, free_vars, name)
+}
+
+// modifyArray built-in function.
+pub fn modify_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("array"),
+        lam(
+            var_var("idx"),
+            lam(var_var("func"), modify_array_lit("array", "idx", "func")),
+        ),
+    )
+}
+
+// Implementation of eqArray built-in function.
+fn eq_array_lit(lhs: &str, rhs: &str) -> Arc<ExprInfo> {
+    let lhs_str = String::from(lhs);
+    let rhs_str = String::from(rhs);
+    let name = format!("eqArray {} {}", lhs, rhs);
+    let name_cloned = name.clone();
+    let free_vars = vec![lhs_str.clone(), rhs_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Array = [ControlBlock, ArrayField] where ArrayField = [Size, PtrToBuffer].
+        let array_ptr_ty = ptr_type(ObjectType::array_type().to_struct_type(gc.context));
+        let lhs = gc.scope_get(lhs_str.as_str()).ptr;
+        let lhs = gc.cast_pointer(lhs, array_ptr_ty);
+        let lhs_field = gc
+            .builder()
+            .build_struct_gep(lhs, 1, "lhs_array_field")
+            .unwrap();
+        let rhs = gc.scope_get(rhs_str.as_str()).ptr;
+        let rhs = gc.cast_pointer(rhs, array_ptr_ty);
+        let rhs_field = gc
+            .builder()
+            .build_struct_gep(rhs, 1, "rhs_array_field")
+            .unwrap();
+        let value = ObjectFieldType::eq_array(gc, lhs_field, rhs_field);
+        let ptr_to_obj = ObjectType::bool_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_obj, bool_type(gc.context), 1, value);
+        gc.release(lhs);
+        gc.release(rhs);
+        ptr_to_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// eqArray built-in function.
+pub fn eq_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(var_var("rhs"), eq_array_lit("lhs", "rhs")),
+    )
+}
+
+// Implementation of loop built-in function.
+// The state is updated by `step` while `cond` holds. This is generated as a loop in LLVM IR (not as a recursion), so the stack usage is constant.
+fn loop_lit(init: &str, cond: &str, step: &str) -> Arc<ExprInfo> {
+    let init_str = String::from(init);
+    let cond_str = String::from(cond);
+    let step_str = String::from(step);
+    let name = format!("loop {} {} {}", init, cond, step);
+    let free_vars = vec![init_str.clone(), cond_str.clone(), step_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get arguments.
+        let init = gc.scope_get(init_str.as_str()).ptr;
+        let cond = gc.scope_get(cond_str.as_str()).ptr;
+        let step = gc.scope_get(step_str.as_str()).ptr;
+
+        // Append blocks: loop_check, loop_body and after_loop.
+        let init_bb = gc.builder().get_insert_block().unwrap();
+        let current_func = init_bb.get_parent().unwrap();
+        let loop_check_bb = gc.context.append_basic_block(current_func, "loop_check");
+        let loop_body_bb = gc.context.append_basic_block(current_func, "loop_body");
+        let after_loop_bb = gc.context.append_basic_block(current_func, "after_loop");
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        // Implement loop_check bb: evaluate `cond state`.
+        gc.builder().position_at_end(loop_check_bb);
+        let state_phi = gc
+            .builder()
+            .build_phi(ptr_to_object_type(gc.context), "state_phi");
+        let state = state_phi.as_basic_value().into_pointer_value();
+        gc.retain(cond);
+        gc.retain(state);
+        let cond_obj = gc.apply_lambda(cond, state);
+        let cond_val = gc
+            .load_obj_field(cond_obj, bool_type(gc.context), 1)
+            .into_int_value();
+        gc.release(cond_obj);
+        let cond_val =
+            gc.builder()
+                .build_int_cast(cond_val, gc.context.bool_type(), "cond_val_i1");
+        gc.builder()
+            .build_conditional_branch(cond_val, loop_body_bb, after_loop_bb);
+
+        // Implement loop_body bb: update state by `step state`.
+        gc.builder().position_at_end(loop_body_bb);
+        gc.retain(step);
+        let next_state = gc.apply_lambda(step, state);
+        let loop_body_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(loop_check_bb);
+        state_phi.add_incoming(&[(&init, init_bb), (&next_state, loop_body_end_bb)]);
+
+        // After loop, release closures and return the state.
+        gc.builder().position_at_end(after_loop_bb);
+        gc.release(cond);
+        gc.release(step);
+        state
+    });
+    lit(generator, free_vars, name)
+}
+
+// loop built-in function.
+pub fn loop_() -> Arc<ExprInfo> {
+    lam(
+        var_var("init"),
+        lam(
+            var_var("cond"),
+            lam(var_var("step"), loop_lit("init", "cond", "step")),
+        ),
+    )
+}
+
+// Create an array object holding elements in range [begin, end) of a given array field.
+fn create_sliced_array<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    array_field: PointerValue<'c>,
+    begin: IntValue<'c>,
+    end: IntValue<'c>,
+    name: &str,
+) -> PointerValue<'c> {
+    let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+    let sliced = ObjectType::array_type().create_obj(gc, name);
+    let sliced = gc.cast_pointer(sliced, ptr_type(array_str_ty));
+    let sliced_field = gc
+        .builder()
+        .build_struct_gep(sliced, 1, "sliced_array_field")
+        .unwrap();
+    ObjectFieldType::slice_array(gc, array_field, sliced_field, begin, end);
+    sliced
+}
+
+// Kinds of built-in functions slicing an array.
+#[derive(Clone, Copy, PartialEq)]
+enum SliceArrayMode {
+    Take,
+    Drop,
+    SplitAt,
+}
+
+// Implementation of takeArray / dropArray / splitAtArray built-in function.
+// Index n is clamped into [0, size of array].
+fn slice_array_lit(n: &str, array: &str, mode: SliceArrayMode) -> Arc<ExprInfo> {
+    let n_str = String::from(n);
+    let array_str = String::from(array);
+    let func_name = match mode {
+        SliceArrayMode::Take => "takeArray",
+        SliceArrayMode::Drop => "dropArray",
+        SliceArrayMode::SplitAt => "splitAtArray",
+    };
+    let name = format!("{} {} {}", func_name, n, array);
+    let name_cloned = name.clone();
+    let free_vars = vec![n_str.clone(), array_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get arguments.
+        let n = gc
+            .scope_get_field(n_str.as_str(), 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(n_str.as_str()).ptr);
+        let array = gc.scope_get(array_str.as_str()).ptr;
+
+        // Get array field.
+        let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+        let array = gc.cast_pointer(array, ptr_type(array_str_ty));
+        let array_field = gc.builder().build_struct_gep(array, 1, "").unwrap();
+
+        // Calculate range.
+        let zero = gc.context.i64_type().const_zero();
+        let (size, _buffer) = ObjectFieldType::get_size_and_buffer_of_array(gc, array_field);
+        let n = ObjectFieldType::clamp_index_of_array(gc, array_field, n);
+
+        // Create arrays.
+        let ret = match mode {
+            SliceArrayMode::Take => {
+                create_sliced_array(gc, array_field, zero, n, name_cloned.as_str())
+            }
+            SliceArrayMode::Drop => {
+                create_sliced_array(gc, array_field, n, size, name_cloned.as_str())
+            }
+            SliceArrayMode::SplitAt => {
+                let taken = create_sliced_array(gc, array_field, zero, n, "takeArray");
+                let dropped = create_sliced_array(gc, array_field, n, size, "dropArray");
+                let pair = ObjectType::pair_type().create_obj(gc, name_cloned.as_str());
+                let pair_str_ty = ObjectType::pair_type().to_struct_type(gc.context);
+                gc.store_obj_field(pair, pair_str_ty, 1, taken);
+                gc.store_obj_field(pair, pair_str_ty, 2, dropped);
+                pair
+            }
+        };
+        gc.release(array);
+        ret
+    });
+    lit(generator, free_vars, name)
+}
+
+// takeArray built-in function.
+pub fn take_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("n"),
+        lam(
+            var_var("array"),
+            slice_array_lit("n", "array", SliceArrayMode::Take),
+        ),
+    )
+}
+
+// dropArray built-in function.
+pub fn drop_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("n"),
+        lam(
+            var_var("array"),
+            slice_array_lit("n", "array", SliceArrayMode::Drop),
+        ),
+    )
+}
+
+// splitAtArray built-in function.
+pub fn split_at_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("n"),
+        lam(
+            var_var("array"),
+            slice_array_lit("n", "array", SliceArrayMode::SplitAt),
+        ),
+    )
+}
+
+// Implementation of pair built-in function.
+fn pair_lit(first: &str, second: &str) -> Arc<ExprInfo> {
+    let first_str = String::from(first);
+    let second_str = String::from(second);
+    let name = format!("pair {} {}", first, second);
+    let name_cloned = name.clone();
+    let free_vars = vec![first_str.clone(), second_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let first = gc.scope_get(first_str.as_str()).ptr;
+        let second = gc.scope_get(second_str.as_str()).ptr;
+        let pair = ObjectType::pair_type().create_obj(gc, name_cloned.as_str());
+        let pair_str_ty = ObjectType::pair_type().to_struct_type(gc.context);
+        gc.store_obj_field(pair, pair_str_ty, 1, first);
+        gc.store_obj_field(pair, pair_str_ty, 2, second);
+        pair
+    });
+    lit(generator, free_vars, name)
+}
+
+// pair built-in function.
+pub fn pair() -> Arc<ExprInfo> {
+    lam(
+        var_var("first"),
+        lam(var_var("second"), pair_lit("first", "second")),
+    )
+}
+
+// Implementation of fst / snd built-in function.
+fn pair_elem_lit(pair: &str, field_idx: u32) -> Arc<ExprInfo> {
+    let pair_str = String::from(pair);
+    let func_name = if field_idx == 1 { "fst" } else { "snd" };
+    let name = format!("{} {}", func_name, pair);
+    let free_vars = vec![pair_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let pair_str_ty = ObjectType::pair_type().to_struct_type(gc.context);
+        let pair = gc.scope_get(pair_str.as_str()).ptr;
+        let elem = gc
+            .load_obj_field(pair, pair_str_ty, field_idx)
+            .into_pointer_value();
+        gc.retain(elem);
+        gc.release(pair);
+        elem
+    });
+    lit(generator, free_vars, name)
+}
+
+// fst built-in function.
+pub fn fst() -> Arc<ExprInfo> {
+    lam(var_var("pair"), pair_elem_lit("pair", 1))
+}
+
+// snd built-in function.
+pub fn snd() -> Arc<ExprInfo> {
+    lam(var_var("pair"), pair_elem_lit("pair", 2))
+}
+
+// firstP built-in function: \f -> \p -> pair (f (fst p)) (snd p).
+pub fn first_p() -> Arc<ExprInfo> {
+    lam(
+        var_var("f"),
+        lam(
+            var_var("p"),
+            app(
+                app(pair(), app(var("f"), app(fst(), var("p")))),
+                app(snd(), var("p")),
+            ),
+        ),
+    )
+}
+
+// secondP built-in function: \f -> \p -> pair (fst p) (f (snd p)).
+pub fn second_p() -> Arc<ExprInfo> {
+    lam(
+        var_var("f"),
+        lam(
+            var_var("p"),
+            app(
+                app(pair(), app(fst(), var("p"))),
+                app(var("f"), app(snd(), var("p"))),
+            ),
+        ),
+    )
+}
+
+// Implementation of lenArray built-in function.
+fn len_array_lit(array: &str) -> Arc<ExprInfo> {
+    let array_str = String::from(array);
+    let name = format!("lenArray {}", array);
+    let name_cloned = name.clone();
+    let free_vars = vec![array_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Array = [ControlBlock, ArrayField] where ArrayField = [Size, PtrToBuffer].
+        let array_ptr_ty = ptr_type(ObjectType::array_type().to_struct_type(gc.context));
+        let array = gc.scope_get(array_str.as_str()).ptr;
+        let array = gc.cast_pointer(array, array_ptr_ty);
+        let array_field = gc
+            .builder()
+            .build_struct_gep(array, 1, "array_field")
+            .unwrap();
+        let (size, _buffer) = ObjectFieldType::get_size_and_buffer_of_array(gc, array_field);
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, size);
+        gc.release(array);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// lenArray built-in function.
+pub fn len_array() -> Arc<ExprInfo> {
+    lam(var_var("array"), len_array_lit("array"))
+}
+
+// Create an option object holding a given value (or absent if None).
+// The value is moved into the option.
+fn create_option<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    value: Option<PointerValue<'c>>,
+    name: &str,
+) -> PointerValue<'c> {
+    let option_str_ty = ObjectType::option_type().to_struct_type(gc.context);
+    let option = ObjectType::option_type().create_obj(gc, name);
+    let value = match value {
+        Some(value) => value,
+        None => ptr_to_object_type(gc.context).const_null(),
+    };
+    gc.store_obj_field(option, option_str_ty, 1, value);
+    gc.cast_pointer(option, ptr_to_object_type(gc.context))
+}
+
+// none built-in value.
+pub fn none() -> Arc<ExprInfo> {
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| create_option(gc, None, "none"));
+    lit(generator, vec![], String::from("none"))
+}
+
+// Implementation of some built-in function.
+fn some_lit(value: &str) -> Arc<ExprInfo> {
+    let value_str = String::from(value);
+    let name = format!("some {}", value);
+    let name_cloned = name.clone();
+    let free_vars = vec![value_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let value = gc.scope_get(value_str.as_str()).ptr;
+        create_option(gc, Some(value), name_cloned.as_str())
+    });
+    lit(generator, free_vars, name)
+}
+
+// some built-in function.
+pub fn some() -> Arc<ExprInfo> {
+    lam(var_var("value"), some_lit("value"))
+}
+
+// Implementation of isSome built-in function.
+fn is_some_lit(option: &str) -> Arc<ExprInfo> {
+    let option_str = String::from(option);
+    let name = format!("isSome {}", option);
+    let name_cloned = name.clone();
+    let free_vars = vec![option_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let option_str_ty = ObjectType::option_type().to_struct_type(gc.context);
+        let option = gc.scope_get(option_str.as_str()).ptr;
+        let value = gc
+            .load_obj_field(option, option_str_ty, 1)
+            .into_pointer_value();
+        let is_some = gc.builder().build_is_not_null(value, "is_some");
+        let is_some = gc.builder().build_int_z_extend(
+            is_some,
+            ObjectFieldType::Bool
+                .to_basic_type(gc.context)
+                .into_int_type(),
+            "is_some_bool",
+        );
+        let ptr_to_obj = ObjectType::bool_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_obj, bool_type(gc.context), 1, is_some);
+        gc.release(option);
+        ptr_to_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// isSome built-in function.
+pub fn is_some() -> Arc<ExprInfo> {
+    lam(var_var("option"), is_some_lit("option"))
+}
+
+// Implementation of unwrap built-in function.
+fn unwrap_lit(option: &str) -> Arc<ExprInfo> {
+    let option_str = String::from(option);
+    let name = format!("unwrap {}", option);
+    let free_vars = vec![option_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let option_str_ty = ObjectType::option_type().to_struct_type(gc.context);
+        let option = gc.scope_get(option_str.as_str()).ptr;
+        let value = gc
+            .load_obj_field(option, option_str_ty, 1)
+            .into_pointer_value();
+
+        // Panic if the value is absent.
+        let is_null = gc.builder().build_is_null(value, "is_null");
+        let curr_bb = gc.builder().get_insert_block().unwrap();
+        let curr_func = curr_bb.get_parent().unwrap();
+        let none_bb = gc.context.append_basic_block(curr_func, "none_bb");
+        let some_bb = gc.context.append_basic_block(curr_func, "some_bb");
+        gc.builder()
+            .build_conditional_branch(is_null, none_bb, some_bb);
+        gc.builder().position_at_end(none_bb);
+        gc.panic("unwrap is called on none!\n");
+        gc.builder().build_unreachable();
+
+        // Retain value and release option.
+        gc.builder().position_at_end(some_bb);
+        gc.retain(value);
+        gc.release(option);
+        value
+    });
+    lit(generator, free_vars, name)
+}
+
+// unwrap built-in function.
+pub fn unwrap() -> Arc<ExprInfo> {
+    lam(var_var("option"), unwrap_lit("option"))
+}
+
+// Implementation of fromOption built-in function.
+fn from_option_lit(default: &str, option: &str) -> Arc<ExprInfo> {
+    let default_str = String::from(default);
+    let option_str = String::from(option);
+    let name = format!("fromOption {} {}", default, option);
+    let free_vars = vec![default_str.clone(), option_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let option_str_ty = ObjectType::option_type().to_struct_type(gc.context);
+        let default = gc.scope_get(default_str.as_str()).ptr;
+        let option = gc.scope_get(option_str.as_str()).ptr;
+        let value = gc
+            .load_obj_field(option, option_str_ty, 1)
+            .into_pointer_value();
+
+        // Branch by whether the value is present.
+        let is_null = gc.builder().build_is_null(value, "is_null");
+        let curr_bb = gc.builder().get_insert_block().unwrap();
+        let curr_func = curr_bb.get_parent().unwrap();
+        let none_bb = gc.context.append_basic_block(curr_func, "none_bb");
+        let some_bb = gc.context.append_basic_block(curr_func, "some_bb");
+        let cont_bb = gc.context.append_basic_block(curr_func, "cont_bb");
+        gc.builder()
+            .build_conditional_branch(is_null, none_bb, some_bb);
+
+        // If absent, return default.
+        gc.builder().position_at_end(none_bb);
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // If present, retain value and release default.
+        gc.builder().position_at_end(some_bb);
+        gc.retain(value);
+        gc.release(default);
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // Release option and return the selected value.
+        gc.builder().position_at_end(cont_bb);
+        let ret = gc
+            .builder()
+            .build_phi(ptr_to_object_type(gc.context), "from_option_phi");
+        ret.add_incoming(&[(&default, none_bb), (&value, some_bb)]);
+        gc.release(option);
+        ret.as_basic_value().into_pointer_value()
+    });
+    lit(generator, free_vars, name)
+}
+
+// fromOption built-in function.
+pub fn from_option() -> Arc<ExprInfo> {
+    lam(
+        var_var("default"),
+        lam(var_var("option"), from_option_lit("default", "option")),
+    )
+}
+
+// Implementation of mapOption built-in function.
+fn map_option_lit(func: &str, option: &str) -> Arc<ExprInfo> {
+    let func_str = String::from(func);
+    let option_str = String::from(option);
+    let name = format!("mapOption {} {}", func, option);
+    let name_cloned = name.clone();
+    let free_vars = vec![func_str.clone(), option_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let option_str_ty = ObjectType::option_type().to_struct_type(gc.context);
+        let func = gc.scope_get(func_str.as_str()).ptr;
+        let option = gc.scope_get(option_str.as_str()).ptr;
+        let value = gc
+            .load_obj_field(option, option_str_ty, 1)
+            .into_pointer_value();
+
+        // Branch by whether the value is present.
+        let is_null = gc.builder().build_is_null(value, "is_null");
+        let curr_bb = gc.builder().get_insert_block().unwrap();
+        let curr_func = curr_bb.get_parent().unwrap();
+        let none_bb = gc.context.append_basic_block(curr_func, "none_bb");
+        let some_bb = gc.context.append_basic_block(curr_func, "some_bb");
+        let cont_bb = gc.context.append_basic_block(curr_func, "cont_bb");
+        gc.builder()
+            .build_conditional_branch(is_null, none_bb, some_bb);
+
+        // If absent, release function and return none.
+        gc.builder().position_at_end(none_bb);
+        gc.release(func);
+        gc.release(option);
+        let none = create_option(gc, None, name_cloned.as_str());
+        let none_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // If present, apply function to the value and wrap the result.
+        gc.builder().position_at_end(some_bb);
+        gc.retain(value);
+        gc.release(option);
+        let mapped = gc.apply_lambda(func, value);
+        let some = create_option(gc, Some(mapped), name_cloned.as_str());
+        let some_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // Build phi.
+        gc.builder().position_at_end(cont_bb);
+        let ret = gc
+            .builder()
+            .build_phi(ptr_to_object_type(gc.context), "option_phi");
+        ret.add_incoming(&[(&none, none_end_bb), (&some, some_end_bb)]);
+        ret.as_basic_value().into_pointer_value()
+    });
+    lit(generator, free_vars, name)
+}
+
+// mapOption built-in function.
+pub fn map_option() -> Arc<ExprInfo> {
+    lam(
+        var_var("func"),
+        lam(var_var("option"), map_option_lit("func", "option")),
+    )
+}
+
+// Implementation of makeWeak built-in function.
+fn make_weak_lit(value: &str) -> Arc<ExprInfo> {
+    let value_str = String::from(value);
+    let name = format!("makeWeak {}", value);
+    let name_cloned = name.clone();
+    let free_vars = vec![value_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let value = gc.scope_get(value_str.as_str()).ptr;
+        let weak_str_ty = ObjectType::weak_type().to_struct_type(gc.context);
+        let cell = gc.retain_weak_cell(value);
+        let weak = ObjectType::weak_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(weak, weak_str_ty, 1, cell);
+        gc.release(value);
+        weak
+    });
+    lit(generator, free_vars, name)
+}
+
+// makeWeak built-in function, which makes a weak reference to an object.
+// A weak reference doesn't keep the object alive, so it can be used to break reference cycles.
+pub fn make_weak() -> Arc<ExprInfo> {
+    lam(var_var("value"), make_weak_lit("value"))
+}
+
+// Implementation of derefWeak built-in function.
+fn deref_weak_lit(weak: &str) -> Arc<ExprInfo> {
+    let weak_str = String::from(weak);
+    let name = format!("derefWeak {}", weak);
+    let name_cloned = name.clone();
+    let free_vars = vec![weak_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let weak_str_ty = ObjectType::weak_type().to_struct_type(gc.context);
+        let weak = gc.scope_get(weak_str.as_str()).ptr;
+        let cell = gc.load_obj_field(weak, weak_str_ty, 1).into_pointer_value();
+        let value = gc
+            .load_obj_field(cell, weak_cell_type(gc.context), 2)
+            .into_pointer_value();
+
+        // Branch by whether the object is alive, i.e., the weak cell is not expired.
+        let is_alive = gc.builder().build_is_not_null(value, "is_alive");
+        let current_bb = gc.builder().get_insert_block().unwrap();
+        let current_func = current_bb.get_parent().unwrap();
+        let alive_bb = gc.context.append_basic_block(current_func, "alive_bb");
+        let expired_bb = gc.context.append_basic_block(current_func, "expired_bb");
+        let cont_bb = gc.context.append_basic_block(current_func, "cont_bb");
+        gc.builder()
+            .build_conditional_branch(is_alive, alive_bb, expired_bb);
+
+        // If alive, retain the object and return some of it.
+        gc.builder().position_at_end(alive_bb);
+        gc.retain(value);
+        let some = create_option(gc, Some(value), name_cloned.as_str());
+        let alive_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // Otherwise, return none.
+        gc.builder().position_at_end(expired_bb);
+        let none = create_option(gc, None, name_cloned.as_str());
+        let expired_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        gc.builder().position_at_end(cont_bb);
+        let option = gc
+            .builder()
+            .build_phi(ptr_to_object_type(gc.context), "option");
+        option.add_incoming(&[(&some, alive_bb), (&none, expired_bb)]);
+        gc.release(weak);
+        option.as_basic_value().into_pointer_value()
+    });
+    lit(generator, free_vars, name)
+}
+
+// derefWeak built-in function, which returns some of the referenced object if it is alive, and none otherwise.
+pub fn deref_weak() -> Arc<ExprInfo> {
+    lam(var_var("weak"), deref_weak_lit("weak"))
+}
+
+// Implementation of findIndexArray built-in function.
+fn find_index_array_lit(array: &str, pred: &str) -> Arc<ExprInfo> {
+    let array_str = String::from(array);
+    let pred_str = String::from(pred);
+    let name = format!("findIndexArray {} {}", array, pred);
+    let name_cloned = name.clone();
+    let free_vars = vec![array_str.clone(), pred_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get arguments.
+        let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+        let array = gc.scope_get(array_str.as_str()).ptr;
+        let array = gc.cast_pointer(array, ptr_type(array_str_ty));
+        let array_field = gc.builder().build_struct_gep(array, 1, "").unwrap();
+        let pred = gc.scope_get(pred_str.as_str()).ptr;
+        let (size, buffer) = ObjectFieldType::get_size_and_buffer_of_array(gc, array_field);
+
+        // Allocate and initialize loop counter.
+        let counter_type = gc.context.i64_type();
+        let counter_ptr = gc.builder().build_alloca(counter_type, "find_loop_counter");
+        gc.builder()
+            .build_store(counter_ptr, counter_type.const_zero());
+
+        // Append blocks.
+        let current_bb = gc.builder().get_insert_block().unwrap();
+        let current_func = current_bb.get_parent().unwrap();
+        let loop_check_bb = gc.context.append_basic_block(current_func, "loop_check");
+        let loop_body_bb = gc.context.append_basic_block(current_func, "loop_body");
+        let found_bb = gc.context.append_basic_block(current_func, "found");
+        let not_found_bb = gc.context.append_basic_block(current_func, "not_found");
+        let cont_bb = gc.context.append_basic_block(current_func, "cont_bb");
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        // Implement loop_check bb.
+        gc.builder().position_at_end(loop_check_bb);
+        let counter_val = gc
+            .builder()
+            .build_load(counter_ptr, "counter_val")
+            .into_int_value();
+        let is_end = gc
+            .builder()
+            .build_int_compare(IntPredicate::EQ, counter_val, size, "is_end");
+        gc.builder()
+            .build_conditional_branch(is_end, not_found_bb, loop_body_bb);
+
+        // Implement loop_body bb: apply the predicate to the element and exit if it holds.
+        gc.builder().position_at_end(loop_body_bb);
+        let ptr_to_elem = unsafe {
+            gc.builder()
+                .build_gep(buffer, &[counter_val.into()], "ptr_to_elem_of_array")
+        };
+        let elem = gc
+            .builder()
+            .build_load(ptr_to_elem, "elem")
+            .into_pointer_value();
+        gc.retain(elem);
+        gc.retain(pred);
+        let pred_obj = gc.apply_lambda(pred, elem);
+        let pred_val = gc
+            .load_obj_field(pred_obj, bool_type(gc.context), 1)
+            .into_int_value();
+        gc.release(pred_obj);
+        let pred_val = gc
+            .builder()
+            .build_int_cast(pred_val, gc.context.bool_type(), "pred_val_i1");
+        let incremented_counter_val = gc.builder().build_int_add(
+            counter_val,
+            counter_type.const_int(1, false),
+            "incremented_counter_val",
+        );
+        gc.builder()
+            .build_store(counter_ptr, incremented_counter_val);
+        gc.builder()
+            .build_conditional_branch(pred_val, found_bb, loop_check_bb);
+
+        // Implement found bb.
+        gc.builder().position_at_end(found_bb);
+        let idx = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(idx, int_type(gc.context), 1, counter_val);
+        let found = create_option(gc, Some(idx), name_cloned.as_str());
+        let found_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // Implement not_found bb.
+        gc.builder().position_at_end(not_found_bb);
+        let not_found = create_option(gc, None, name_cloned.as_str());
+        let not_found_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // Implement cont bb.
+        gc.builder().position_at_end(cont_bb);
+        let phi = gc
+            .builder()
+            .build_phi(ptr_to_object_type(gc.context), "option_phi");
+        phi.add_incoming(&[(&found, found_end_bb), (&not_found, not_found_end_bb)]);
+        gc.release(pred);
+        gc.release(array);
+        phi.as_basic_value().into_pointer_value()
+    });
+    lit(generator, free_vars, name)
+}
+
+// findIndexArray built-in function.
+pub fn find_index_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("array"),
+        lam(var_var("pred"), find_index_array_lit("array", "pred")),
+    )
+}
+
+// Implementation of foldArray / foldRightArray built-in function.
+// These are generated as loops, so the stack usage is constant.
+// foldArray arr f init = f (... (f (f init arr[0]) arr[1]) ...) arr[n-1].
+// foldRightArray arr f init = f arr[0] (f arr[1] (... (f arr[n-1] init) ...)).
+fn fold_array_lit(array: &str, func: &str, init: &str, is_right: bool) -> Arc<ExprInfo> {
+    let array_str = String::from(array);
+    let func_str = String::from(func);
+    let init_str = String::from(init);
+    let func_name = if is_right {
+        "foldRightArray"
+    } else {
+        "foldArray"
+    };
+    let name = format!("{} {} {} {}", func_name, array, func, init);
+    let free_vars = vec![array_str.clone(), func_str.clone(), init_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get arguments.
+        let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+        let array = gc.scope_get(array_str.as_str()).ptr;
+        let array = gc.cast_pointer(array, ptr_type(array_str_ty));
+        let array_field = gc.builder().build_struct_gep(array, 1, "").unwrap();
+        let func = gc.scope_get(func_str.as_str()).ptr;
+        let init = gc.scope_get(init_str.as_str()).ptr;
+        let (size, buffer) = ObjectFieldType::get_size_and_buffer_of_array(gc, array_field);
+
+        // Append blocks: loop_check, loop_body and after_loop.
+        let counter_type = gc.context.i64_type();
+        let init_bb = gc.builder().get_insert_block().unwrap();
+        let current_func = init_bb.get_parent().unwrap();
+        let loop_check_bb = gc.context.append_basic_block(current_func, "loop_check");
+        let loop_body_bb = gc.context.append_basic_block(current_func, "loop_body");
+        let after_loop_bb = gc.context.append_basic_block(current_func, "after_loop");
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        // Implement loop_check bb.
+        gc.builder().position_at_end(loop_check_bb);
+        let counter_phi = gc.builder().build_phi(counter_type, "counter_phi");
+        let counter_val = counter_phi.as_basic_value().into_int_value();
+        let acc_phi = gc
+            .builder()
+            .build_phi(ptr_to_object_type(gc.context), "acc_phi");
+        let acc = acc_phi.as_basic_value().into_pointer_value();
+        let counter_end = if is_right {
+            counter_type.const_zero()
+        } else {
+            size
+        };
+        let is_end =
+            gc.builder()
+                .build_int_compare(IntPredicate::EQ, counter_val, counter_end, "is_end");
+        gc.builder()
+            .build_conditional_branch(is_end, after_loop_bb, loop_body_bb);
+
+        // Implement loop_body bb.
+        gc.builder().position_at_end(loop_body_bb);
+        let one = counter_type.const_int(1, false);
+        let idx = if is_right {
+            gc.builder().build_int_sub(counter_val, one, "idx")
+        } else {
+            counter_val
+        };
+        let ptr_to_elem = unsafe {
+            gc.builder()
+                .build_gep(buffer, &[idx.into()], "ptr_to_elem_of_array")
+        };
+        let elem = gc
+            .builder()
+            .build_load(ptr_to_elem, "elem")
+            .into_pointer_value();
+        gc.retain(elem);
+        gc.retain(func);
+        let next_acc = if is_right {
+            let partial = gc.apply_lambda(func, elem);
+            gc.apply_lambda(partial, acc)
+        } else {
+            let partial = gc.apply_lambda(func, acc);
+            gc.apply_lambda(partial, elem)
+        };
+        let next_counter_val = if is_right {
+            idx
+        } else {
+            gc.builder()
+                .build_int_add(counter_val, one, "next_counter_val")
+        };
+        let loop_body_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        // Set up phis.
+        let counter_start = if is_right {
+            size
+        } else {
+            counter_type.const_zero()
+        };
+        counter_phi.add_incoming(&[
+            (&counter_start, init_bb),
+            (&next_counter_val, loop_body_end_bb),
+        ]);
+        acc_phi.add_incoming(&[(&init, init_bb), (&next_acc, loop_body_end_bb)]);
+
+        // After loop, release function and array, and return the accumulator.
+        gc.builder().position_at_end(after_loop_bb);
+        gc.release(func);
+        gc.release(array);
+        acc
+    });
+    lit(generator, free_vars, name)
+}
+
+// foldArray built-in function.
+pub fn fold_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("array"),
+        lam(
+            var_var("func"),
+            lam(
+                var_var("init"),
+                fold_array_lit("array", "func", "init", false),
+            ),
+        ),
+    )
+}
+
+// foldRightArray built-in function.
+pub fn fold_right_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("array"),
+        lam(
+            var_var("func"),
+            lam(
+                var_var("init"),
+                fold_array_lit("array", "func", "init", true),
+            ),
+        ),
+    )
+}
+
+// Implementation of copyArray built-in function.
+fn copy_array_lit(array: &str) -> Arc<ExprInfo> {
+    let array_str = String::from(array);
+    let name = format!("copyArray {}", array);
+    let name_cloned = name.clone();
+    let free_vars = vec![array_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get argument.
+        let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+        let array = gc.scope_get(array_str.as_str()).ptr;
+        let array = gc.cast_pointer(array, ptr_type(array_str_ty));
+        let array_field = gc.builder().build_struct_gep(array, 1, "").unwrap();
+
+        // Create new array and clone array field (elements are retained).
+        let copied = ObjectType::array_type().create_obj(gc, name_cloned.as_str());
+        let copied_array = gc.cast_pointer(copied, ptr_type(array_str_ty));
+        let copied_field = gc.builder().build_struct_gep(copied_array, 1, "").unwrap();
+        ObjectFieldType::clone_array(gc, array_field, copied_field);
+        gc.release(array);
+        copied
+    });
+    lit(generator, free_vars, name)
+}
+
+// copyArray built-in function.
+pub fn copy_array() -> Arc<ExprInfo> {
+    lam(var_var("array"), copy_array_lit("array"))
+}
+
+// Implementation of rotateArray built-in function.
+fn rotate_array_lit(shift: &str, array: &str) -> Arc<ExprInfo> {
+    let shift_str = String::from(shift);
+    let array_str = String::from(array);
+    let name = format!("rotateArray {} {}", shift, array);
+    let name_cloned = name.clone();
+    let free_vars = vec![shift_str.clone(), array_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get arguments.
+        let shift = gc
+            .scope_get_field(&shift_str, 1, int_type(gc.context))
+            .into_int_value();
+        let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+        let array = gc.scope_get(array_str.as_str()).ptr;
+        let array = gc.cast_pointer(array, ptr_type(array_str_ty));
+        let array_field = gc.builder().build_struct_gep(array, 1, "").unwrap();
+
+        // Create new array with rotated elements (elements are retained).
+        let rotated = ObjectType::array_type().create_obj(gc, name_cloned.as_str());
+        let rotated_array = gc.cast_pointer(rotated, ptr_type(array_str_ty));
+        let rotated_field = gc.builder().build_struct_gep(rotated_array, 1, "").unwrap();
+        ObjectFieldType::rotate_array(gc, array_field, rotated_field, shift);
+        gc.release(gc.scope_get(&shift_str).ptr);
+        gc.release(array);
+        rotated
+    });
+    lit(generator, free_vars, name)
+}
+
+// rotateArray built-in function, which creates an array with elements rotated left by a given number of positions.
+// The number is taken modulo the length of the array, so a negative number rotates right.
+pub fn rotate_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("shift"),
+        lam(var_var("array"), rotate_array_lit("shift", "array")),
+    )
+}
+
+// Implementation of deepCopyArray built-in function.
+fn deep_copy_array_lit(array: &str, func: &str) -> Arc<ExprInfo> {
+    let array_str = String::from(array);
+    let func_str = String::from(func);
+    let name = format!("deepCopyArray {} {}", array, func);
+    let name_cloned = name.clone();
+    let free_vars = vec![array_str.clone(), func_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get arguments.
+        let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+        let array = gc.scope_get(array_str.as_str()).ptr;
+        let array = gc.cast_pointer(array, ptr_type(array_str_ty));
+        let array_field = gc.builder().build_struct_gep(array, 1, "").unwrap();
+        let func = gc.scope_get(func_str.as_str()).ptr;
+        let (size, buffer) = ObjectFieldType::get_size_and_buffer_of_array(gc, array_field);
+
+        // Create new array with a buffer of the same size.
+        let copied = ObjectType::array_type().create_obj(gc, name_cloned.as_str());
+        let copied_array = gc.cast_pointer(copied, ptr_type(array_str_ty));
+        let copied_field = gc.builder().build_struct_gep(copied_array, 1, "").unwrap();
+        let array_field_ty = ObjectFieldType::Array
+            .to_basic_type(gc.context)
+            .into_struct_type();
+        gc.store_obj_field(copied_field, array_field_ty, 0, size);
+        let copied_buffer = gc
+            .builder()
+            .build_array_malloc(ptr_to_object_type(gc.context), size, "copied_buffer")
+            .unwrap();
+        gc.store_obj_field(copied_field, array_field_ty, 1, copied_buffer);
+
+        // Append blocks: loop_check, loop_body and after_loop.
+        let counter_type = gc.context.i64_type();
+        let init_bb = gc.builder().get_insert_block().unwrap();
+        let current_func = init_bb.get_parent().unwrap();
+        let loop_check_bb = gc.context.append_basic_block(current_func, "loop_check");
+        let loop_body_bb = gc.context.append_basic_block(current_func, "loop_body");
+        let after_loop_bb = gc.context.append_basic_block(current_func, "after_loop");
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        // Implement loop_check bb.
+        gc.builder().position_at_end(loop_check_bb);
+        let counter_phi = gc.builder().build_phi(counter_type, "counter_phi");
+        let counter_val = counter_phi.as_basic_value().into_int_value();
+        let is_end = gc
+            .builder()
+            .build_int_compare(IntPredicate::EQ, counter_val, size, "is_end");
+        gc.builder()
+            .build_conditional_branch(is_end, after_loop_bb, loop_body_bb);
+
+        // Implement loop_body bb: store (func elem) at the same index of the new buffer.
+        gc.builder().position_at_end(loop_body_bb);
+        let ptr_to_elem = unsafe {
+            gc.builder()
+                .build_gep(buffer, &[counter_val.into()], "ptr_to_elem_of_array")
+        };
+        let elem = gc
+            .builder()
+            .build_load(ptr_to_elem, "elem")
+            .into_pointer_value();
+        gc.retain(elem);
+        gc.retain(func);
+        let copied_elem = gc.apply_lambda(func, elem);
+        let ptr_to_copied_elem = unsafe {
+            gc.builder()
+                .build_gep(copied_buffer, &[counter_val.into()], "ptr_to_copied_elem")
+        };
+        gc.builder().build_store(ptr_to_copied_elem, copied_elem);
+        let one = counter_type.const_int(1, false);
+        let next_counter_val = gc
+            .builder()
+            .build_int_add(counter_val, one, "next_counter_val");
+        let loop_body_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        // Set up phi.
+        counter_phi.add_incoming(&[
+            (&counter_type.const_zero(), init_bb),
+            (&next_counter_val, loop_body_end_bb),
+        ]);
+
+        // After loop, release function and source array, and return the copy.
+        gc.builder().position_at_end(after_loop_bb);
+        gc.release(func);
+        gc.release(array);
+        copied
+    });
+    lit(generator, free_vars, name)
+}
+
+// deepCopyArray built-in function.
+pub fn deep_copy_array() -> Arc<ExprInfo> {
+    lam(
+        var_var("array"),
+        lam(var_var("func"), deep_copy_array_lit("array", "func")),
+    )
+}
+
+// Get string field of a string object.
+fn get_string_field<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    string: PointerValue<'c>,
+) -> PointerValue<'c> {
+    let string_ptr_ty = ptr_type(ObjectType::string_type().to_struct_type(gc.context));
+    let string = gc.cast_pointer(string, string_ptr_ty);
+    gc.builder()
+        .build_struct_gep(string, 1, "string_field")
+        .unwrap()
+}
+
+// Implementation of concatStr built-in function.
+fn concat_str_lit(lhs: &str, rhs: &str) -> Arc<ExprInfo> {
+    let lhs_str = String::from(lhs);
+    let rhs_str = String::from(rhs);
+    let name = format!("concatStr {} {}", lhs, rhs);
+    let name_cloned = name.clone();
+    let free_vars = vec![lhs_str.clone(), rhs_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get sizes and buffers of arguments.
+        let lhs = gc.scope_get(lhs_str.as_str()).ptr;
+        let lhs_field = get_string_field(gc, lhs);
+        let (lhs_size, lhs_buffer) = ObjectFieldType::get_size_and_buffer_of_string(gc, lhs_field);
+        let rhs = gc.scope_get(rhs_str.as_str()).ptr;
+        let rhs_field = get_string_field(gc, rhs);
+        let (rhs_size, rhs_buffer) = ObjectFieldType::get_size_and_buffer_of_string(gc, rhs_field);
+
+        // Create a new string and copy buffers into it.
+        let string = ObjectType::string_type().create_obj(gc, name_cloned.as_str());
+        let string_field = get_string_field(gc, string);
+        let size = gc.builder().build_int_add(lhs_size, rhs_size, "size");
+        let buffer = ObjectFieldType::allocate_string_buffer(gc, string_field, size);
+        gc.builder()
+            .build_memcpy(buffer, 1, lhs_buffer, 1, lhs_size)
+            .unwrap();
+        let buffer_rhs_part = unsafe {
+            gc.builder()
+                .build_gep(buffer, &[lhs_size.into()], "buffer_rhs_part")
+        };
+        gc.builder()
+            .build_memcpy(buffer_rhs_part, 1, rhs_buffer, 1, rhs_size)
+            .unwrap();
+
+        // Release arguments.
+        gc.release(lhs);
+        gc.release(rhs);
+        string
+    });
+    lit(generator, free_vars, name)
+}
+
+// concatStr built-in function.
+pub fn concat_str() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(var_var("rhs"), concat_str_lit("lhs", "rhs")),
+    )
+}
+
+// Implementation of lengthStr built-in function.
+fn length_str_lit(string: &str) -> Arc<ExprInfo> {
+    let string_str = String::from(string);
+    let name = format!("lengthStr {}", string);
+    let name_cloned = name.clone();
+    let free_vars = vec![string_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let string = gc.scope_get(string_str.as_str()).ptr;
+        let string_field = get_string_field(gc, string);
+        let (size, _buffer) = ObjectFieldType::get_size_and_buffer_of_string(gc, string_field);
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, size);
+        gc.release(string);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// lengthStr built-in function.
+pub fn length_str() -> Arc<ExprInfo> {
+    lam(var_var("string"), length_str_lit("string"))
+}
+
+// Implementation of print built-in function.
+fn print_lit(string: &str) -> Arc<ExprInfo> {
+    let string_str = String::from(string);
+    let name = format!("print {}", string);
+    let name_cloned = name.clone();
+    let free_vars = vec![string_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let string = gc.scope_get(string_str.as_str()).ptr;
+        let string_field = get_string_field(gc, string);
+        let (size, buffer) = ObjectFieldType::get_size_and_buffer_of_string(gc, string_field);
+        let written = gc
+            .call_runtime(RuntimeFunctions::PutStr, &[buffer.into(), size.into()])
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_int_value();
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, written);
+        gc.release(string);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// print built-in function, which writes a string to stdout and returns the number of bytes written.
+pub fn print() -> Arc<ExprInfo> {
+    lam(var_var("string"), print_lit("string"))
+}
+
+// Implementation of error built-in function.
+fn error_lit(code: &str) -> Arc<ExprInfo> {
+    let code_str = String::from(code);
+    let name = format!("error {}", code);
+    let free_vars = vec![code_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let code = gc
+            .scope_get_field(&code_str, 1, int_type(gc.context))
+            .into_int_value();
+        gc.call_runtime(RuntimeFunctions::Error, &[code.into()]);
+        gc.build_unreachable_and_continue();
+        // Since error never returns, the value is never used.
+        ptr_to_object_type(gc.context).get_undef()
+    });
+    lit(generator, free_vars, name)
+}
+
+// error built-in function, which prints the given code and exits.
+// Since error never returns, an application of it can appear at any place, e.g., in either branch of if.
+pub fn error() -> Arc<ExprInfo> {
+    lam(var_var("code"), error_lit("code"))
+}
+
+// Implementation of getEnvInt built-in function.
+fn get_env_int_lit(name: &str, default: &str) -> Arc<ExprInfo> {
+    let name_str = String::from(name);
+    let default_str = String::from(default);
+    let name = format!("getEnvInt {} {}", name, default);
+    let name_cloned = name.clone();
+    let free_vars = vec![name_str.clone(), default_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let var_name = gc.scope_get(name_str.as_str()).ptr;
+        let var_name_field = get_string_field(gc, var_name);
+        let (_size, buffer) = ObjectFieldType::get_size_and_buffer_of_string(gc, var_name_field);
+        let default = gc
+            .scope_get_field(&default_str, 1, int_type(gc.context))
+            .into_int_value();
+        let value = gc
+            .call_runtime(
+                RuntimeFunctions::GetEnvInt,
+                &[buffer.into(), default.into()],
+            )
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_int_value();
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, value);
+        gc.release(var_name);
+        gc.release(gc.scope_get(&default_str).ptr);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// getEnvInt built-in function, which reads an environment variable as an integer.
+// Returns the default value if the variable is unset or is not an integer.
+pub fn get_env_int() -> Arc<ExprInfo> {
+    lam(
+        var_var("name"),
+        lam(var_var("default"), get_env_int_lit("name", "default")),
+    )
+}
+
+// Generate code that panics with a given message if cond holds.
+fn panic_if<'c, 'm>(gc: &mut GenerationContext<'c, 'm>, cond: IntValue<'c>, msg: &str) {
+    let curr_bb = gc.builder().get_insert_block().unwrap();
+    let curr_func = curr_bb.get_parent().unwrap();
+    let panic_bb = gc.context.append_basic_block(curr_func, "panic_bb");
+    let cont_bb = gc.context.append_basic_block(curr_func, "cont_bb");
+    gc.builder()
+        .build_conditional_branch(cond, panic_bb, cont_bb);
+    gc.builder().position_at_end(panic_bb);
+    gc.panic(msg);
+    gc.builder().build_unreachable();
+    gc.builder().position_at_end(cont_bb);
+}
+
+// Implementation of sliceView built-in function.
+// The view retains the parent array and shares its buffer, so no element is copied.
+fn slice_view_lit(array: &str, start: &str, len: &str) -> Arc<ExprInfo> {
+    let array_str = String::from(array);
+    let start_str = String::from(start);
+    let len_str = String::from(len);
+    let name = format!("sliceView {} {} {}", array, start, len);
+    let name_cloned = name.clone();
+    let free_vars = vec![array_str.clone(), start_str.clone(), len_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get arguments.
+        let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+        let array = gc.scope_get(array_str.as_str()).ptr;
+        let array = gc.cast_pointer(array, ptr_type(array_str_ty));
+        let array_field = gc.builder().build_struct_gep(array, 1, "").unwrap();
+        let start = gc
+            .scope_get_field(start_str.as_str(), 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(start_str.as_str()).ptr);
+        let len = gc
+            .scope_get_field(len_str.as_str(), 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(len_str.as_str()).ptr);
+
+        // Panic unless 0 <= start, 0 <= len and start + len <= size.
+        let (size, _buffer) = ObjectFieldType::get_size_and_buffer_of_array(gc, array_field);
+        let zero = gc.context.i64_type().const_zero();
+        let end = gc.builder().build_int_add(start, len, "end");
+        let is_start_negative =
+            gc.builder()
+                .build_int_compare(IntPredicate::SLT, start, zero, "is_start_negative");
+        let is_len_negative =
+            gc.builder()
+                .build_int_compare(IntPredicate::SLT, len, zero, "is_len_negative");
+        let is_over = gc
+            .builder()
+            .build_int_compare(IntPredicate::SGT, end, size, "is_over");
+        let is_out_of_range =
+            gc.builder()
+                .build_or(is_start_negative, is_len_negative, "is_out_of_range");
+        let is_out_of_range = gc
+            .builder()
+            .build_or(is_out_of_range, is_over, "is_out_of_range");
+        panic_if(gc, is_out_of_range, "Range of sliceView is out of array!\n");
+
+        // Create view. The array is moved into the view.
+        let view_str_ty = ObjectType::array_view_type().to_struct_type(gc.context);
+        let view = ObjectType::array_view_type().create_obj(gc, name_cloned.as_str());
+        let array = gc.cast_pointer(array, ptr_to_object_type(gc.context));
+        gc.store_obj_field(view, view_str_ty, 1, array);
+        gc.store_obj_field(view, view_str_ty, 2, start);
+        gc.store_obj_field(view, view_str_ty, 3, len);
+        view
+    });
+    lit(generator, free_vars, name)
+}
+
+// sliceView built-in function, which creates a view of elements in range [start, start + len) of an array.
+pub fn slice_view() -> Arc<ExprInfo> {
+    lam(
+        var_var("array"),
+        lam(
+            var_var("start"),
+            lam(var_var("len"), slice_view_lit("array", "start", "len")),
+        ),
+    )
+}
+
+// Get pointer to the place of an element of the parent array of a view.
+// idx is relative to the view, and the code panics if it is out of the view.
+fn get_place_in_view<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    view: PointerValue<'c>,
+    idx: IntValue<'c>,
+) -> PointerValue<'c> {
+    let view_str_ty = ObjectType::array_view_type().to_struct_type(gc.context);
+    let parent = gc.load_obj_field(view, view_str_ty, 1).into_pointer_value();
+    let offset = gc.load_obj_field(view, view_str_ty, 2).into_int_value();
+    let len = gc.load_obj_field(view, view_str_ty, 3).into_int_value();
+
+    // Panic if out_of_range.
+    let is_out_of_range =
+        gc.builder()
+            .build_int_compare(IntPredicate::UGE, idx, len, "is_out_of_range");
+    panic_if(gc, is_out_of_range, "Index out of range!");
+
+    // Get place in the buffer of parent.
+    let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+    let parent = gc.cast_pointer(parent, ptr_type(array_str_ty));
+    let parent_field = gc.builder().build_struct_gep(parent, 1, "").unwrap();
+    let (_size, buffer) = ObjectFieldType::get_size_and_buffer_of_array(gc, parent_field);
+    let parent_idx = gc.builder().build_int_add(offset, idx, "parent_idx");
+    unsafe {
+        gc.builder()
+            .build_gep(buffer, &[parent_idx.into()], "ptr_to_elem_of_view")
+    }
+}
+
+// Implementation of readView built-in function.
+fn read_view_lit(view: &str, idx: &str) -> Arc<ExprInfo> {
+    let view_str = String::from(view);
+    let idx_str = String::from(idx);
+    let name = format!("readView {} {}", view, idx);
+    let free_vars = vec![view_str.clone(), idx_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let view = gc.scope_get(view_str.as_str()).ptr;
+        let idx = gc
+            .scope_get_field(&idx_str, 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(&idx_str).ptr);
+        let place = get_place_in_view(gc, view, idx);
+        let elem = gc.builder().build_load(place, "elem").into_pointer_value();
+        gc.retain(elem);
+        gc.release(view);
+        elem
+    });
+    lit(generator, free_vars, name)
+}
+
+// readView built-in function.
+pub fn read_view() -> Arc<ExprInfo> {
+    lam(
+        var_var("view"),
+        lam(var_var("idx"), read_view_lit("view", "idx")),
+    )
+}
+
+// Implementation of writeView built-in function.
+fn write_view_lit(view: &str, idx: &str, value: &str) -> Arc<ExprInfo> {
+    let view_str = String::from(view);
+    let idx_str = String::from(idx);
+    let value_str = String::from(value);
+    let name = format!("writeView {} {} {}", view, idx, value);
+    let free_vars = vec![view_str.clone(), idx_str.clone(), value_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let view = gc.scope_get(view_str.as_str()).ptr;
+        let idx = gc
+            .scope_get_field(&idx_str, 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(&idx_str).ptr);
+        let value = gc.scope_get(value_str.as_str()).ptr;
+        let place = get_place_in_view(gc, view, idx);
+
+        // Release element that is already at the place, and insert the given value to the place.
+        let elem = gc.builder().build_load(place, "elem").into_pointer_value();
+        gc.release(elem);
+        gc.builder().build_store(place, value);
+        view
+    });
+    lit(generator, free_vars, name)
+}
+
+// writeView built-in function.
+// The buffer is shared with the parent array, so the written value is also visible through the parent and other views of it.
+pub fn write_view() -> Arc<ExprInfo> {
+    lam(
+        var_var("view"),
+        lam(
+            var_var("idx"),
+            lam(var_var("value"), write_view_lit("view", "idx", "value")),
+        ),
+    )
+}
+
+// Implementation of liveObjects built-in function.
+fn live_objects_lit(obj: &str) -> Arc<ExprInfo> {
+    let obj_str = String::from(obj);
+    let name = format!("liveObjects {}", obj);
+    let name_cloned = name.clone();
+    let free_vars = vec![obj_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let obj = gc.scope_get(obj_str.as_str()).ptr;
+        let count = if SANITIZE_MEMORY {
+            gc.call_runtime(RuntimeFunctions::GetNumberOfLiveObjects, &[])
+                .try_as_basic_value()
+                .unwrap_left()
+                .into_int_value()
+        } else {
+            gc.context.i64_type().const_zero()
+        };
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, count);
+        gc.release(obj);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// liveObjects built-in function, which returns the number of objects alive (including the argument) in sanitize mode, and 0 otherwise.
+// The argument is only for keeping objects alive until counted, and for delaying evaluation until application.
+pub fn live_objects() -> Arc<ExprInfo> {
+    lam(var_var("obj"), live_objects_lit("obj"))
+}
+
+// Implementation of iterate built-in function.
+// iterate n f x = f (... (f (f x))) where f is applied n times (0 times if n <= 0). This is generated as a loop.
+fn iterate_lit(n: &str, func: &str, x: &str) -> Arc<ExprInfo> {
+    let n_str = String::from(n);
+    let func_str = String::from(func);
+    let x_str = String::from(x);
+    let name = format!("iterate {} {} {}", n, func, x);
+    let free_vars = vec![n_str.clone(), func_str.clone(), x_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get arguments.
+        let n = gc
+            .scope_get_field(n_str.as_str(), 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(n_str.as_str()).ptr);
+        let func = gc.scope_get(func_str.as_str()).ptr;
+        let x = gc.scope_get(x_str.as_str()).ptr;
+
+        // Append blocks: loop_check, loop_body and after_loop.
+        let counter_type = gc.context.i64_type();
+        let init_bb = gc.builder().get_insert_block().unwrap();
+        let current_func = init_bb.get_parent().unwrap();
+        let loop_check_bb = gc.context.append_basic_block(current_func, "loop_check");
+        let loop_body_bb = gc.context.append_basic_block(current_func, "loop_body");
+        let after_loop_bb = gc.context.append_basic_block(current_func, "after_loop");
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        // Implement loop_check bb.
+        gc.builder().position_at_end(loop_check_bb);
+        let counter_phi = gc.builder().build_phi(counter_type, "counter_phi");
+        let counter_val = counter_phi.as_basic_value().into_int_value();
+        let state_phi = gc
+            .builder()
+            .build_phi(ptr_to_object_type(gc.context), "state_phi");
+        let state = state_phi.as_basic_value().into_pointer_value();
+        let is_end = gc
+            .builder()
+            .build_int_compare(IntPredicate::SGE, counter_val, n, "is_end");
+        gc.builder()
+            .build_conditional_branch(is_end, after_loop_bb, loop_body_bb);
+
+        // Implement loop_body bb.
+        gc.builder().position_at_end(loop_body_bb);
+        gc.retain(func);
+        let next_state = gc.apply_lambda(func, state);
+        let next_counter_val = gc.builder().build_int_add(
+            counter_val,
+            counter_type.const_int(1, false),
+            "next_counter_val",
+        );
+        let loop_body_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(loop_check_bb);
+
+        // Set up phis.
+        counter_phi.add_incoming(&[
+            (&counter_type.const_zero(), init_bb),
+            (&next_counter_val, loop_body_end_bb),
+        ]);
+        state_phi.add_incoming(&[(&x, init_bb), (&next_state, loop_body_end_bb)]);
+
+        // After loop, release function and return the state.
+        gc.builder().position_at_end(after_loop_bb);
+        gc.release(func);
+        state
+    });
+    lit(generator, free_vars, name)
+}
+
+// iterate built-in function.
+pub fn iterate() -> Arc<ExprInfo> {
+    lam(
+        var_var("n"),
+        lam(
+            var_var("func"),
+            lam(var_var("x"), iterate_lit("n", "func", "x")),
+        ),
+    )
+}
+
+// Name of the fused version of `iterate n (add k) x`, which cannot be written in source.
+pub const ITERATE_ADD_NAME: &str = "%iterateAdd%";
+
+// Implementation of fused version of `iterate n (add k) x`.
+// The result is calculated as x + max(n, 0) * k (wrapping around on overflow), so that neither closure calls nor allocations are performed per step.
+fn iterate_add_lit(n: &str, k: &str, x: &str) -> Arc<ExprInfo> {
+    let n_str = String::from(n);
+    let k_str = String::from(k);
+    let x_str = String::from(x);
+    let name = format!("iterate {} (add {}) {}", n, k, x);
+    let name_cloned = name.clone();
+    let free_vars = vec![n_str.clone(), k_str.clone(), x_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let mut vals = vec![];
+        for arg in [&n_str, &k_str, &x_str] {
+            let val = gc
+                .scope_get_field(arg, 1, int_type(gc.context))
+                .into_int_value();
+            gc.release(gc.scope_get(arg).ptr);
+            vals.push(val);
+        }
+        let (n, k, x) = (vals[0], vals[1], vals[2]);
+        let zero = gc.context.i64_type().const_zero();
+        let is_negative = gc
+            .builder()
+            .build_int_compare(IntPredicate::SLT, n, zero, "is_negative");
+        let n = gc
+            .builder()
+            .build_select(is_negative, zero, n, "n_clamped")
+            .into_int_value();
+        let increment = gc.builder().build_int_mul(n, k, "increment");
+        let value = gc.builder().build_int_add(x, increment, "iterate_add");
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, value);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// Fused version of `iterate n (add k) x`, introduced by optimization.
+pub fn iterate_add() -> Arc<ExprInfo> {
+    lam(
+        var_var("n"),
+        lam(
+            var_var("k"),
+            lam(var_var("x"), iterate_add_lit("n", "k", "x")),
+        ),
+    )
+}
+
+// Implementation of trace built-in function.
+fn trace_lit(obj: &str) -> Arc<ExprInfo> {
+    let obj_str = String::from(obj);
+    let name = format!("trace {}", obj);
+    let free_vars = vec![obj_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let obj = gc.scope_get(obj_str.as_str()).ptr;
+        if SANITIZE_MEMORY {
+            let obj_id = gc.get_obj_id(obj);
+            let refcnt = gc
+                .load_obj_field(obj, control_block_type(gc.context), 0)
+                .into_int_value();
+            gc.call_runtime(
+                RuntimeFunctions::ReportTrace,
+                &[obj.into(), obj_id.into(), refcnt.into()],
+            );
+        }
+        obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// trace built-in function, which returns the argument as is.
+// In sanitize mode, the id, name and refcnt of the argument are printed for debugging.
+pub fn trace() -> Arc<ExprInfo> {
+    lam(var_var("obj"), trace_lit("obj"))
+}
+
+// Implementation of newArrayLazy built-in function.
+fn new_array_lazy_lit(size: &str, thunk: &str) -> Arc<ExprInfo> {
+    let size_str = String::from(size);
+    let thunk_str = String::from(thunk);
+    let name = format!("newArrayLazy {} {}", size, thunk);
+    let name_cloned = name.clone();
+    let free_vars = vec![size_str.clone(), thunk_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Get arguments.
+        let size = gc
+            .scope_get_field(&size_str, 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(&size_str).ptr);
+        let thunk = gc.scope_get(&thunk_str).ptr;
+
+        // Create array.
+        let array_str_ty = ObjectType::array_type().to_struct_type(gc.context);
+        let array = ObjectType::array_type().create_obj(gc, name_cloned.as_str());
+        let array = gc.cast_pointer(array, ptr_type(array_str_ty));
+        let array_field = gc
+            .builder()
+            .build_struct_gep(array, 1, "array_field")
+            .unwrap();
+
+        // Append blocks: force, empty and cont.
+        let current_bb = gc.builder().get_insert_block().unwrap();
+        let current_func = current_bb.get_parent().unwrap();
+        let force_bb = gc.context.append_basic_block(current_func, "force_bb");
+        let empty_bb = gc.context.append_basic_block(current_func, "empty_bb");
+        let cont_bb = gc.context.append_basic_block(current_func, "cont_bb");
+        let zero = gc.context.i64_type().const_zero();
+        let is_positive =
+            gc.builder()
+                .build_int_compare(IntPredicate::SGT, size, zero, "is_positive");
+        gc.builder()
+            .build_conditional_branch(is_positive, force_bb, empty_bb);
+
+        // In force_bb, force the thunk once and fill the array with the value.
+        gc.builder().position_at_end(force_bb);
+        let dummy = ObjectType::int_obj_type().create_obj(gc, "argument of thunk");
+        gc.store_obj_field(dummy, int_type(gc.context), 1, zero);
+        let value = gc.apply_lambda(thunk, dummy);
+        ObjectFieldType::initialize_array(gc, array_field, size, value);
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // In empty_bb, the thunk is released without being forced.
+        gc.builder().position_at_end(empty_bb);
+        gc.release(thunk);
+        let array_struct = ObjectFieldType::Array
+            .to_basic_type(gc.context)
+            .into_struct_type();
+        gc.store_obj_field(array_field, array_struct, 0, zero);
+        let null_buffer = ptr_to_object_type(gc.context)
+            .ptr_type(AddressSpace::Generic)
+            .const_null();
+        gc.store_obj_field(array_field, array_struct, 1, null_buffer);
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        gc.builder().position_at_end(cont_bb);
+        array
+    });
+    lit(generator, free_vars, name)
+}
+
+// newArrayLazy built-in function.
+// The thunk (which is applied to a dummy Int argument) is forced only if size > 0, and only once; the value is shared by all elements.
+pub fn new_array_lazy() -> Arc<ExprInfo> {
+    lam(
+        var_var("size"),
+        lam(var_var("thunk"), new_array_lazy_lit("size", "thunk")),
+    )
+}
+
+// Create an option object which has the integer stored at the given pointer if is_valid is true, and none otherwise.
+fn create_int_option<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    is_valid: IntValue<'c>,
+    ptr_to_value: PointerValue<'c>,
+    name: &str,
+) -> PointerValue<'c> {
+    // Append blocks.
+    let current_bb = gc.builder().get_insert_block().unwrap();
+    let current_func = current_bb.get_parent().unwrap();
+    let valid_bb = gc.context.append_basic_block(current_func, "valid");
+    let invalid_bb = gc.context.append_basic_block(current_func, "invalid");
+    let cont_bb = gc.context.append_basic_block(current_func, "cont_bb");
+    gc.builder()
+        .build_conditional_branch(is_valid, valid_bb, invalid_bb);
+
+    // Implement valid bb.
+    gc.builder().position_at_end(valid_bb);
+    let value = gc
+        .builder()
+        .build_load(ptr_to_value, "parsed")
+        .into_int_value();
+    let int_obj = ObjectType::int_obj_type().create_obj(gc, name);
+    gc.store_obj_field(int_obj, int_type(gc.context), 1, value);
+    let valid = create_option(gc, Some(int_obj), name);
+    let valid_end_bb = gc.builder().get_insert_block().unwrap();
+    gc.builder().build_unconditional_branch(cont_bb);
+
+    // Implement invalid bb.
+    gc.builder().position_at_end(invalid_bb);
+    let invalid = create_option(gc, None, name);
+    let invalid_end_bb = gc.builder().get_insert_block().unwrap();
+    gc.builder().build_unconditional_branch(cont_bb);
+
+    // Implement cont bb.
+    gc.builder().position_at_end(cont_bb);
+    let phi = gc
+        .builder()
+        .build_phi(ptr_to_object_type(gc.context), "option_phi");
+    phi.add_incoming(&[(&valid, valid_end_bb), (&invalid, invalid_end_bb)]);
+    phi.as_basic_value().into_pointer_value()
+}
+
+// Implementation of parseInt built-in function.
+fn parse_int_lit(string: &str) -> Arc<ExprInfo> {
+    let string_str = String::from(string);
+    let name = format!("parseInt {}", string);
+    let name_cloned = name.clone();
+    let free_vars = vec![string_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // Parse the string.
+        let string = gc.scope_get(string_str.as_str()).ptr;
+        let string_field = get_string_field(gc, string);
+        let (size, buffer) = ObjectFieldType::get_size_and_buffer_of_string(gc, string_field);
+        let ptr_to_value = gc
+            .builder()
+            .build_alloca(gc.context.i64_type(), "ptr_to_parsed");
+        let is_valid = gc
+            .call_runtime(
+                RuntimeFunctions::ParseInt,
+                &[buffer.into(), size.into(), ptr_to_value.into()],
+            )
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_int_value();
+        let is_valid = gc
+            .builder()
+            .build_int_cast(is_valid, gc.context.bool_type(), "is_valid_i1");
+        gc.release(string);
+
+        create_int_option(gc, is_valid, ptr_to_value, name_cloned.as_str())
+    });
+    lit(generator, free_vars, name)
+}
+
+// parseInt built-in function, which returns some integer if the whole string is an integer, and none otherwise.
+pub fn parse_int() -> Arc<ExprInfo> {
+    lam(var_var("string"), parse_int_lit("string"))
+}
+
+// Implementation of seq built-in function.
+fn seq_lit(a: &str, b: &str) -> Arc<ExprInfo> {
+    let a_str = String::from(a);
+    let b_str = String::from(b);
+    let name = format!("seq {} {}", a, b);
+    let free_vars = vec![a_str.clone(), b_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // The first argument is already evaluated here, so just discard it.
+        let a = gc.scope_get(a_str.as_str()).ptr;
+        gc.release(a);
+        gc.scope_get(b_str.as_str()).ptr
+    });
+    lit(generator, free_vars, name)
+}
+
+// seq built-in function, which evaluates the first argument, discards it and returns the second one.
+// Unlike an unused let binding, it is never eliminated by optimization passes.
+pub fn seq() -> Arc<ExprInfo> {
+    lam(var_var("a"), lam(var_var("b"), seq_lit("a", "b")))
+}
+
+// Implementation of built-in functions which apply an LLVM bit intrinsic (of type i64(i64, ...)) to an integer.
+// `flags` are passed to the intrinsic as additional i1 arguments.
+fn int_bit_intrinsic_lit(
+    func_name: &'static str,
+    intrinsic: &'static str,
+    flags: &'static [bool],
+    val: &str,
+) -> Arc<ExprInfo> {
+    let val_str = String::from(val);
+    let free_vars = vec![val_str.clone()];
+    let name = format!("{} {}", func_name, val);
+    let name_cloned = name.clone();
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let i64_type = gc.context.i64_type();
+        let bool_type = gc.context.bool_type();
+        let intrinsic_func = match gc.module.get_function(intrinsic) {
+            Some(func) => func,
+            None => {
+                let mut param_types: Vec<BasicMetadataTypeEnum> = vec![i64_type.into()];
+                param_types.extend(flags.iter().map(|_| bool_type.into()));
+                let fn_ty = i64_type.fn_type(&param_types, false);
+                gc.module.add_function(intrinsic, fn_ty, None)
+            }
+        };
+        let val = gc
+            .scope_get_field(&val_str, 1, int_type(gc.context))
+            .into_int_value();
+        let mut args: Vec<BasicMetadataValueEnum> = vec![val.into()];
+        args.extend(
+            flags
+                .iter()
+                .map(|flag| bool_type.const_int(*flag as u64, false).into()),
+        );
+        let value = gc
+            .builder()
+            .build_call(intrinsic_func, &args, func_name)
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_int_value();
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, value);
+        gc.release(gc.scope_get(&val_str).ptr);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// clz built-in function, which counts leading zero bits of an integer. `clz 0` is 64.
+pub fn clz() -> Arc<ExprInfo> {
+    // Pass is_zero_poison = false so that the result for zero is defined.
+    lam(
+        var_var("x"),
+        int_bit_intrinsic_lit("clz", "llvm.ctlz.i64", &[false], "x"),
+    )
+}
+
+// popcount built-in function, which counts one bits of an integer.
+pub fn popcount() -> Arc<ExprInfo> {
+    lam(
+        var_var("x"),
+        int_bit_intrinsic_lit("popcount", "llvm.ctpop.i64", &[], "x"),
+    )
+}
+
+// Implementation of a host function of type Int -> Int.
+// It calls an external function `symbol` of type i64(i64), whose address the host gives by `CompileOptions::host_symbols`.
+fn host_int_fn_lit(symbol: &str, arg: &str) -> Arc<ExprInfo> {
+    let symbol_str = String::from(symbol);
+    let arg_str = String::from(arg);
+    let free_vars = vec![arg_str.clone()];
+    let name = format!("{} {}", symbol, arg);
+    let name_cloned = name.clone();
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let i64_type = gc.context.i64_type();
+        let host_func = match gc.module.get_function(symbol_str.as_str()) {
+            Some(func) => func,
+            None => {
+                let fn_ty = i64_type.fn_type(&[i64_type.into()], false);
+                gc.module.add_function(symbol_str.as_str(), fn_ty, None)
+            }
+        };
+        let arg_val = gc
+            .scope_get_field(&arg_str, 1, int_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(&arg_str).ptr);
+        let value = gc
+            .builder()
+            .build_call(host_func, &[arg_val.into()], "call_host_fn")
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_int_value();
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, value);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// Library function of type Int -> Int implemented by the host, to be registered by `CompileOptions::extra_builtins`.
+// The host has to map `symbol` to a function `extern "C" fn(i64) -> i64` in `CompileOptions::host_symbols`.
+pub fn host_int_fn(symbol: &str) -> Arc<ExprInfo> {
+    lam(var_var("x"), host_int_fn_lit(symbol, "x"))
+}
+
+// Implementation of readLineInt built-in function.
+fn read_line_int_lit(dummy: &str) -> Arc<ExprInfo> {
+    let dummy_str = String::from(dummy);
+    let name = format!("readLineInt {}", dummy);
+    let name_cloned = name.clone();
+    let free_vars = vec![dummy_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let dummy = gc.scope_get(dummy_str.as_str()).ptr;
+        gc.release(dummy);
+
+        // Read a line from stdin.
+        let i8_ptr_type = gc.context.i8_type().ptr_type(AddressSpace::Generic);
+        let stdin_global = match gc.module.get_global("stdin") {
+            Some(global) => global,
+            None => gc
+                .module
+                .add_global(i8_ptr_type, Some(AddressSpace::Generic), "stdin"),
+        };
+        let stream = gc
+            .builder()
+            .build_load(stdin_global.as_pointer_value(), "stdin");
+        let ptr_to_value = gc
+            .builder()
+            .build_alloca(gc.context.i64_type(), "ptr_to_read");
+        let is_valid = gc
+            .call_runtime(
+                RuntimeFunctions::ReadLineInt,
+                &[stream.into(), ptr_to_value.into()],
+            )
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_int_value();
+        let is_valid = gc
+            .builder()
+            .build_int_cast(is_valid, gc.context.bool_type(), "is_valid_i1");
+        create_int_option(gc, is_valid, ptr_to_value, name_cloned.as_str())
+    });
+    lit(generator, free_vars, name)
+}
+
+// readLineInt built-in function, which reads a line from stdin and returns some integer if it is an integer, and none otherwise (e.g., at EOF).
+// The argument is ignored; it is taken so that a line is read each time the function is applied.
+pub fn read_line_int() -> Arc<ExprInfo> {
+    lam(var_var("dummy"), read_line_int_lit("dummy"))
+}
+
+// Implementation of not built-in function.
+fn not_lit(val: &str) -> Arc<ExprInfo> {
+    let val_str = String::from(val);
+    let name = format!("not {}", val);
+    let name_cloned = name.clone();
+    let free_vars = vec![val_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let val = gc
+            .scope_get_field(&val_str, 1, bool_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(&val_str).ptr);
+        let is_false = gc.builder().build_int_compare(
+            IntPredicate::EQ,
+            val,
+            val.get_type().const_zero(),
+            "is_false",
+        );
+        let value = gc
+            .builder()
+            .build_int_z_extend(is_false, val.get_type(), "not");
+        let ptr_to_obj = ObjectType::bool_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_obj, bool_type(gc.context), 1, value);
+        ptr_to_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+pub fn not() -> Arc<ExprInfo> {
+    lam(var_var("val"), not_lit("val"))
+}
+
+// Implementation of built-in functions `and` and `or`.
+// The second argument is read only if the first one doesn't determine the result; otherwise it is just released.
+fn bool_logic_lit(func_name: &str, is_and: bool, lhs: &str, rhs: &str) -> Arc<ExprInfo> {
+    let lhs_str = String::from(lhs);
+    let rhs_str = String::from(rhs);
+    let name = format!("{} {} {}", func_name, lhs, rhs);
+    let name_cloned = name.clone();
+    let func_name = String::from(func_name);
+    let free_vars = vec![lhs_str.clone(), rhs_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let lhs_val = gc
+            .scope_get_field(&lhs_str, 1, bool_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(&lhs_str).ptr);
+        let lhs_i1 = gc.builder().build_int_compare(
+            IntPredicate::NE,
+            lhs_val,
+            lhs_val.get_type().const_zero(),
+            "lhs_i1",
+        );
+
+        // Append blocks.
+        let current_bb = gc.builder().get_insert_block().unwrap();
+        let current_func = current_bb.get_parent().unwrap();
+        let short_bb = gc.context.append_basic_block(current_func, "short_bb");
+        let rhs_bb = gc.context.append_basic_block(current_func, "rhs_bb");
+        let cont_bb = gc.context.append_basic_block(current_func, "cont_bb");
+        if is_and {
+            gc.builder()
+                .build_conditional_branch(lhs_i1, rhs_bb, short_bb);
+        } else {
+            gc.builder()
+                .build_conditional_branch(lhs_i1, short_bb, rhs_bb);
+        }
+
+        // Implement short bb, where the result is the value of lhs.
+        gc.builder().position_at_end(short_bb);
+        gc.release(gc.scope_get(&rhs_str).ptr);
+        let short_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // Implement rhs bb, where the result is the value of rhs.
+        gc.builder().position_at_end(rhs_bb);
+        let rhs_val = gc
+            .scope_get_field(&rhs_str, 1, bool_type(gc.context))
+            .into_int_value();
+        gc.release(gc.scope_get(&rhs_str).ptr);
+        let rhs_end_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // Implement cont bb.
+        gc.builder().position_at_end(cont_bb);
+        let value = gc.builder().build_phi(lhs_val.get_type(), &func_name);
+        value.add_incoming(&[(&lhs_val, short_end_bb), (&rhs_val, rhs_end_bb)]);
+        let value = value.as_basic_value().into_int_value();
+        let ptr_to_obj = ObjectType::bool_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_obj, bool_type(gc.context), 1, value);
+        ptr_to_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+pub fn and() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(var_var("rhs"), bool_logic_lit("and", true, "lhs", "rhs")),
+    )
+}
+
+pub fn or() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(var_var("rhs"), bool_logic_lit("or", false, "lhs", "rhs")),
+    )
+}
+
+// Implementation of built-in functions of arithmetic on floats.
+fn float_arith_lit(func_name: &str, lhs: &str, rhs: &str) -> Arc<ExprInfo> {
+    let lhs_str = String::from(lhs);
+    let rhs_str = String::from(rhs);
+    let free_vars = vec![lhs_str.clone(), rhs_str.clone()];
+    let name = format!("{} {} {}", func_name, lhs, rhs);
+    let name_cloned = name.clone();
+    let func_name = String::from(func_name);
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let lhs_val = gc
+            .scope_get_field(&lhs_str, 1, float_type(gc.context))
+            .into_float_value();
+        let rhs_val = gc
+            .scope_get_field(&rhs_str, 1, float_type(gc.context))
+            .into_float_value();
+        let value = match func_name.as_str() {
+            "addF" => gc.builder().build_float_add(lhs_val, rhs_val, "addF"),
+            "mulF" => gc.builder().build_float_mul(lhs_val, rhs_val, "mulF"),
+            _ => unreachable!(),
+        };
+        let ptr_to_float_obj = ObjectType::float_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_float_obj, float_type(gc.context), 1, value);
+        gc.release(gc.scope_get(&lhs_str).ptr);
+        gc.release(gc.scope_get(&rhs_str).ptr);
+        ptr_to_float_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+pub fn add_float() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(var_var("rhs"), float_arith_lit("addF", "lhs", "rhs")),
+    )
+}
+
+pub fn mul_float() -> Arc<ExprInfo> {
+    lam(
+        var_var("lhs"),
+        lam(var_var("rhs"), float_arith_lit("mulF", "lhs", "rhs")),
+    )
+}
+
+// Name of the library function which converts a float into an integer having the same bit pattern.
+// This is used to return a float from entry functions, whose return type is i64.
+pub const FLOAT_BITS_NAME: &str = "%floatBits%";
+
+fn float_bits_lit(val: &str) -> Arc<ExprInfo> {
+    let val_str = String::from(val);
+    let free_vars = vec![val_str.clone()];
+    let name = format!("{} {}", FLOAT_BITS_NAME, val);
+    let name_cloned = name.clone();
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        let val = gc
+            .scope_get_field(&val_str, 1, float_type(gc.context))
+            .into_float_value();
+        gc.release(gc.scope_get(&val_str).ptr);
+        let bits = gc
+            .builder()
+            .build_bitcast(val, gc.context.i64_type(), "float_bits")
+            .into_int_value();
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, bits);
+        ptr_to_int_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+pub fn float_bits() -> Arc<ExprInfo> {
+    lam(var_var("x"), float_bits_lit("x"))
+}
+
+// Implementation of isUnique built-in function.
+fn is_unique_lit(obj: &str) -> Arc<ExprInfo> {
+    let obj_str = String::from(obj);
+    let name = format!("isUnique {}", obj);
+    let name_cloned = name.clone();
+    let free_vars = vec![obj_str.clone()];
+    let generator: Arc<LiteralGenerator> = Arc::new(move |gc| {
+        // The argument is moved into this function if it is not used later by the caller, and then the refcnt is one
+        // iff no one else refers to it. So load the refcnt before releasing the argument.
+        let obj = gc.scope_get(obj_str.as_str()).ptr;
+        let refcnt = gc
+            .builder()
+            .build_load(gc.get_refcnt_ptr(obj), "refcnt")
+            .into_int_value();
+        let one = refcnt_type(gc.context).const_int(1, false);
+        let is_unique = gc
+            .builder()
+            .build_int_compare(IntPredicate::EQ, refcnt, one, "is_unique");
+        gc.release(obj);
+        let value = gc.builder().build_int_z_extend(
+            is_unique,
+            ObjectFieldType::Bool
+                .to_basic_type(gc.context)
+                .into_int_type(),
+            "is_unique_bool",
+        );
+        let ptr_to_obj = ObjectType::bool_obj_type().create_obj(gc, name_cloned.as_str());
+        gc.store_obj_field(ptr_to_obj, bool_type(gc.context), 1, value);
+        ptr_to_obj
+    });
+    lit(generator, free_vars, name)
+}
+
+// isUnique built-in function, which returns true if the argument is referenced only by this call.
+// An object which is used after the call is retained to be passed, so it is reported as shared.
+pub fn is_unique() -> Arc<ExprInfo> {
+    lam(var_var("obj"), is_unique_lit("obj"))
+}
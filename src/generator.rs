@@ -14,7 +14,7 @@ use super::*;
 #[derive(Clone)]
 pub struct LocalVariable<'c> {
     pub ptr: PointerValue<'c>,
-    used_later: u32,
+    pub(crate) used_later: u32,
 }
 
 #[derive(Default)]
@@ -23,7 +23,7 @@ pub struct Scope<'c> {
 }
 
 impl<'c> Scope<'c> {
-    fn push(self: &mut Self, var_name: &str, code: &PointerValue<'c>) {
+    pub(crate) fn push(self: &mut Self, var_name: &str, code: &PointerValue<'c>) {
         if !self.data.contains_key(var_name) {
             self.data.insert(String::from(var_name), Default::default());
         }
@@ -51,7 +51,7 @@ impl<'c> Scope<'c> {
         let expr = self.get(var_name);
         gc.load_obj_field(expr.ptr, ty, field_idx)
     }
-    fn modify_used_later(self: &mut Self, names: &HashSet<String>, by: i32) {
+    pub(crate) fn modify_used_later(self: &mut Self, names: &HashSet<String>, by: i32) {
         for name in names {
             let used_later = &mut self
                 .data
@@ -71,9 +71,12 @@ impl<'c> Scope<'c> {
     }
 }
 
+// Add a signed difference to a counter.
+// A counter never becomes negative: it is a bug in release logic, so it is asserted in debug build, and saturated at 0 otherwise.
 fn add_i32_to_u32(u: u32, i: i32) -> u32 {
     if i.is_negative() {
-        u - i.wrapping_abs() as u32
+        debug_assert!(u >= i.unsigned_abs(), "used_later underflow");
+        u.saturating_sub(i.unsigned_abs())
     } else {
         u + i as u32
     }
@@ -85,6 +88,10 @@ pub struct GenerationContext<'c, 'm> {
     builders: Rc<RefCell<Vec<Rc<Builder<'c>>>>>,
     scope: Rc<RefCell<Vec<Scope<'c>>>>,
     pub runtimes: HashMap<RuntimeFunctions, FunctionValue<'c>>,
+    // If true, calls of lambdas are not marked as tail calls (useful for debugging).
+    pub disable_tail_calls: bool,
+    // If Some(n), closures capturing more than n variables hold the captured objects in an array object.
+    pub max_closure_captures: Option<usize>,
 }
 
 pub struct PopBuilderGuard<'c> {
@@ -107,6 +114,21 @@ impl<'c> Drop for PopScopeGuard<'c> {
     }
 }
 
+pub struct PopVariableGuard<'c> {
+    scope: Rc<RefCell<Vec<Scope<'c>>>>,
+    var_name: String,
+}
+
+impl<'c> Drop for PopVariableGuard<'c> {
+    fn drop(&mut self) {
+        self.scope
+            .borrow_mut()
+            .last_mut()
+            .unwrap()
+            .pop(&self.var_name);
+    }
+}
+
 impl<'c, 'm> GenerationContext<'c, 'm> {
     // Create new gc.
     pub fn new(ctx: &'c Context, module: &'m Module<'c>) -> Self {
@@ -116,6 +138,8 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             builders: Rc::new(RefCell::new(vec![Rc::new(ctx.create_builder())])),
             scope: Rc::new(RefCell::new(vec![Default::default()])),
             runtimes: Default::default(),
+            disable_tail_calls: false,
+            max_closure_captures: None,
         };
         ret
     }
@@ -143,6 +167,25 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         }
     }
 
+    // Push a variable to the current scope, which is popped when the returned guard is dropped.
+    fn push_variable(&mut self, var_name: &str, code: &PointerValue<'c>) -> PopVariableGuard<'c> {
+        self.scope_push(var_name, code);
+        PopVariableGuard {
+            scope: self.scope.clone(),
+            var_name: String::from(var_name),
+        }
+    }
+
+    // Check if a variable is in scope.
+    pub fn scope_has(&self, var_name: &str) -> bool {
+        self.scope
+            .borrow()
+            .last()
+            .unwrap()
+            .data
+            .contains_key(var_name)
+    }
+
     // Get a variable from scope.
     pub fn scope_get(&self, var_name: &str) -> LocalVariable<'c> {
         self.scope.borrow().last().unwrap().get(var_name)
@@ -173,6 +216,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         field_idx: u32,
         ty: StructType<'c>,
     ) -> BasicValueEnum<'c> {
+        self.panic_if_null(self.scope_get(var_name).ptr);
         self.scope
             .borrow_mut()
             .last()
@@ -189,11 +233,6 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             .push(var_name, code)
     }
 
-    // Pop scope.
-    fn scope_pop(self: &mut Self, var_name: &str) {
-        self.scope.borrow_mut().last_mut().unwrap().pop(var_name);
-    }
-
     pub fn get_var_retained_if_used_later(&mut self, var_name: &str) -> PointerValue<'c> {
         let var = self.scope_get(var_name);
         let code = var.ptr;
@@ -224,14 +263,57 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             .unwrap()
     }
 
+    // Get pointer to dtor field of a given object.
+    pub fn get_dtor_ptr(&self, obj: PointerValue<'c>) -> PointerValue<'c> {
+        let ptr_control_block = self.get_control_block_ptr(obj);
+        self.builder()
+            .build_struct_gep(ptr_control_block, 1, "ptr_to_dtor_field")
+            .unwrap()
+    }
+
     // Call dtor of object.
+    // If the dtor field is tagged by the lowest bit, it holds the weak cell of the object (see `weak_cell_type`),
+    // and the object is destructed by the runtime function which expires the cell.
     pub fn call_dtor(&self, obj: PointerValue<'c>) {
         let ptr_to_dtor = self
             .load_obj_field(obj, control_block_type(self.context), 1)
             .into_pointer_value();
+        let i64_type = self.context.i64_type();
+        let dtor_addr = self
+            .builder()
+            .build_ptr_to_int(ptr_to_dtor, i64_type, "dtor_addr");
+        let tag = self
+            .builder()
+            .build_and(dtor_addr, i64_type.const_int(1, false), "dtor_tag");
+        let has_weak_cell = self.builder().build_int_compare(
+            IntPredicate::NE,
+            tag,
+            i64_type.const_zero(),
+            "has_weak_cell",
+        );
+        let curr_func = self
+            .builder()
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let weak_target_bb = self.context.append_basic_block(curr_func, "weak_target_bb");
+        let dtor_bb = self.context.append_basic_block(curr_func, "dtor_bb");
+        let cont_bb = self.context.append_basic_block(curr_func, "dtor_cont_bb");
+        self.builder()
+            .build_conditional_branch(has_weak_cell, weak_target_bb, dtor_bb);
+
+        self.builder().position_at_end(weak_target_bb);
+        self.call_runtime(RuntimeFunctions::DtorWeakTarget, &[obj.into()]);
+        self.builder().build_unconditional_branch(cont_bb);
+
+        self.builder().position_at_end(dtor_bb);
         let dtor_func = CallableValue::try_from(ptr_to_dtor).unwrap();
         self.builder()
             .build_call(dtor_func, &[obj.into()], "call_dtor");
+        self.builder().build_unconditional_branch(cont_bb);
+
+        self.builder().position_at_end(cont_bb);
     }
 
     // Take an pointer of struct and return the loaded value of a pointer field.
@@ -279,6 +361,8 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         ptr_to_lambda: PointerValue<'c>,
         ptr_to_arg: PointerValue<'c>,
     ) -> PointerValue<'c> {
+        self.panic_if_null(ptr_to_lambda);
+        self.panic_if_null(ptr_to_arg);
         let ptr_to_func = self.get_lambda_func_ptr(ptr_to_lambda);
         let lambda_func = CallableValue::try_from(ptr_to_func).unwrap();
         let ret = self.builder().build_call(
@@ -286,7 +370,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             &[ptr_to_arg.into(), ptr_to_lambda.into()],
             "call_lambda",
         );
-        ret.set_tail_call(true);
+        ret.set_tail_call(!self.disable_tail_calls);
         ret.try_as_basic_value().unwrap_left().into_pointer_value()
     }
 
@@ -302,6 +386,20 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         self.call_runtime(RuntimeFunctions::ReleaseObj, &[ptr_to_obj.clone().into()]);
     }
 
+    // Get the weak cell of object (which is created if the object has none) for a new weak reference.
+    pub fn retain_weak_cell(&self, ptr_to_obj: PointerValue<'c>) -> PointerValue<'c> {
+        let ptr_to_obj = self.cast_pointer(ptr_to_obj, ptr_to_object_type(self.context));
+        self.call_runtime(RuntimeFunctions::RetainWeakCell, &[ptr_to_obj.into()])
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_pointer_value()
+    }
+
+    // Release weak reference to weak cell.
+    pub fn release_weak_cell(&self, ptr_to_cell: PointerValue<'c>) {
+        self.call_runtime(RuntimeFunctions::ReleaseWeakCell, &[ptr_to_cell.into()]);
+    }
+
     // Printf Rust's &str.
     pub fn printf(&self, string: &str) {
         let string_ptr = self.builder().build_global_string_ptr(string, "rust_str");
@@ -315,6 +413,40 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         self.call_runtime(RuntimeFunctions::Abort, &[]);
     }
 
+    // Terminate current basic block by unreachable, and continue generation in a new basic block without predecessors.
+    // Used after code that never returns (e.g., panic); code generated afterwards is dead.
+    pub fn build_unreachable_and_continue(&self) {
+        let curr_bb = self.builder().get_insert_block().unwrap();
+        let curr_func = curr_bb.get_parent().unwrap();
+        self.builder().build_unreachable();
+        let dead_bb = self.context.append_basic_block(curr_func, "dead_bb");
+        self.builder().position_at_end(dead_bb);
+    }
+
+    // Check if a basic block is never reached, i.e., it has no predecessors and is not an entry block.
+    pub fn is_dead_block(&self, bb: BasicBlock<'c>) -> bool {
+        let func = bb.get_parent().unwrap();
+        bb.get_first_use().is_none() && func.get_first_basic_block() != Some(bb)
+    }
+
+    // Panic if the pointer to object is null (only in sanitize mode).
+    pub fn panic_if_null(&self, ptr_to_obj: PointerValue<'c>) {
+        if !SANITIZE_MEMORY {
+            return;
+        }
+        let is_null = self.builder().build_is_null(ptr_to_obj, "is_null");
+        let curr_bb = self.builder().get_insert_block().unwrap();
+        let curr_func = curr_bb.get_parent().unwrap();
+        let null_bb = self.context.append_basic_block(curr_func, "null_bb");
+        let nonnull_bb = self.context.append_basic_block(curr_func, "nonnull_bb");
+        self.builder()
+            .build_conditional_branch(is_null, null_bb, nonnull_bb);
+        self.builder().position_at_end(null_bb);
+        self.panic("Null object dereference!\n");
+        self.builder().build_unreachable();
+        self.builder().position_at_end(nonnull_bb);
+    }
+
     // Get object id of a object
     pub fn get_obj_id(&self, ptr_to_obj: PointerValue<'c>) -> IntValue<'c> {
         assert!(SANITIZE_MEMORY);
@@ -343,6 +475,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             Expr::If(cond_expr, then_expr, else_expr) => {
                 self.eval_if(cond_expr.clone(), then_expr.clone(), else_expr.clone())
             }
+            Expr::Case(scrutinee, arms) => self.eval_case(scrutinee.clone(), arms),
             Expr::Type(_) => todo!(),
         };
         self.cast_pointer(ret, ptr_to_object_type(self.context))
@@ -378,16 +511,30 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         let mut captured_names = val.free_vars.clone();
         captured_names.remove(arg.name());
         captured_names.remove(SELF_NAME);
-        let captured_names: Vec<String> = captured_names.into_iter().collect();
-        // Determine the type of closure
+        let mut captured_names: Vec<String> = captured_names.into_iter().collect();
+        // Sort names so that the layout of closure (and hence the generated code) is deterministic.
+        captured_names.sort();
+        // Determine the type of closure.
+        // Closure = [ControlBlock, LambdaFunction, Captures...], or [ControlBlock, LambdaFunction, ArrayOfCaptures] if captures are packed.
+        let pack_captures = self
+            .max_closure_captures
+            .map_or(false, |max| captured_names.len() > max);
         let mut field_types = vec![
             ObjectFieldType::ControlBlock,
             ObjectFieldType::LambdaFunction,
         ];
-        for _ in captured_names.iter() {
+        if pack_captures {
             field_types.push(ObjectFieldType::SubObject);
+        } else {
+            for _ in captured_names.iter() {
+                field_types.push(ObjectFieldType::SubObject);
+            }
         }
-        let obj_type = ObjectType { field_types };
+        let obj_type = ObjectType {
+            field_types,
+            finalizer: None,
+            field_order: None,
+        };
         let closure_ty = obj_type.to_struct_type(context);
         // Declare lambda function
         let lam_fn_ty = lambda_function_type(context);
@@ -407,11 +554,23 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             self.scope_push(&arg.name(), &arg_ptr);
             let closure_obj = lam_fn.get_nth_param(1).unwrap().into_pointer_value();
             self.scope_push(SELF_NAME, &closure_obj);
-            for (i, cap_name) in captured_names.iter().enumerate() {
-                let cap_obj = self
-                    .load_obj_field(closure_obj, closure_ty, i as u32 + 2)
+            if pack_captures {
+                let captures = self
+                    .load_obj_field(closure_obj, closure_ty, 2)
                     .into_pointer_value();
-                self.scope_push(cap_name, &cap_obj);
+                let captures = self.array_field_of(captures);
+                for (i, cap_name) in captured_names.iter().enumerate() {
+                    let cap_obj =
+                        ObjectFieldType::load_array_element_unchecked(self, captures, i as u64);
+                    self.scope_push(cap_name, &cap_obj);
+                }
+            } else {
+                for (i, cap_name) in captured_names.iter().enumerate() {
+                    let cap_obj = self
+                        .load_obj_field(closure_obj, closure_ty, i as u32 + 2)
+                        .into_pointer_value();
+                    self.scope_push(cap_name, &cap_obj);
+                }
             }
             // Retain captured objects
             for cap_name in &captured_names {
@@ -433,21 +592,42 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         }
         // Allocate and set up closure
         let name = lam(arg, val).expr.to_string();
-        let obj = obj_type.create_obj(self, Some(name.as_str()));
+        let obj = obj_type.create_obj(self, name.as_str());
         self.store_obj_field(
             obj,
             closure_ty,
             1,
             lam_fn.as_global_value().as_pointer_value(),
         );
-        for (i, cap) in captured_names.iter().enumerate() {
-            let ptr = self.get_var_retained_if_used_later(cap);
-            self.store_obj_field(obj, closure_ty, i as u32 + 2, ptr);
+        if pack_captures {
+            let captures: Vec<PointerValue<'c>> = captured_names
+                .iter()
+                .map(|cap| self.get_var_retained_if_used_later(cap))
+                .collect();
+            let captures_name = format!("captures of {}", name);
+            let array = ObjectType::array_type().create_obj(self, captures_name.as_str());
+            let array_field = self.array_field_of(array);
+            ObjectFieldType::initialize_array_with_elements(self, array_field, &captures);
+            self.store_obj_field(obj, closure_ty, 2, array);
+        } else {
+            for (i, cap) in captured_names.iter().enumerate() {
+                let ptr = self.get_var_retained_if_used_later(cap);
+                self.store_obj_field(obj, closure_ty, i as u32 + 2, ptr);
+            }
         }
         // Return closure object
         obj
     }
 
+    // Get pointer to the array field of an array object.
+    fn array_field_of(&self, array: PointerValue<'c>) -> PointerValue<'c> {
+        let array_ptr_ty = ptr_type(ObjectType::array_type().to_struct_type(self.context));
+        let array = self.cast_pointer(array, array_ptr_ty);
+        self.builder()
+            .build_struct_gep(array, 1, "array_field")
+            .unwrap()
+    }
+
     // Evaluate let
     fn eval_let(
         &mut self,
@@ -461,13 +641,11 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         self.scope_lock_as_used_later(&used_in_val_except_var);
         let bound_code = self.eval_expr(bound.clone());
         self.scope_unlock_as_used_later(&used_in_val_except_var);
-        self.scope_push(&var_name, &bound_code);
+        let _var_guard = self.push_variable(&var_name, &bound_code);
         if !val.free_vars.contains(var_name) {
             self.release(bound_code);
         }
-        let val_code = self.eval_expr(val.clone());
-        self.scope_pop(&var_name);
-        val_code
+        self.eval_expr(val.clone())
     }
 
     // Evaluate if
@@ -477,6 +655,11 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         then_expr: Arc<ExprInfo>,
         else_expr: Arc<ExprInfo>,
     ) -> PointerValue<'c> {
+        // A variable live before the if is released along every path exactly once:
+        // - if it is used in neither branch, it is used in cond_expr (otherwise the enclosing let or lambda has released it already), and the last use in cond_expr consumes it,
+        // - if it is used in only one branch, the other branch releases it at its beginning,
+        // - if it is used in both branches, each branch consumes it.
+        // Variables which are used after the if (used_later > 0) are never released here.
         let mut used_then_or_else = then_expr.free_vars.clone();
         used_then_or_else.extend(else_expr.free_vars.clone());
         self.scope_lock_as_used_later(&used_then_or_else);
@@ -500,31 +683,172 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
 
         self.builder().position_at_end(then_bb);
         // Release variables used only in the else block.
-        for var_name in &else_expr.free_vars {
+        for var_name in sorted_names(&else_expr.free_vars) {
             if !then_expr.free_vars.contains(var_name) && self.scope_get(var_name).used_later == 0 {
                 self.release(self.scope_get(var_name).ptr);
             }
         }
         let then_code = self.eval_expr(then_expr.clone());
-        self.builder().build_unconditional_branch(cont_bb);
+        // Generating then_expr may add basic blocks, so take the block at the end of the branch as incoming block.
+        let then_end_bb = self.builder().get_insert_block().unwrap();
+        self.build_branch_to_cont(then_end_bb, cont_bb);
 
         self.builder().position_at_end(else_bb);
         // Release variables used only in the then block.
-        for var_name in &then_expr.free_vars {
+        for var_name in sorted_names(&then_expr.free_vars) {
             if !else_expr.free_vars.contains(var_name) && self.scope_get(var_name).used_later == 0 {
                 self.release(self.scope_get(var_name).ptr);
             }
         }
         let else_code = self.eval_expr(else_expr);
-        self.builder().build_unconditional_branch(cont_bb);
+        let else_end_bb = self.builder().get_insert_block().unwrap();
+        self.build_branch_to_cont(else_end_bb, cont_bb);
+
+        self.builder().position_at_end(cont_bb);
+        // Only branches which reach cont_bb are incomings of phi.
+        let incomings: Vec<(&dyn BasicValue<'c>, BasicBlock<'c>)> =
+            [(&then_code, then_end_bb), (&else_code, else_end_bb)]
+                .into_iter()
+                .filter(|(_, bb)| !self.is_dead_block(*bb))
+                .map(|(code, bb)| (code as &dyn BasicValue<'c>, bb))
+                .collect();
+        if incomings.is_empty() {
+            // Neither branch reaches here, so cont_bb is a dead block and its value is never used.
+            return ptr_to_object_type(self.context).get_undef();
+        }
+        let phi = self
+            .builder()
+            .build_phi(ptr_to_object_type(self.context), "phi");
+        phi.add_incoming(&incomings);
+        phi.as_basic_value().into_pointer_value()
+    }
+
+    // Evaluate case.
+    // Patterns are tested in order by a chain of comparisons. If no pattern matches, the program aborts.
+    fn eval_case(
+        &mut self,
+        scrutinee: Arc<ExprInfo>,
+        arms: &[(Arc<Pattern>, Arc<ExprInfo>)],
+    ) -> PointerValue<'c> {
+        let mut used_in_arms: HashSet<String> = Default::default();
+        for (_, arm) in arms {
+            used_in_arms.extend(arm.free_vars.iter().cloned());
+        }
+        self.scope_lock_as_used_later(&used_in_arms);
+        let ptr_to_scrutinee = self.eval_expr(scrutinee);
+        self.scope_unlock_as_used_later(&used_in_arms);
+
+        // Load the value of scrutinee if some pattern is tested (patterns after a wildcard are never tested).
+        // Its type is determined by the first pattern, since patterns have the same type (see `check_case_patterns`).
+        let value = match arms.first().map(|(pat, _)| &**pat) {
+            Some(Pattern::Int(_)) => Some(int_type(self.context)),
+            Some(Pattern::Bool(_)) => Some(bool_type(self.context)),
+            Some(Pattern::Wildcard) | None => None,
+        }
+        .map(|ty| {
+            self.load_obj_field(ptr_to_scrutinee, ty, 1)
+                .into_int_value()
+        });
+        self.release(ptr_to_scrutinee);
+
+        let bb = self.builder().get_insert_block().unwrap();
+        let func = bb.get_parent().unwrap();
+        let cont_bb = self.context.append_basic_block(func, "cont");
+        let mut incomings: Vec<(PointerValue<'c>, BasicBlock<'c>)> = vec![];
+        for (pat, arm) in arms {
+            // Test pattern.
+            let arm_bb = self.context.append_basic_block(func, "arm");
+            let next_bb = match **pat {
+                Pattern::Wildcard => {
+                    self.builder().build_unconditional_branch(arm_bb);
+                    None
+                }
+                Pattern::Int(_) | Pattern::Bool(_) => {
+                    let value = value.unwrap();
+                    let matched = match **pat {
+                        Pattern::Int(val) => self.builder().build_int_compare(
+                            IntPredicate::EQ,
+                            value,
+                            value.get_type().const_int(val as u64, false),
+                            "matched",
+                        ),
+                        Pattern::Bool(val) => self.builder().build_int_compare(
+                            if val {
+                                IntPredicate::NE
+                            } else {
+                                IntPredicate::EQ
+                            },
+                            value,
+                            value.get_type().const_zero(),
+                            "matched",
+                        ),
+                        Pattern::Wildcard => unreachable!(),
+                    };
+                    let next_bb = self.context.append_basic_block(func, "next");
+                    self.builder()
+                        .build_conditional_branch(matched, arm_bb, next_bb);
+                    Some(next_bb)
+                }
+            };
+
+            // Evaluate arm, after releasing variables used only in other arms.
+            self.builder().position_at_end(arm_bb);
+            for var_name in sorted_names(&used_in_arms) {
+                if !arm.free_vars.contains(var_name) && self.scope_get(var_name).used_later == 0 {
+                    self.release(self.scope_get(var_name).ptr);
+                }
+            }
+            let arm_code = self.eval_expr(arm.clone());
+            let arm_end_bb = self.builder().get_insert_block().unwrap();
+            self.build_branch_to_cont(arm_end_bb, cont_bb);
+            if !self.is_dead_block(arm_end_bb) {
+                incomings.push((arm_code, arm_end_bb));
+            }
+
+            match next_bb {
+                Some(next_bb) => self.builder().position_at_end(next_bb),
+                // Arms after a wildcard are never taken.
+                None => break,
+            }
+        }
+        let last_bb = self.builder().get_insert_block().unwrap();
+        if last_bb.get_terminator().is_none() {
+            // No pattern matched.
+            self.panic("No pattern matches in case!\n");
+            self.builder().build_unreachable();
+        }
 
         self.builder().position_at_end(cont_bb);
+        if incomings.is_empty() {
+            // No arm reaches here, so cont_bb is a dead block and its value is never used.
+            return ptr_to_object_type(self.context).get_undef();
+        }
+        let incomings: Vec<(&dyn BasicValue<'c>, BasicBlock<'c>)> = incomings
+            .iter()
+            .map(|(code, bb)| (code as &dyn BasicValue<'c>, *bb))
+            .collect();
         let phi = self
             .builder()
             .build_phi(ptr_to_object_type(self.context), "phi");
-        phi.add_incoming(&[(&then_code, then_bb), (&else_code, else_bb)]);
+        phi.add_incoming(&incomings);
         phi.as_basic_value().into_pointer_value()
     }
+
+    // Jump from the end of a branch of if to the continuation, unless the end of the branch is never reached.
+    fn build_branch_to_cont(&self, end_bb: BasicBlock<'c>, cont_bb: BasicBlock<'c>) {
+        if self.is_dead_block(end_bb) {
+            self.builder().build_unreachable();
+        } else {
+            self.builder().build_unconditional_branch(cont_bb);
+        }
+    }
+}
+
+// Sort names in a set, so that code generated for each of them is emitted in a deterministic order.
+fn sorted_names(names: &HashSet<String>) -> Vec<&String> {
+    let mut names: Vec<&String> = names.iter().collect();
+    names.sort();
+    names
 }
 
 pub fn ptr_type<'c>(ty: StructType<'c>) -> PointerType<'c> {
@@ -9,11 +9,14 @@ extern crate serial_test;
 mod ast;
 mod builtin;
 mod generator;
+mod optimizer;
 mod parser;
+mod repl;
 mod runner;
 mod runtime;
 #[cfg(test)]
 mod tests;
+mod typecheck;
 mod types;
 
 use ast::*;
@@ -24,49 +27,118 @@ use generator::*;
 use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
-use inkwell::module::Module;
+use inkwell::execution_engine::{ExecutionEngine, JitFunction};
+use inkwell::module::{Linkage, Module};
+use inkwell::passes::{PassManager, PassManagerBuilder};
 use inkwell::support::load_library_permanently;
-use inkwell::types::{BasicTypeEnum, FunctionType, IntType, PointerType, StructType};
+use inkwell::targets::{
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
+};
+use inkwell::types::{
+    BasicMetadataTypeEnum, BasicTypeEnum, FunctionType, IntType, PointerType, StructType,
+};
 use inkwell::values::{
-    BasicValue, BasicValueEnum, CallableValue, FunctionValue, IntValue, PointerValue,
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, CallableValue, FunctionValue, IntValue,
+    PointerValue,
 };
 use inkwell::{AddressSpace, IntPredicate, OptimizationLevel};
 use once_cell::sync::Lazy;
+use optimizer::*;
 use parser::*;
+use pest::error::InputLocation;
 use pest::iterators::{Pair, Pairs};
 use pest::Parser;
+use repl::*;
 use runner::*;
 use runtime::*;
 use std::alloc::System;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::fmt::Pointer;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::io::{BufRead, Read, Write};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
 use std::ptr::null;
 use std::string;
 use std::sync::Arc;
 use std::thread::panicking;
+use std::time::{Duration, Instant};
 use std::vec::Vec;
+use typecheck::*;
 use types::*;
 use Either::Right;
 
-const SANITIZE_MEMORY: bool = true;
+// Build objects with ids reported to the sanitizer (libfixsanitizer.so), which checks refcounts and leaks.
+// Building with feature `no-sanitize` removes ids from control blocks and all calls to the sanitizer.
+const SANITIZE_MEMORY: bool = !cfg!(feature = "no-sanitize");
 
 fn main() {
     let source_file = Arg::new("source-file").required(true);
-    let run_subcom = App::new("run").arg(source_file);
+    let time_passes = Arg::new("time-passes")
+        .long("time-passes")
+        .help("Report time spent in each phase of compilation and execution.");
+    let disable_tail_calls = Arg::new("disable-tail-calls")
+        .long("disable-tail-calls")
+        .help(
+        "Do not mark calls as tail calls (for debugging; deep recursion may overflow the stack).",
+    );
+    let optimize = Arg::new("optimize")
+        .long("optimize")
+        .help("Run optimization passes on AST (e.g., elimination of unused lets).");
+    let opt_remarks = Arg::new("opt-remarks")
+        .long("opt-remarks")
+        .requires("optimize")
+        .help("Report what optimization passes did to the program.");
+    let dump_runtime_decls = Arg::new("dump-runtime-decls")
+        .long("dump-runtime-decls")
+        .help("Print signatures of runtime functions (for checking ABI of the runtime library).");
+    let strict = Arg::new("strict")
+        .long("strict")
+        .help("Treat lints (e.g., unused let bindings) as errors.");
+    let run_subcom = App::new("run")
+        .arg(source_file)
+        .arg(time_passes)
+        .arg(disable_tail_calls)
+        .arg(optimize)
+        .arg(opt_remarks)
+        .arg(dump_runtime_decls)
+        .arg(strict);
+    let repl_subcom = App::new("repl").about("Evaluate expressions entered line by line.");
     let app = App::new("Fix-lang")
         .bin_name("fix")
         .setting(AppSettings::ArgRequiredElseHelp)
-        .subcommand(run_subcom);
+        .subcommand(run_subcom)
+        .subcommand(repl_subcom);
 
     match app.get_matches().subcommand() {
         Some(("run", m)) => {
             let path = m.value_of("source-file").unwrap();
-            let res = run_file(Path::new(path), OptimizationLevel::Default);
-            println!("{}", res);
+            let options = CompileOptions {
+                time_passes: m.is_present("time-passes"),
+                disable_tail_calls: m.is_present("disable-tail-calls"),
+                ast_optimization: m.is_present("optimize"),
+                opt_remarks: m.is_present("opt-remarks"),
+                dump_runtime_decls: m.is_present("dump-runtime-decls"),
+                strict: m.is_present("strict"),
+                ..Default::default()
+            };
+            match run_file(Path::new(path), OptimizationLevel::Default, &options) {
+                Ok(res) => println!("{}", res),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(("repl", _)) => {
+            let mut repl = Repl::new(OptimizationLevel::Default, &CompileOptions::default());
+            let stdin = std::io::stdin();
+            if let Err(err) = repl.run(stdin.lock(), std::io::stdout()) {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
         }
         _ => eprintln!("Unknown command!"),
     }
@@ -0,0 +1,598 @@
+// optimizer module
+// --
+// Optimization passes on AST, and remarks reporting what they did.
+
+use super::*;
+
+// A remark emitted by an optimization pass.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptRemark {
+    // Name of the pass which emitted the remark.
+    pub pass: &'static str,
+    // Span of the expression the remark is about, if it comes from source.
+    pub location: Option<Span>,
+    pub message: String,
+}
+
+impl std::fmt::Display for OptRemark {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.pass, self.message)?;
+        if let Some(span) = &self.location {
+            write!(f, " at {}..{}", span.start, span.end)?;
+        }
+        Ok(())
+    }
+}
+
+// Library functions which always terminate without side effects, as pairs of name and arity.
+// An application of them to at most arity arguments can be removed if its value is unused.
+// NOTE: `div` and `mod` are not pure since they abort on division by zero.
+// NOTE: `seq` must not be added here, since it is used to force evaluation of its first argument explicitly.
+const PURE_BUILTINS: [(&str, usize); 29] = [
+    ("add", 2),
+    ("sub", 2),
+    ("neg", 1),
+    ("mul", 2),
+    ("pow", 2),
+    ("addF", 2),
+    ("mulF", 2),
+    ("eq", 2),
+    ("lt", 2),
+    ("le", 2),
+    ("gt", 2),
+    ("ge", 2),
+    ("not", 1),
+    ("and", 2),
+    ("or", 2),
+    ("pair", 2),
+    ("fst", 1),
+    ("snd", 1),
+    ("some", 1),
+    ("isSome", 1),
+    ("fromOption", 2),
+    ("lenArray", 1),
+    ("eqArray", 2),
+    ("takeArray", 2),
+    ("dropArray", 2),
+    ("splitAtArray", 2),
+    ("rotateArray", 2),
+    ("concatStr", 2),
+    ("lengthStr", 1),
+];
+
+// Pass which removes let bindings whose variable is unused and whose bound expression is pure.
+#[derive(Default)]
+struct DeadLetEliminator {
+    // Names bound by enclosing lets / lambdas, which may shadow library functions.
+    bound_names: Vec<String>,
+    remarks: Vec<OptRemark>,
+}
+
+impl DeadLetEliminator {
+    // Check if an expression can be removed without changing behavior of the program when its value is unused.
+    // This is conservative: applications are pure only if they are (partial) applications of pure library functions.
+    // Names bound inside the expression are tracked as well as enclosing ones, since they may shadow library functions.
+    fn is_pure(&mut self, ei: &Arc<ExprInfo>) -> bool {
+        match &*ei.expr {
+            Expr::Var(_) => true,
+            Expr::Lit(_) => true,
+            Expr::Lam(_, _) => true,
+            Expr::App(_, _) => {
+                let (head, args) = unfold_app(ei);
+                let name = match &*head.expr {
+                    Expr::Var(var) => var.name(),
+                    _ => return false,
+                };
+                if self.bound_names.contains(name) {
+                    return false;
+                }
+                let is_pure_app = PURE_BUILTINS
+                    .iter()
+                    .any(|(n, arity)| n == name && args.len() <= *arity);
+                is_pure_app && args.iter().all(|arg| self.is_pure(arg))
+            }
+            Expr::Let(var, bound, val) => {
+                if !self.is_pure(bound) {
+                    return false;
+                }
+                self.enter_binder(var);
+                let is_pure = self.is_pure(val);
+                self.exit_binder(var);
+                is_pure
+            }
+            Expr::If(cond, then_expr, else_expr) => {
+                self.is_pure(cond) && self.is_pure(then_expr) && self.is_pure(else_expr)
+            }
+            // A case without wildcard arm aborts if no pattern matches.
+            Expr::Case(scrutinee, arms) => {
+                arms.iter().any(|(pat, _)| **pat == Pattern::Wildcard)
+                    && self.is_pure(scrutinee)
+                    && arms.iter().all(|(_, arm)| self.is_pure(arm))
+            }
+            Expr::Type(_) => true,
+        }
+    }
+}
+
+impl Folder for DeadLetEliminator {
+    // Each rebuilt node gets free variables from its subexpressions, so that a let binding which becomes unused by elimination in its body is also found.
+    fn fold_expr(&mut self, ei: Arc<ExprInfo>) -> Arc<ExprInfo> {
+        fold_subexprs(self, ei).with_free_vars_of_subexprs()
+    }
+    fn enter_binder(&mut self, var: &Arc<Var>) {
+        self.bound_names.push(var.name().clone());
+    }
+    fn exit_binder(&mut self, _var: &Arc<Var>) {
+        self.bound_names.pop();
+    }
+    fn fold_let(
+        &mut self,
+        ei: Arc<ExprInfo>,
+        var: &Arc<Var>,
+        bound: Arc<ExprInfo>,
+        val: Arc<ExprInfo>,
+    ) -> Arc<ExprInfo> {
+        if !val.free_vars.contains(var.name()) && self.is_pure(&bound) {
+            self.remarks.push(OptRemark {
+                pass: "dead-let",
+                location: bound.source,
+                message: format!("unused let binding of `{}` is eliminated", var.name()),
+            });
+            return val;
+        }
+        let_in(var.clone(), bound, val).with_source_of(&ei)
+    }
+}
+
+// Get the head and arguments of a (nested) application, e.g., (f, [a, b]) for `f a b`.
+fn unfold_app(ei: &Arc<ExprInfo>) -> (Arc<ExprInfo>, Vec<Arc<ExprInfo>>) {
+    let mut args = vec![];
+    let mut head = ei.clone();
+    while let Expr::App(func, arg) = &*head.expr.clone() {
+        args.push(arg.clone());
+        head = func.clone();
+    }
+    args.reverse();
+    (head, args)
+}
+
+// Pass which replaces `iterate n (add k) x` by a fused version, which neither calls closures nor allocates objects per step.
+#[derive(Default)]
+struct IterateFuser {
+    // Names bound by enclosing lets / lambdas, which may shadow library functions.
+    bound_names: Vec<String>,
+    remarks: Vec<OptRemark>,
+}
+
+impl IterateFuser {
+    // Check if an expression is a variable referring to a given library function.
+    fn is_builtin(&self, ei: &Arc<ExprInfo>, name: &str) -> bool {
+        match &*ei.expr {
+            Expr::Var(var) => var.name() == name && !self.bound_names.iter().any(|n| n == name),
+            _ => false,
+        }
+    }
+
+    // If an expression is `iterate n (add k) x`, get n, k and x.
+    fn match_iterate_add(
+        &self,
+        ei: &Arc<ExprInfo>,
+    ) -> Option<(Arc<ExprInfo>, Arc<ExprInfo>, Arc<ExprInfo>)> {
+        let (head, args) = unfold_app(ei);
+        if args.len() != 3 || !self.is_builtin(&head, "iterate") {
+            return None;
+        }
+        let (step_head, step_args) = unfold_app(&args[1]);
+        if step_args.len() != 1 || !self.is_builtin(&step_head, "add") {
+            return None;
+        }
+        Some((args[0].clone(), step_args[0].clone(), args[2].clone()))
+    }
+}
+
+impl Folder for IterateFuser {
+    fn enter_binder(&mut self, var: &Arc<Var>) {
+        self.bound_names.push(var.name().clone());
+    }
+    fn exit_binder(&mut self, _var: &Arc<Var>) {
+        self.bound_names.pop();
+    }
+    fn fold_expr(&mut self, ei: Arc<ExprInfo>) -> Arc<ExprInfo> {
+        if let Some((n, k, x)) = self.match_iterate_add(&ei) {
+            self.remarks.push(OptRemark {
+                pass: "fuse-iterate",
+                location: ei.source,
+                message: String::from(
+                    "iterate of `add` is fused into a loop without closure calls",
+                ),
+            });
+            let (n, k, x) = (self.fold_expr(n), self.fold_expr(k), self.fold_expr(x));
+            return app(app(app(var(ITERATE_ADD_NAME), n), k), x).with_source_of(&ei);
+        }
+        fold_subexprs(self, ei)
+    }
+}
+
+// Recursive function `fix (\f -> \x -> body)` to be unrolled.
+struct FixDef {
+    f: Arc<Var>,
+    x: Arc<Var>,
+    body: Arc<ExprInfo>,
+    // Free variables of the function, which must not be shadowed where its body is inlined.
+    free_vars: HashSet<String>,
+}
+
+// Pass which unrolls recursive functions defined by `fix` at applications to int literals, e.g., `g 3` for
+// `let g = fix (\f -> \x -> body)`, by inlining `body[x := 3]`. Conditions of ifs which become constant are folded,
+// and recursive calls to constants are inlined in turn, up to a bounded number of inlined bodies.
+// Recursive calls beyond the bound (or to non-constant arguments) fall back to the general recursion.
+struct FixUnroller {
+    // Maximum number of bodies inlined at an application.
+    max_depth: usize,
+    // Names bound by enclosing lets / lambdas (which may shadow library functions),
+    // with the recursive function if the name is bound by a let to one.
+    scope: Vec<(String, Option<Arc<FixDef>>)>,
+    remarks: Vec<OptRemark>,
+}
+
+impl FixUnroller {
+    // Check if a name is bound by an enclosing let / lambda.
+    fn is_bound(&self, name: &str) -> bool {
+        self.scope.iter().any(|(n, _)| n == name)
+    }
+
+    // If an expression is `fix (\f -> \x -> body)` or its eta-expansion `\n -> fix (\f -> \x -> body) n`, get the function.
+    fn match_fix_def(&self, ei: &Arc<ExprInfo>) -> Option<FixDef> {
+        let fix_app = match &*ei.expr {
+            Expr::Lam(n, val) => match &*val.expr {
+                Expr::App(func, arg) if matches!(&*arg.expr, Expr::Var(v) if v.name() == n.name()) => {
+                    func.clone()
+                }
+                _ => return None,
+            },
+            _ => ei.clone(),
+        };
+        let fix_lam = match &*fix_app.expr {
+            Expr::App(func, arg) => match &*func.expr {
+                Expr::Var(var) if var.name() == "fix" && !self.is_bound("fix") => arg.clone(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let (f, x, body) = match &*fix_lam.expr {
+            Expr::Lam(f, inner) => match &*inner.expr {
+                Expr::Lam(x, body) => (f.clone(), x.clone(), body.clone()),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        // Only a function whose free variables are all library functions is unrolled,
+        // since applications of library functions in the inlined body are evaluated at compile time.
+        let free_vars = calculate_free_vars(fix_lam).free_vars.clone();
+        if free_vars.iter().any(|name| self.is_bound(name)) {
+            return None;
+        }
+        if let Expr::Lam(n, _) = &*ei.expr {
+            if free_vars.contains(n.name()) {
+                return None;
+            }
+        }
+        Some(FixDef {
+            f,
+            x,
+            body,
+            free_vars,
+        })
+    }
+
+    // If an expression is an application of a recursive function to an int literal, get the function,
+    // the expression referring to it (to which recursive calls beyond the bound fall back) and the argument.
+    fn match_fix_app(&self, ei: &Arc<ExprInfo>) -> Option<(Arc<FixDef>, Arc<ExprInfo>, i64)> {
+        let (func, arg) = match &*ei.expr {
+            Expr::App(func, arg) => (func, arg),
+            _ => return None,
+        };
+        let n = match &*arg.expr {
+            Expr::Lit(lit) if lit.ty == Some(int_lit_ty()) => {
+                arg.expr.to_string().parse::<i64>().ok()?
+            }
+            _ => return None,
+        };
+        if let Expr::Var(var) = &*func.expr {
+            // A variable bound by a let to a recursive function, whose free variables are not shadowed since then.
+            let i = self
+                .scope
+                .iter()
+                .rposition(|(name, _)| name == var.name())?;
+            let def = self.scope[i].1.clone()?;
+            if self.scope[i + 1..]
+                .iter()
+                .any(|(name, _)| def.free_vars.contains(name))
+            {
+                return None;
+            }
+            return Some((def, func.clone(), n));
+        }
+        let def = self.match_fix_def(func)?;
+        Some((Arc::new(def), func.clone(), n))
+    }
+}
+
+impl Folder for FixUnroller {
+    fn enter_binder(&mut self, var: &Arc<Var>) {
+        self.scope.push((var.name().clone(), None));
+    }
+    fn exit_binder(&mut self, _var: &Arc<Var>) {
+        self.scope.pop();
+    }
+    fn fold_expr(&mut self, ei: Arc<ExprInfo>) -> Arc<ExprInfo> {
+        // Remember recursive functions bound by lets.
+        if let Expr::Let(var, bound, val) = &*ei.expr {
+            let bound = self.fold_expr(bound.clone());
+            let def = self.match_fix_def(&bound).map(Arc::new);
+            self.scope.push((var.name().clone(), def));
+            let val = self.fold_expr(val.clone());
+            self.scope.pop();
+            return self.fold_let(ei, var, bound, val);
+        }
+        let ei = fold_subexprs(self, ei);
+        if let Some((def, func, n)) = self.match_fix_app(&ei) {
+            let mut inliner = FixBodyInliner {
+                def: &def,
+                x_val: n,
+                budget: self.max_depth,
+                binders: vec![],
+            };
+            let unrolled = match inliner.inline(n) {
+                Some(body) => body,
+                None => return ei,
+            };
+            let count = self.max_depth - inliner.budget;
+            self.remarks.push(OptRemark {
+                pass: "unroll-fix",
+                location: ei.source,
+                message: format!(
+                    "recursion of `{}` is unrolled {} times",
+                    def.f.name(),
+                    count
+                ),
+            });
+            // Recursive calls left in the unrolled expression refer to the function itself.
+            let unrolled = if calculate_free_vars(unrolled.clone())
+                .free_vars
+                .contains(def.f.name())
+            {
+                let_in(def.f.clone(), func, unrolled)
+            } else {
+                unrolled
+            };
+            return unrolled.with_source_of(&ei);
+        }
+        ei
+    }
+}
+
+// Folder which inlines the body of a recursive function for an argument, folding constant conditions of ifs and
+// inlining recursive calls to constants in turn while the budget of inlined bodies remains.
+struct FixBodyInliner<'a> {
+    def: &'a FixDef,
+    // Value of the argument of the body being inlined.
+    x_val: i64,
+    // Number of bodies which can be inlined further.
+    budget: usize,
+    // Names bound inside the body being inlined. Constants are folded and calls are inlined only where this is empty,
+    // since library functions and free variables of the function may be shadowed otherwise.
+    binders: Vec<String>,
+}
+
+impl<'a> FixBodyInliner<'a> {
+    // Get the body of the function for an argument, or None if the budget is used up.
+    fn inline(&mut self, x_val: i64) -> Option<Arc<ExprInfo>> {
+        if self.budget == 0 {
+            return None;
+        }
+        self.budget -= 1;
+        let outer = std::mem::replace(&mut self.x_val, x_val);
+        let body = self.fold_expr(self.def.body.clone());
+        self.x_val = outer;
+        Some(body)
+    }
+}
+
+impl<'a> Folder for FixBodyInliner<'a> {
+    fn enter_binder(&mut self, var: &Arc<Var>) {
+        self.binders.push(var.name().clone());
+    }
+    fn exit_binder(&mut self, _var: &Arc<Var>) {
+        self.binders.pop();
+    }
+    fn fold_var(&mut self, ei: Arc<ExprInfo>, var: &Arc<Var>) -> Arc<ExprInfo> {
+        if var.name() == self.def.x.name() && !self.binders.contains(var.name()) {
+            return int(self.x_val).with_source_of(&ei);
+        }
+        ei
+    }
+    fn fold_expr(&mut self, ei: Arc<ExprInfo>) -> Arc<ExprInfo> {
+        if !self.binders.is_empty() {
+            return fold_subexprs(self, ei);
+        }
+        match &*ei.expr {
+            // Fold the condition first, so that only the branch taken is inlined.
+            Expr::If(cond, then_expr, else_expr) => {
+                let cond = self.fold_expr(cond.clone());
+                match eval_const_bool(&cond) {
+                    Some(true) => self.fold_expr(then_expr.clone()),
+                    Some(false) => self.fold_expr(else_expr.clone()),
+                    None => {
+                        let then_expr = self.fold_expr(then_expr.clone());
+                        let else_expr = self.fold_expr(else_expr.clone());
+                        self.fold_if(ei, cond, then_expr, else_expr)
+                    }
+                }
+            }
+            // Inline a recursive call to a constant, or make its argument a literal if the budget is used up.
+            Expr::App(func, arg) => {
+                let func = self.fold_expr(func.clone());
+                let arg = self.fold_expr(arg.clone());
+                let is_rec_call =
+                    matches!(&*func.expr, Expr::Var(var) if var.name() == self.def.f.name());
+                match eval_const(&arg, &HashMap::default()) {
+                    Some(val) if is_rec_call => match self.inline(val) {
+                        Some(body) => body,
+                        None => self.fold_app(ei, func, int(val).with_source_of(&arg)),
+                    },
+                    _ => self.fold_app(ei, func, arg),
+                }
+            }
+            _ => fold_subexprs(self, ei),
+        }
+    }
+}
+
+// Unroll recursive functions by `fix` applied to int literals, inlining up to the given number of bodies at each application.
+pub fn unroll_fix(
+    program: Arc<ExprInfo>,
+    max_depth: usize,
+    remarks: &mut Vec<OptRemark>,
+) -> Arc<ExprInfo> {
+    let mut unroller = FixUnroller {
+        max_depth,
+        scope: vec![],
+        remarks: vec![],
+    };
+    let program = unroller.fold_expr(program);
+    remarks.append(&mut unroller.remarks);
+    program
+}
+
+// Replace `iterate n (add k) x` in a program by the fused version.
+// This is always run before code generation (not only with `ast_optimization`), so that such a loop never allocates per step.
+pub fn fuse_iterate(program: Arc<ExprInfo>, remarks: &mut Vec<OptRemark>) -> Arc<ExprInfo> {
+    let mut iterate_fuser = IterateFuser::default();
+    let program = iterate_fuser.fold_expr(program);
+    remarks.append(&mut iterate_fuser.remarks);
+    program
+}
+
+// Run optimization passes on a program (before library functions are added).
+// Each pass appends remarks on what it did to the given vector.
+pub fn optimize_ast(program: Arc<ExprInfo>, remarks: &mut Vec<OptRemark>) -> Arc<ExprInfo> {
+    let mut dead_let = DeadLetEliminator::default();
+    let program = dead_let.fold_expr(program);
+    remarks.append(&mut dead_let.remarks);
+    program
+}
+
+// Evaluate a closed expression of ints at compile time.
+// Int literals, constants defined before and full applications of arithmetic library functions to them are supported.
+// Returns None if the expression is not such a constant or its evaluation fails (e.g., division by zero).
+pub fn eval_const(ei: &Arc<ExprInfo>, consts: &HashMap<String, i64>) -> Option<i64> {
+    match &*ei.expr {
+        Expr::Lit(lit) if lit.ty == Some(int_lit_ty()) => ei.expr.to_string().parse::<i64>().ok(),
+        Expr::Var(var) => consts.get(var.name()).copied(),
+        Expr::App(_, _) => {
+            let (head, args) = unfold_app(ei);
+            let name = match &*head.expr {
+                Expr::Var(var) if !consts.contains_key(var.name()) => var.name().clone(),
+                _ => return None,
+            };
+            let args = args
+                .iter()
+                .map(|arg| eval_const(arg, consts))
+                .collect::<Option<Vec<i64>>>()?;
+            match (name.as_str(), args.as_slice()) {
+                ("add", [lhs, rhs]) => Some(lhs.wrapping_add(*rhs)),
+                ("sub", [lhs, rhs]) => Some(lhs.wrapping_sub(*rhs)),
+                ("mul", [lhs, rhs]) => Some(lhs.wrapping_mul(*rhs)),
+                ("neg", [val]) => Some(val.wrapping_neg()),
+                // Division overflowing (i.e., the minimum divided by -1) aborts at runtime, so it is not a constant.
+                ("div", [lhs, rhs]) => lhs.checked_div(*rhs),
+                ("mod", [lhs, rhs]) => lhs.checked_rem(*rhs),
+                ("pow", [base, exp]) => Some(wrapping_pow(*base, *exp)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// Evaluate a closed condition at compile time: bool literals, comparisons of constant ints and `not` of them.
+// Returns None if the expression is not such a constant.
+pub fn eval_const_bool(ei: &Arc<ExprInfo>) -> Option<bool> {
+    match &*ei.expr {
+        Expr::Lit(lit) if lit.ty == Some(bool_lit_ty()) => ei.expr.to_string().parse::<bool>().ok(),
+        Expr::App(_, _) => {
+            let (head, args) = unfold_app(ei);
+            let name = match &*head.expr {
+                Expr::Var(var) => var.name().clone(),
+                _ => return None,
+            };
+            if let ("not", [val]) = (name.as_str(), args.as_slice()) {
+                return eval_const_bool(val).map(|val| !val);
+            }
+            let consts = HashMap::default();
+            let (lhs, rhs) = match args.as_slice() {
+                [lhs, rhs] => (eval_const(lhs, &consts)?, eval_const(rhs, &consts)?),
+                _ => return None,
+            };
+            match name.as_str() {
+                "eq" => Some(lhs == rhs),
+                "lt" => Some(lhs < rhs),
+                "le" => Some(lhs <= rhs),
+                "gt" => Some(lhs > rhs),
+                "ge" => Some(lhs >= rhs),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// Power by square-and-multiply, wrapping on overflow as `pow` does at runtime. A negative exponent gives 1.
+fn wrapping_pow(mut base: i64, mut exp: i64) -> i64 {
+    let mut acc: i64 = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = acc.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exp >>= 1;
+    }
+    acc
+}
+
+// Pass which replaces variables referring to constants by int literals.
+struct ConstInliner {
+    consts: HashMap<String, i64>,
+    // Names bound by enclosing lets / lambdas, which shadow constants.
+    bound_names: Vec<String>,
+}
+
+impl Folder for ConstInliner {
+    fn enter_binder(&mut self, var: &Arc<Var>) {
+        self.bound_names.push(var.name().clone());
+    }
+    fn exit_binder(&mut self, _var: &Arc<Var>) {
+        self.bound_names.pop();
+    }
+    fn fold_var(&mut self, ei: Arc<ExprInfo>, var: &Arc<Var>) -> Arc<ExprInfo> {
+        let name = var.name();
+        match self.consts.get(name) {
+            Some(val) if !self.bound_names.contains(name) => int(*val).with_source_of(&ei),
+            _ => ei,
+        }
+    }
+}
+
+// Replace uses of constants in a program by int literals of their values.
+// Rebuilt nodes keep their source spans, and an inlined literal has the span of the variable it replaces.
+pub fn inline_consts(program: Arc<ExprInfo>, consts: HashMap<String, i64>) -> Arc<ExprInfo> {
+    if consts.is_empty() {
+        return program;
+    }
+    let mut inliner = ConstInliner {
+        consts,
+        bound_names: vec![],
+    };
+    inliner.fold_expr(program)
+}
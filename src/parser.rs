@@ -4,19 +4,162 @@ struct FixParser;
 use super::*;
 
 pub fn parse_source(source: &str) -> Arc<ExprInfo> {
-    let file = FixParser::parse(Rule::file, source).unwrap();
+    match try_parse_source(source) {
+        Ok(ast) => ast,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+// Error in parsing, with the span of the source where it is found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Parse source, returning the diagnostics as an error if the source is malformed.
+pub fn try_parse_source(source: &str) -> Result<Arc<ExprInfo>, ParseError> {
+    let file = FixParser::parse(Rule::file, source).map_err(|e| {
+        if let Some(start) = find_unterminated_block_comment(source) {
+            return ParseError {
+                message: "unterminated block comment".to_string(),
+                span: Span {
+                    start,
+                    end: start + 2,
+                },
+            };
+        }
+        let (start, end) = match e.location {
+            InputLocation::Pos(pos) => (pos, pos),
+            InputLocation::Span(span) => span,
+        };
+        ParseError {
+            message: e.to_string(),
+            span: Span { start, end },
+        }
+    })?;
     parse_file(file)
 }
 
-fn parse_file(mut file: Pairs<Rule>) -> Arc<ExprInfo> {
-    let pair = file.next().unwrap();
+// Find the position of the outermost "/*" which is not closed, skipping line comments and string literals.
+fn find_unterminated_block_comment(source: &str) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let mut openings: Vec<usize> = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest.starts_with(b"/*") {
+            openings.push(i);
+            i += 2;
+        } else if !openings.is_empty() && rest.starts_with(b"*/") {
+            openings.pop();
+            i += 2;
+        } else if openings.is_empty() && rest.starts_with(b"//") {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if openings.is_empty() && bytes[i] == b'"' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i += 1;
+        } else {
+            i += 1;
+        }
+    }
+    openings.first().copied()
+}
+
+// Parse a line of the form "let name = expr" (without "in" and the body) into the name and the bound value.
+// Returns None if the line is not of this form.
+pub fn parse_repl_binding(source: &str) -> Option<(Arc<Var>, Arc<ExprInfo>)> {
+    let mut pairs = FixParser::parse(Rule::repl_binding, source).ok()?;
+    let var = pairs.next().unwrap();
+    let bound = pairs.next().unwrap();
+    Some((parse_var_var(var), parse_expr(bound)))
+}
+
+// Span of the source matched by a pair.
+fn span_of(pair: &Pair<Rule>) -> Span {
+    let span = pair.as_span();
+    Span {
+        start: span.start(),
+        end: span.end(),
+    }
+}
+
+fn parse_file(file: Pairs<Rule>) -> Result<Arc<ExprInfo>, ParseError> {
+    let mut consts: HashMap<String, i64> = HashMap::new();
+    for pair in file {
+        match pair.as_rule() {
+            Rule::const_decl => {
+                let span = span_of(&pair);
+                let mut pairs = pair.into_inner();
+                let name = pairs.next().unwrap().as_str().to_string();
+                let bound = parse_expr(pairs.next().unwrap());
+                match eval_const(&bound, &consts) {
+                    Some(val) => {
+                        consts.insert(name, val);
+                    }
+                    None => {
+                        return Err(ParseError {
+                            message: format!(
+                                "`{}` is not a compile-time constant",
+                                bound.expr.to_string()
+                            ),
+                            span,
+                        })
+                    }
+                }
+            }
+            Rule::expr => return Ok(inline_consts(parse_expr(pair), consts)),
+            _ => unreachable!(),
+        }
+    }
+    unreachable!()
+}
+
+fn parse_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+    let span = span_of(&expr);
+    let pair = expr.into_inner().next().unwrap();
+    let ei = match pair.as_rule() {
+        Rule::pipe_expr => parse_pipe_expr(pair),
+        Rule::cmp_expr => parse_cmp_expr(pair),
+        Rule::app_expr => parse_app_expr(pair),
+        Rule::not_app_expr => parse_not_app_expr(pair),
+        _ => unreachable!(),
+    };
+    ei.with_source(span)
+}
+
+fn parse_pipe_operand_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+    let pair = expr.into_inner().next().unwrap();
     match pair.as_rule() {
-        Rule::expr => return parse_expr(pair),
+        Rule::cmp_expr => parse_cmp_expr(pair),
+        Rule::app_expr => parse_app_expr(pair),
+        Rule::not_app_expr => parse_not_app_expr(pair),
         _ => unreachable!(),
     }
 }
 
-fn parse_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+// Parse pipeline, desugaring "x |> f |> g" into "g (f x)".
+fn parse_pipe_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+    let mut pairs = expr.into_inner();
+    let mut ret = parse_pipe_operand_expr(pairs.next().unwrap());
+    for pair in pairs {
+        ret = app(parse_pipe_operand_expr(pair), ret);
+    }
+    ret
+}
+
+fn parse_cmp_operand_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
     let pair = expr.into_inner().next().unwrap();
     match pair.as_rule() {
         Rule::app_expr => parse_app_expr(pair),
@@ -25,33 +168,81 @@ fn parse_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
     }
 }
 
+fn parse_cmp_op(op: Pair<Rule>) -> Arc<ExprInfo> {
+    match op.as_str() {
+        "<" => var("lt"),
+        _ => unreachable!(),
+    }
+}
+
+// Parse comparison, desugaring a chain "a < b < c" into "let t = b in if lt a t then lt t c else false".
+// Middle operands are bound to variables so that they are evaluated only once.
+fn parse_cmp_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+    let mut pairs = expr.into_inner();
+    let first = parse_cmp_operand_expr(pairs.next().unwrap());
+    let mut rest: Vec<(Arc<ExprInfo>, Arc<ExprInfo>)> = vec![];
+    while let Some(op) = pairs.next() {
+        let operand = parse_cmp_operand_expr(pairs.next().unwrap());
+        rest.push((parse_cmp_op(op), operand));
+    }
+    desugar_cmp_chain(first, &rest, 0)
+}
+
+fn desugar_cmp_chain(
+    lhs: Arc<ExprInfo>,
+    rest: &[(Arc<ExprInfo>, Arc<ExprInfo>)],
+    depth: usize,
+) -> Arc<ExprInfo> {
+    let (op, rhs) = &rest[0];
+    if rest.len() == 1 {
+        return app(app(op.clone(), lhs), rhs.clone());
+    }
+    // Names of temporary variables cannot be written in source, so they never shadow user's variables.
+    let tmp_name = format!("%cmp{}%", depth);
+    let cmp = app(app(op.clone(), lhs), var(&tmp_name));
+    let following = desugar_cmp_chain(var(&tmp_name), &rest[1..], depth + 1);
+    let_in(
+        var_var(&tmp_name),
+        rhs.clone(),
+        conditional(cmp, following, bool(false)),
+    )
+}
+
+// Each partial application spans from the function to its last argument.
 fn parse_app_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+    let start = span_of(&expr).start;
     let mut subexprs = expr.into_inner();
     let mut ret = parse_not_app_expr(subexprs.next().unwrap());
     for pair in subexprs {
-        ret = app(ret, parse_not_app_expr(pair));
+        let end = span_of(&pair).end;
+        ret = app(ret, parse_not_app_expr(pair)).with_source(Span { start, end });
     }
     ret
 }
 
 fn parse_not_app_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+    let span = span_of(&expr);
     let pair = expr.into_inner().next().unwrap();
-    match pair.as_rule() {
+    let ei = match pair.as_rule() {
         Rule::lit_expr => parse_lit_expr(pair),
         Rule::var_expr => parse_var_expr(pair),
         Rule::let_expr => parse_let_expr(pair),
         Rule::lam_expr => parse_lam_expr(pair),
         Rule::if_expr => parse_if_expr(pair),
         Rule::bracket_expr => parse_bracket_expr(pair),
+        Rule::array_lit_expr => parse_array_lit_expr(pair),
         _ => unreachable!(),
-    }
+    };
+    ei.with_source(span)
 }
 
 fn parse_lit_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
     let pair = expr.into_inner().next().unwrap();
     match pair.as_rule() {
+        Rule::float_lit_expr => parse_float_expr(pair),
         Rule::int_lit_expr => parse_int_expr(pair),
         Rule::bool_lit_expr => parse_bool_lit_expr(pair),
+        Rule::str_lit_expr => parse_str_lit_expr(pair),
         _ => unreachable!(),
     }
 }
@@ -72,11 +263,14 @@ fn parse_let_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
     let_in(parse_var_var(var), parse_expr(bound), parse_expr(val))
 }
 
+// Parse lambda, desugaring "\x y -> e" into "\x -> \y -> e".
 fn parse_lam_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
-    let mut pairs = expr.into_inner();
-    let var = pairs.next().unwrap();
-    let val = pairs.next().unwrap();
-    lam(parse_var_var(var), parse_expr(val))
+    let mut pairs: Vec<Pair<Rule>> = expr.into_inner().collect();
+    let val = pairs.pop().unwrap();
+    pairs
+        .into_iter()
+        .rev()
+        .fold(parse_expr(val), |val, var| lam(parse_var_var(var), val))
 }
 
 fn parse_if_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
@@ -92,12 +286,62 @@ fn parse_bracket_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
     parse_expr(inner)
 }
 
+// Parse array literal, desugaring "[e0, e1, e2]" into "writeArray! (writeArray! (newArray 3 e0) 1 e1) 2 e2".
+// Elements are evaluated from left to right. The empty literal "[]" is desugared into "newArrayLazy 0 (\u -> error 0)",
+// whose thunk is never forced.
+fn parse_array_lit_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+    let mut elems = expr.into_inner().map(parse_expr);
+    let first = match elems.next() {
+        Some(first) => first,
+        None => {
+            let thunk = lam(var_var("u"), app(var("error"), int(0)));
+            return app(app(var("newArrayLazy"), int(0)), thunk);
+        }
+    };
+    let rest: Vec<Arc<ExprInfo>> = elems.collect();
+    let size = int(rest.len() as i64 + 1);
+    let mut ret = app(app(var("newArray"), size), first);
+    for (i, elem) in rest.into_iter().enumerate() {
+        ret = app(app(app(var("writeArray!"), ret), int(i as i64 + 1)), elem);
+    }
+    ret
+}
+
 fn parse_int_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
     let val = expr.as_str().parse::<i64>().unwrap();
     int(val)
 }
 
+fn parse_float_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+    let val = expr.as_str().parse::<f64>().unwrap();
+    float(val)
+}
+
 fn parse_bool_lit_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
     let val = expr.as_str().parse::<bool>().unwrap();
     bool(val)
 }
+
+fn parse_str_lit_expr(expr: Pair<Rule>) -> Arc<ExprInfo> {
+    let inner = expr.into_inner().next().unwrap();
+    string(&unescape_str(inner.as_str()))
+}
+
+// Replace escape sequences accepted by str_lit_inner with the characters they represent.
+fn unescape_str(s: &str) -> String {
+    let mut ret = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            ret.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => ret.push('\n'),
+            Some('t') => ret.push('\t'),
+            Some(c) => ret.push(c),
+            None => unreachable!(),
+        }
+    }
+    ret
+}
@@ -0,0 +1,73 @@
+use super::*;
+
+// Interactive loop which evaluates expressions line by line.
+// A line "let name = expr" is remembered and the binding is visible from subsequent lines.
+pub struct Repl {
+    // Bindings entered so far, in order.
+    bindings: Vec<(Arc<Var>, Arc<ExprInfo>)>,
+    opt_level: OptimizationLevel,
+    options: CompileOptions,
+}
+
+// Result of a line entered to REPL.
+#[derive(Debug, PartialEq)]
+pub enum ReplOutput {
+    // The line is a binding, which is remembered.
+    Bound(String),
+    // The line is an expression, evaluated to a value.
+    Value(i64),
+}
+
+impl Repl {
+    pub fn new(opt_level: OptimizationLevel, options: &CompileOptions) -> Self {
+        Repl {
+            bindings: vec![],
+            opt_level,
+            options: options.clone(),
+        }
+    }
+
+    // Process a line: remember it if it is a binding, and otherwise evaluate it under the bindings so far.
+    pub fn eval_line(&mut self, line: &str) -> Result<ReplOutput, CompileError> {
+        if let Some((var, bound)) = parse_repl_binding(line) {
+            let name = var.name().clone();
+            self.bindings.push((var, bound));
+            return Ok(ReplOutput::Bound(name));
+        }
+        let expr = try_parse_source(line).map_err(|e| CompileError::Parse(e.to_string()))?;
+        let program = self.wrap_with_bindings(expr);
+        run_ast(program, self.opt_level, &self.options).map(ReplOutput::Value)
+    }
+
+    // Put an expression inside the bindings it refers to (transitively).
+    // Unused bindings are not added, so that they are neither compiled nor reported as unused.
+    fn wrap_with_bindings(&self, expr: Arc<ExprInfo>) -> Arc<ExprInfo> {
+        let mut needed = calculate_free_vars(expr.clone()).free_vars.clone();
+        let mut program = expr;
+        for (var, bound) in self.bindings.iter().rev() {
+            if !needed.remove(var.name()) {
+                continue;
+            }
+            // A binding may refer to a previous binding of the same name, since let is not recursive.
+            needed.extend(calculate_free_vars(bound.clone()).free_vars.iter().cloned());
+            program = let_in(var.clone(), bound.clone(), program);
+        }
+        program
+    }
+
+    // Read lines from input until EOF, and write results (or errors) of them to output.
+    pub fn run(&mut self, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match self.eval_line(&line) {
+                Ok(ReplOutput::Bound(name)) => writeln!(output, "{} is defined.", name)?,
+                Ok(ReplOutput::Value(val)) => writeln!(output, "{}", val)?,
+                Err(err) => writeln!(output, "{}", err)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,59 +1,410 @@
 use super::*;
 
+// Options for compilation and execution of a program.
+#[derive(Clone, Default)]
+pub struct CompileOptions {
+    // Report time spent in each phase (and the optimization level used) to stderr.
+    pub time_passes: bool,
+    // Do not mark calls of lambdas as tail calls, so that all frames are kept in debuggers.
+    // Deep recursion may overflow the stack with this option.
+    pub disable_tail_calls: bool,
+    // Library functions defined by the embedder, as pairs of name and definition (built by `lit`, `lam`, etc.).
+    // A definition may refer to built-in library functions and to preceding ones in this list.
+    pub extra_builtins: Vec<(String, Arc<ExprInfo>)>,
+    // Addresses of functions of the host by their symbols, which are installed in the execution engine
+    // for functions declared in the module (e.g., by `host_int_fn`).
+    pub host_symbols: HashMap<String, usize>,
+    // Path to the sanitizer library. If None, the value of `RUNTIME_LIBRARY_PATH_ENV` or `DEFAULT_RUNTIME_LIBRARY_PATH` is used.
+    pub runtime_library_path: Option<String>,
+    // Run optimization passes on AST (e.g., elimination of dead lets).
+    pub ast_optimization: bool,
+    // Report remarks of optimization passes to stderr.
+    pub opt_remarks: bool,
+    // Print signatures of runtime functions to stderr.
+    pub dump_runtime_decls: bool,
+    // Unroll recursive functions by `fix` applied to int literals, inlining up to this number of bodies at each application
+    // (0 disables unrolling).
+    pub fix_unroll_depth: usize,
+    // Path to which LLVM IR of the program is written. If None, IR is not written (except on verification failure).
+    pub ir_dump_path: Option<PathBuf>,
+    // Write LLVM IR normalized by `normalize_ir`, which is stable for comparison with golden files.
+    pub normalize_ir_dump: bool,
+    // Do not check leak of objects at the end of the program. Leaked objects remain in the object table of the sanitizer.
+    pub no_leak_check: bool,
+    // Treat lints of the program (e.g., unused let bindings) as errors instead of warnings.
+    pub strict: bool,
+    // The program produces an array of ints, and the entry function is `i64 main(i64* out, i64 out_len)`,
+    // which copies the elements (up to out_len) into out and returns the number of elements of the array.
+    pub out_array: bool,
+    // If Some(n), a closure capturing more than n variables holds the captured objects in one array object
+    // instead of one field per captured variable, which bounds the size of types and dtors of closures.
+    pub max_closure_captures: Option<usize>,
+}
+
+// Error in compilation or execution of a program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    // The source is malformed.
+    Parse(String),
+    // LLVM module failed verification.
+    Verify(String),
+    // Failed to prepare JIT execution (e.g., loading the runtime library).
+    Jit(String),
+    // Failed to read a source file.
+    Io(String),
+    // The program has lints in strict mode.
+    Lint(String),
+    // The program is ill-typed.
+    Type(String),
+    // The program is well-typed, but the entry function can't run a program of its type
+    // (e.g., the result of `i64 main()` is a function).
+    NonRunnable { found_type: Arc<Type> },
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Parse(msg) => write!(f, "Parse error: {}", msg),
+            CompileError::Verify(msg) => write!(f, "Verification error: {}", msg),
+            CompileError::Jit(msg) => write!(f, "JIT error: {}", msg),
+            CompileError::Io(msg) => write!(f, "IO error: {}", msg),
+            CompileError::Lint(msg) => write!(f, "Lint error: {}", msg),
+            CompileError::Type(msg) => write!(f, "Type error: {}", msg),
+            CompileError::NonRunnable { found_type } => write!(
+                f,
+                "Type error: the program can't be run since it has type {}",
+                found_type.to_string()
+            ),
+        }
+    }
+}
+
+// Environment variable to specify the path to the sanitizer library.
+pub const RUNTIME_LIBRARY_PATH_ENV: &str = "FIX_RUNTIME_LIBRARY_PATH";
+
+// Default path to the sanitizer library.
+pub const DEFAULT_RUNTIME_LIBRARY_PATH: &str = "sanitizer/libfixsanitizer.so";
+
+impl CompileOptions {
+    // Get the path to the sanitizer library.
+    pub fn runtime_library_path(&self) -> String {
+        match &self.runtime_library_path {
+            Some(path) => path.clone(),
+            None => std::env::var(RUNTIME_LIBRARY_PATH_ENV)
+                .unwrap_or_else(|_| String::from(DEFAULT_RUNTIME_LIBRARY_PATH)),
+        }
+    }
+}
+
+// Time spent in each phase of compilation and execution.
+#[derive(Default)]
+struct PhaseTimes {
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl PhaseTimes {
+    // Run a phase and record the time spent in it.
+    fn measure<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let ret = f();
+        self.phases.push((phase, start.elapsed()));
+        ret
+    }
+
+    fn report(&self, opt_level: OptimizationLevel) {
+        eprintln!("===== Time passes (opt level: {:?}) =====", opt_level);
+        let mut total = Duration::ZERO;
+        for (phase, time) in &self.phases {
+            eprintln!("{:>12}: {:>10.3} ms", phase, time.as_secs_f64() * 1000.0);
+            total += *time;
+        }
+        eprintln!("{:>12}: {:>10.3} ms", "total", total.as_secs_f64() * 1000.0);
+    }
+}
+
 fn execute_main_module<'c>(
     context: &'c Context,
     module: &Module<'c>,
     opt_level: OptimizationLevel,
-) -> i64 {
-    if SANITIZE_MEMORY {
-        assert_eq!(
-            load_library_permanently("sanitizer/libfixsanitizer.so"),
-            false
-        );
+    options: &CompileOptions,
+    times: &mut PhaseTimes,
+    out: &mut [i64],
+) -> Result<i64, CompileError> {
+    load_runtime_library(&options.runtime_library_path()).map_err(CompileError::Jit)?;
+    let execution_engine = times.measure("jit", || {
+        module
+            .create_jit_execution_engine(opt_level)
+            .map_err(|e| CompileError::Jit(e.to_string()))
+    })?;
+    for (symbol, addr) in &options.host_symbols {
+        // A symbol is not declared if the program doesn't use it.
+        if let Some(func) = module.get_function(symbol) {
+            execution_engine.add_global_mapping(&func, *addr);
+        }
     }
-    let execution_engine = module.create_jit_execution_engine(opt_level).unwrap();
-    unsafe {
-        let func = execution_engine
-            .get_function::<unsafe extern "C" fn() -> i64>("main")
-            .unwrap();
-        func.call()
+    if options.out_array {
+        let func = unsafe {
+            execution_engine
+                .get_function::<unsafe extern "C" fn(*mut i64, i64) -> i64>("main")
+                .map_err(|e| CompileError::Jit(e.to_string()))?
+        };
+        Ok(times.measure("execute", || unsafe {
+            func.call(out.as_mut_ptr(), out.len() as i64)
+        }))
+    } else {
+        let func = unsafe {
+            execution_engine
+                .get_function::<unsafe extern "C" fn() -> i64>("main")
+                .map_err(|e| CompileError::Jit(e.to_string()))?
+        };
+        Ok(times.measure("execute", || unsafe { func.call() }))
+    }
+}
+
+// All library functions as pairs of name and definition.
+fn builtins() -> Vec<(&'static str, Arc<ExprInfo>)> {
+    vec![
+        ("add", add()),
+        ("sub", sub()),
+        ("neg", neg()),
+        ("mul", mul()),
+        ("div", div()),
+        ("mod", mod_()),
+        ("pow", pow()),
+        ("eq", eq()),
+        ("lt", lt()),
+        ("le", le()),
+        ("gt", gt()),
+        ("ge", ge()),
+        ("not", not()),
+        ("and", and()),
+        ("or", or()),
+        ("fix", fix()),
+        ("newArray", new_array()),
+        ("readArray", read_array()),
+        ("writeArray", write_array()),
+        ("writeArray!", write_array_unique()),
+        ("modifyArray", modify_array()),
+        ("eqArray", eq_array()),
+        ("loop", loop_()),
+        ("takeArray", take_array()),
+        ("dropArray", drop_array()),
+        ("splitAtArray", split_at_array()),
+        ("fst", fst()),
+        ("snd", snd()),
+        ("pair", pair()),
+        ("firstP", first_p()),
+        ("secondP", second_p()),
+        ("lenArray", len_array()),
+        ("none", none()),
+        ("some", some()),
+        ("isSome", is_some()),
+        ("unwrap", unwrap()),
+        ("fromOption", from_option()),
+        ("mapOption", map_option()),
+        ("makeWeak", make_weak()),
+        ("derefWeak", deref_weak()),
+        ("findIndexArray", find_index_array()),
+        ("foldArray", fold_array()),
+        ("foldRightArray", fold_right_array()),
+        ("copyArray", copy_array()),
+        ("deepCopyArray", deep_copy_array()),
+        ("rotateArray", rotate_array()),
+        ("concatStr", concat_str()),
+        ("lengthStr", length_str()),
+        ("print", print()),
+        ("error", error()),
+        ("getEnvInt", get_env_int()),
+        ("sliceView", slice_view()),
+        ("readView", read_view()),
+        ("writeView", write_view()),
+        ("liveObjects", live_objects()),
+        ("trace", trace()),
+        ("newArrayLazy", new_array_lazy()),
+        ("parseInt", parse_int()),
+        ("readLineInt", read_line_int()),
+        ("isUnique", is_unique()),
+        ("seq", seq()),
+        ("clz", clz()),
+        ("popcount", popcount()),
+        ("iterate", iterate()),
+        (ITERATE_ADD_NAME, iterate_add()),
+        ("addF", add_float()),
+        ("mulF", mul_float()),
+        (FLOAT_BITS_NAME, float_bits()),
+    ]
+}
+
+// Add library functions used in program.
+// Unused library functions are not added to reduce the size of the module.
+fn add_builtins(
+    program: Arc<ExprInfo>,
+    extra_builtins: &[(String, Arc<ExprInfo>)],
+) -> Arc<ExprInfo> {
+    let builtins: Vec<(&str, Arc<ExprInfo>)> = builtins()
+        .into_iter()
+        .chain(
+            extra_builtins
+                .iter()
+                .map(|(name, expr)| (name.as_str(), expr.clone())),
+        )
+        .map(|(name, expr)| (name, calculate_free_vars(expr)))
+        .collect();
+
+    // Collect library functions referenced from program, transitively.
+    let mut used: HashSet<String> = HashSet::default();
+    let mut stack: Vec<String> = calculate_free_vars(program.clone())
+        .free_vars
+        .iter()
+        .cloned()
+        .collect();
+    while let Some(name) = stack.pop() {
+        if used.contains(&name) {
+            continue;
+        }
+        if let Some((_, expr)) = builtins.iter().rfind(|(n, _)| *n == name) {
+            stack.extend(expr.free_vars.iter().cloned());
+            used.insert(name);
+        }
+    }
+
+    // Later definitions are put inside earlier ones, so that they can refer to earlier ones.
+    let mut program = program;
+    for (name, expr) in builtins.into_iter().rev() {
+        if used.contains(name) {
+            program = let_in(var_var(name), expr, program);
+        }
+    }
+    program
+}
+
+// Names of all library functions, including ones defined by the embedder.
+fn builtin_names(options: &CompileOptions) -> Vec<String> {
+    builtins()
+        .into_iter()
+        .map(|(name, _)| String::from(name))
+        .chain(options.extra_builtins.iter().map(|(name, _)| name.clone()))
+        .collect()
+}
+
+// Environment of types of all library functions, including ones defined by the embedder.
+// Types of library functions whose types are not known are inferred from their definitions.
+fn builtin_type_env(options: &CompileOptions) -> Result<TypeEnv, TypeError> {
+    let mut env = TypeEnv::default();
+    for (name, expr) in builtins() {
+        let ty = match builtin_type(name) {
+            Some(ty) => generalize(&env, &ty),
+            None => generalize(&env, &infer(&expr, &env)?),
+        };
+        env.insert(name, ty);
     }
+    for (name, expr) in &options.extra_builtins {
+        let ty = generalize(&env, &infer(expr, &env)?);
+        env.insert(name, ty);
+    }
+    Ok(env)
+}
+
+// Kind of an entry function, which determines the type of programs it can run.
+#[derive(Clone, Copy, PartialEq)]
+enum EntryKind {
+    // `i64 main()`, which returns the value of a scalar (or `i64 main(i64*, i64)` for an array of ints if `out_array` is set).
+    Main,
+    // `i64 f(i64)`, which applies a program of type Int -> Int to the argument.
+    IntFn,
 }
 
-fn run_ast(program: Arc<ExprInfo>, opt_level: OptimizationLevel) -> i64 {
+// Perform diagnostics and passes on a program, and make it ready for code generation.
+// Every entry function is built through this function, so no ill-typed program reaches code generation.
+fn prepare_program(
+    program: Arc<ExprInfo>,
+    options: &CompileOptions,
+    entry: EntryKind,
+) -> Result<Arc<ExprInfo>, CompileError> {
+    // Perform diagnostics, which give more specific messages than type inference.
+    check_if_branch_types(&program).map_err(CompileError::Type)?;
+    check_case_patterns(&program).map_err(CompileError::Type)?;
+
+    // Report lints and check the type of the program.
+    check_program(&program, options, entry)?;
+
+    // Run optimization passes.
+    let mut remarks = vec![];
+    let program = fuse_iterate(program, &mut remarks);
+    let program = if options.ast_optimization {
+        optimize_ast(program, &mut remarks)
+    } else {
+        program
+    };
+    let program = if options.fix_unroll_depth > 0 {
+        unroll_fix(program, options.fix_unroll_depth, &mut remarks)
+    } else {
+        program
+    };
+    if options.opt_remarks {
+        for remark in &remarks {
+            eprintln!("{}", remark);
+        }
+    }
+
     // Add library functions to program.
-    let program = let_in(var_var("add"), add(), program);
-    let program = let_in(var_var("eq"), eq(), program);
-    let program = let_in(var_var("fix"), fix(), program);
-    let program = let_in(var_var("newArray"), new_array(), program);
-    let program = let_in(var_var("readArray"), read_array(), program);
-    let program = let_in(var_var("writeArray"), write_array(), program);
-    let program = let_in(var_var("writeArray!"), write_array_unique(), program);
+    let program = add_builtins(program, &options.extra_builtins);
 
     // Calculate free variables of nodes.
-    let program = calculate_free_vars(program);
-
-    // Create GenerationContext.
-    let context = Context::create();
-    let module = context.create_module("main");
-    let mut gc = GenerationContext::new(&context, &module);
+    Ok(calculate_free_vars(program))
+}
 
-    // Build runtime functions.
-    build_runtime(&mut gc);
+// Build a function of a given name, which evaluates the program and returns the int value of the result.
+fn build_entry_function<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    program: Arc<ExprInfo>,
+    name: &str,
+    options: &CompileOptions,
+) -> Result<FunctionValue<'c>, CompileError> {
+    let context = gc.context;
+    let program = prepare_program(program, options, EntryKind::Main)?;
 
-    // Add main function.
-    let main_fn_type = context.i64_type().fn_type(&[], false);
-    let main_function = module.add_function("main", main_fn_type, None);
-    let entry_bb = context.append_basic_block(main_function, "entry");
+    // Add entry function.
+    let i64_type = context.i64_type();
+    let entry_fn_type = if options.out_array {
+        i64_type.fn_type(
+            &[
+                i64_type.ptr_type(AddressSpace::Generic).into(),
+                i64_type.into(),
+            ],
+            false,
+        )
+    } else {
+        i64_type.fn_type(&[], false)
+    };
+    let entry_function = gc.module.add_function(name, entry_fn_type, None);
+    let entry_bb = context.append_basic_block(entry_function, "entry");
     gc.builder().position_at_end(entry_bb);
 
     // Evaluate program and extract int value from result.
+    // The type of the result is checked by `check_program`.
     let program_result = gc.eval_expr(program);
-    let result = gc.load_obj_field(program_result, int_type(&context), 1);
+    let result = if options.out_array {
+        // Copy elements of the resulting array into out, and return the size of the array.
+        let out = entry_function
+            .get_nth_param(0)
+            .unwrap()
+            .into_pointer_value();
+        let out_len = entry_function.get_nth_param(1).unwrap().into_int_value();
+        let array_ptr_ty = ptr_type(ObjectType::array_type().to_struct_type(context));
+        let array = gc.cast_pointer(program_result, array_ptr_ty);
+        let array_field = gc
+            .builder()
+            .build_struct_gep(array, 1, "array_field")
+            .unwrap();
+        ObjectFieldType::copy_int_array_to_buffer(gc, array_field, out, out_len).into()
+    } else {
+        gc.load_obj_field(program_result, int_type(context), 1)
+    };
     gc.release(program_result);
 
     // Perform leak check
-    if SANITIZE_MEMORY {
+    if SANITIZE_MEMORY && !options.no_leak_check {
         gc.call_runtime(RuntimeFunctions::CheckLeak, &[]);
     }
 
@@ -63,31 +414,620 @@ fn run_ast(program: Arc<ExprInfo>, opt_level: OptimizationLevel) -> i64 {
     } else {
         panic!("Given program doesn't return int value!");
     }
+    Ok(entry_function)
+}
+
+// Build LLVM module of a program.
+pub fn build_module<'c>(
+    context: &'c Context,
+    program: Arc<ExprInfo>,
+    options: &CompileOptions,
+) -> Result<Module<'c>, CompileError> {
+    // Create GenerationContext.
+    let module = context.create_module("main");
+    let mut gc = GenerationContext::new(context, &module);
+    gc.disable_tail_calls = options.disable_tail_calls;
+    gc.max_closure_captures = options.max_closure_captures;
 
-    // Print LLVM bitcode to file
-    module.print_to_file("main.ll").unwrap();
+    // Build runtime functions.
+    build_runtime(&mut gc);
+    if options.dump_runtime_decls {
+        for (name, ty) in runtime_function_signatures(&gc) {
+            eprintln!("{}: {}", name, ty);
+        }
+    }
 
-    // Verify LLVM module.
+    // Add main function.
+    build_entry_function(&mut gc, program, "main", options)?;
+
+    drop(gc);
+    Ok(module)
+}
+
+// Path to which LLVM IR is written when verification failed.
+pub const IR_PATH_ON_VERIFY_FAILURE: &str = "main.ll";
+
+// Normalize textual LLVM IR so that it doesn't depend on the environment or incidental ordering:
+// the module id, source file name, target and metadata are removed, the attributes in each attribute group are sorted,
+// and top-level entities (globals, declarations, definitions and attribute groups) are sorted in each kind.
+pub fn normalize_ir(ir: &str) -> String {
+    let mut globals: Vec<String> = vec![];
+    let mut declarations: Vec<String> = vec![];
+    let mut definitions: Vec<String> = vec![];
+    let mut attributes: Vec<String> = vec![];
+    let mut lines = ir.lines().map(|line| line.trim_end());
+    while let Some(line) = lines.next() {
+        if line.is_empty()
+            || line.starts_with(';')
+            || line.starts_with("source_filename")
+            || line.starts_with("target ")
+            || line.starts_with('!')
+        {
+            continue;
+        }
+        if line.starts_with("define ") {
+            // A definition continues until the closing brace at the head of a line.
+            let mut definition = String::from(line);
+            for line in lines.by_ref() {
+                definition.push('\n');
+                definition.push_str(line);
+                if line == "}" {
+                    break;
+                }
+            }
+            definitions.push(definition);
+        } else if line.starts_with("declare ") {
+            declarations.push(String::from(line));
+        } else if line.starts_with("attributes ") {
+            let (head, attrs) = line.split_once('{').unwrap_or((line, ""));
+            let mut attrs: Vec<&str> = attrs.trim_end_matches('}').split_whitespace().collect();
+            attrs.sort();
+            attributes.push(format!("{}{{ {} }}", head, attrs.join(" ")));
+        } else {
+            globals.push(String::from(line));
+        }
+    }
+    let mut groups = vec![globals, declarations, definitions, attributes];
+    for group in &mut groups {
+        group.sort();
+    }
+    let groups: Vec<String> = groups
+        .into_iter()
+        .filter(|group| !group.is_empty())
+        .map(|group| group.join("\n"))
+        .collect();
+    groups.join("\n\n") + "\n"
+}
+
+// Verify LLVM module, and panic if verification failed.
+// If verification failed, LLVM IR is written to a file for debugging.
+pub fn verify_module(module: &Module) {
+    if let Err(msg) = try_verify_module(module) {
+        panic!("{}", msg);
+    }
+}
+
+// Verify LLVM module, and return the error of LLVM with a message if verification failed.
+// If verification failed, LLVM IR is written to a file for debugging.
+pub fn try_verify_module(module: &Module) -> Result<(), String> {
     let verify = module.verify();
-    if verify.is_err() {
-        print!("{}", verify.unwrap_err().to_str().unwrap());
-        panic!("LLVM verify failed!");
+    if let Err(err) = verify {
+        module.print_to_file(IR_PATH_ON_VERIFY_FAILURE).unwrap();
+        return Err(format!(
+            "{}LLVM verify failed! LLVM IR is written to {}.",
+            err.to_str().unwrap(),
+            IR_PATH_ON_VERIFY_FAILURE
+        ));
+    }
+    Ok(())
+}
+
+// Run the standard optimization pipeline (function passes and module passes) of a given level on a module.
+// This is useful to optimize a module which is not executed by JIT (the JIT execution engine optimizes the module by itself).
+pub fn optimize_module(module: &Module, opt_level: OptimizationLevel) {
+    let pass_manager_builder = PassManagerBuilder::create();
+    pass_manager_builder.set_optimization_level(opt_level);
+
+    // Run function passes.
+    let fpm = PassManager::create(module);
+    pass_manager_builder.populate_function_pass_manager(&fpm);
+    fpm.initialize();
+    for func in module.get_functions() {
+        fpm.run_on(&func);
+    }
+    fpm.finalize();
+
+    // Run module passes.
+    let mpm = PassManager::create(());
+    pass_manager_builder.populate_module_pass_manager(&mpm);
+    mpm.run_on(module);
+}
+
+// Remove functions which are not referenced (directly or indirectly) from given entry functions.
+// This removes unused runtime functions and declarations of external functions, so that a module (e.g., an AOT output) doesn't carry them.
+pub fn prune_unused_functions(module: &Module, entry_names: &[&str]) {
+    loop {
+        let unused: Vec<FunctionValue> = module
+            .get_functions()
+            .filter(|func| {
+                let name = func.get_name().to_str().unwrap();
+                !entry_names.contains(&name)
+                    && func
+                        .as_global_value()
+                        .as_pointer_value()
+                        .get_first_use()
+                        .is_none()
+            })
+            .collect();
+        if unused.is_empty() {
+            break;
+        }
+        // Removing a function may make functions called from it unused, so repeat until no function is removed.
+        for func in unused {
+            unsafe { func.delete() };
+        }
+    }
+}
+
+// Load runtime library if necessary.
+pub fn load_runtime_library(path: &str) -> Result<(), String> {
+    if SANITIZE_MEMORY && load_library_permanently(path) {
+        return Err(format!(
+            "Failed to load runtime library \"{}\". Set {} to the path of libfixsanitizer.so.",
+            path, RUNTIME_LIBRARY_PATH_ENV
+        ));
+    }
+    Ok(())
+}
+
+// Report lints of a program (which are errors in strict mode) and check that the program is well-typed.
+// The type of the program should be the one required by the entry function: a scalar (Int or Bool),
+// an array of ints if `out_array` is set, or Int -> Int for a JIT function.
+fn check_program(
+    program: &Arc<ExprInfo>,
+    options: &CompileOptions,
+    entry: EntryKind,
+) -> Result<(), CompileError> {
+    // Report lints of the program, which are errors in strict mode.
+    let warnings = lint_program(program, &builtin_names(options));
+    if options.strict && !warnings.is_empty() {
+        return Err(CompileError::Lint(warnings.join("\n")));
+    }
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    // Infer the type of the program.
+    let ty = builtin_type_env(options)
+        .and_then(|env| infer(program, &env))
+        .map_err(|e| CompileError::Type(e.message))?;
+
+    // Check the type of the result, which is read by the entry function.
+    // A type variable is allowed since such a program doesn't return normally (e.g., `error 0`) or its type is not known.
+    let is_tyvar = matches!(&*ty, Type::TyVar(_));
+    let is_runnable = match entry {
+        EntryKind::Main if options.out_array => is_tyvar || ty == array_ty(int_lit_ty()),
+        EntryKind::Main => is_tyvar || ty == int_lit_ty() || ty == bool_lit_ty(),
+        // A polymorphic function (e.g., `\x -> x`) is also applicable to an int.
+        EntryKind::IntFn => unify(&ty, &lambda_ty(int_lit_ty(), int_lit_ty())).is_ok(),
+    };
+    if !is_runnable {
+        return Err(CompileError::NonRunnable { found_type: ty });
+    }
+    Ok(())
+}
+
+pub fn run_ast(
+    program: Arc<ExprInfo>,
+    opt_level: OptimizationLevel,
+    options: &CompileOptions,
+) -> Result<i64, CompileError> {
+    run_ast_with_out(program, opt_level, options, &mut [])
+}
+
+// Run a program which produces an array of ints, and copy its elements into out (up to the length of out).
+// Returns the number of elements of the array, which may exceed the length of out.
+pub fn run_ast_with_out_array(
+    program: Arc<ExprInfo>,
+    opt_level: OptimizationLevel,
+    options: &CompileOptions,
+    out: &mut [i64],
+) -> Result<i64, CompileError> {
+    let options = CompileOptions {
+        out_array: true,
+        ..options.clone()
+    };
+    run_ast_with_out(program, opt_level, &options, out)
+}
+
+fn run_ast_with_out(
+    program: Arc<ExprInfo>,
+    opt_level: OptimizationLevel,
+    options: &CompileOptions,
+    out: &mut [i64],
+) -> Result<i64, CompileError> {
+    let mut times = PhaseTimes::default();
+
+    // Build LLVM module.
+    let context = Context::create();
+    let module = times.measure("build", || build_module(&context, program, options))?;
+
+    // Write LLVM IR if requested.
+    if let Some(path) = &options.ir_dump_path {
+        if options.normalize_ir_dump {
+            let ir = normalize_ir(&module.print_to_string().to_string());
+            std::fs::write(path, ir).map_err(|e| CompileError::Io(e.to_string()))?;
+        } else {
+            module
+                .print_to_file(path)
+                .map_err(|e| CompileError::Io(e.to_string()))?;
+        }
     }
 
+    // Verify LLVM module.
+    times
+        .measure("verify", || try_verify_module(&module))
+        .map_err(CompileError::Verify)?;
+
     // Run the module.
-    execute_main_module(&context, &module, opt_level)
+    let ret = execute_main_module(&context, &module, opt_level, options, &mut times, out)?;
+
+    if options.time_passes {
+        times.report(opt_level);
+    }
+    Ok(ret)
+}
+
+pub fn run_source(source: &str, opt_level: OptimizationLevel) -> Result<i64, CompileError> {
+    run_source_with_options(source, opt_level, &CompileOptions::default())
+}
+
+pub fn run_source_with_options(
+    source: &str,
+    opt_level: OptimizationLevel,
+    options: &CompileOptions,
+) -> Result<i64, CompileError> {
+    let ast = try_parse_source(source).map_err(|e| CompileError::Parse(e.to_string()))?;
+    run_ast(ast, opt_level, options)
+}
+
+// Get the table of live objects in the sanitizer as JSON and clear the table.
+fn take_object_table_dump(options: &CompileOptions) -> Result<String, CompileError> {
+    if !SANITIZE_MEMORY {
+        return Ok(String::from("[]"));
+    }
+    unsafe {
+        let lib = libloading::Library::new(options.runtime_library_path())
+            .map_err(|e| CompileError::Jit(e.to_string()))?;
+        let dump: libloading::Symbol<unsafe extern "C" fn() -> *mut c_char> = lib
+            .get(b"dump_object_table")
+            .map_err(|e| CompileError::Jit(e.to_string()))?;
+        let free_dump: libloading::Symbol<unsafe extern "C" fn(*mut c_char)> = lib
+            .get(b"free_object_table_dump")
+            .map_err(|e| CompileError::Jit(e.to_string()))?;
+        let clear: libloading::Symbol<unsafe extern "C" fn()> = lib
+            .get(b"clear_object_table")
+            .map_err(|e| CompileError::Jit(e.to_string()))?;
+        let ptr = dump();
+        let json = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+        free_dump(ptr);
+        clear();
+        Ok(json)
+    }
+}
+
+// Run a program without leak check, and return the result with the table of objects left alive as JSON.
+// Each entry of the table has fields id, name, refcnt and kind. The table of the sanitizer is cleared after the dump.
+pub fn run_ast_with_object_dump(
+    program: Arc<ExprInfo>,
+    opt_level: OptimizationLevel,
+    options: &CompileOptions,
+) -> Result<(i64, String), CompileError> {
+    let options = CompileOptions {
+        no_leak_check: true,
+        ..options.clone()
+    };
+    let ret = run_ast(program, opt_level, &options)?;
+    let json = take_object_table_dump(&options)?;
+    Ok((ret, json))
+}
+
+pub fn run_source_with_object_dump(
+    source: &str,
+    opt_level: OptimizationLevel,
+) -> Result<(i64, String), CompileError> {
+    let ast = try_parse_source(source).map_err(|e| CompileError::Parse(e.to_string()))?;
+    run_ast_with_object_dump(ast, opt_level, &CompileOptions::default())
+}
+
+// Compile a program into a native object file for the host, which defines the entry function `i64 main()`.
+// The module is optimized and unused runtime functions and external declarations are removed.
+// If SANITIZE_MEMORY is true, the object refers to functions of the sanitizer, so it should be linked with libfixsanitizer.so.
+pub fn compile_to_object(
+    source: &str,
+    out: &Path,
+    opt_level: OptimizationLevel,
+) -> Result<(), CompileError> {
+    let options = CompileOptions::default();
+    let program = try_parse_source(source).map_err(|e| CompileError::Parse(e.to_string()))?;
+
+    // Build and verify LLVM module.
+    let context = Context::create();
+    let module = build_module(&context, program, &options)?;
+    try_verify_module(&module).map_err(CompileError::Verify)?;
+
+    // Optimize the module, and remove functions which are not used (after inlining) from the entry function.
+    optimize_module(&module, opt_level);
+    prune_unused_functions(&module, &["main"]);
+
+    // Create target machine of the host.
+    Target::initialize_native(&InitializationConfig::default()).map_err(CompileError::Jit)?;
+    let triple = TargetMachine::get_default_triple();
+    let target = Target::from_triple(&triple).map_err(|e| CompileError::Jit(e.to_string()))?;
+    let target_machine = target
+        .create_target_machine(
+            &triple,
+            TargetMachine::get_host_cpu_name().to_str().unwrap(),
+            TargetMachine::get_host_cpu_features().to_str().unwrap(),
+            opt_level,
+            RelocMode::PIC,
+            CodeModel::Default,
+        )
+        .ok_or_else(|| {
+            CompileError::Jit(format!(
+                "Failed to create target machine for {}.",
+                triple.as_str().to_string_lossy()
+            ))
+        })?;
+    module.set_triple(&triple);
+    module.set_data_layout(&target_machine.get_target_data().get_data_layout());
+
+    // Write object file.
+    target_machine
+        .write_to_file(&module, FileType::Object, out)
+        .map_err(|e| CompileError::Io(e.to_string()))
+}
+
+// LLVM context and execution engines (one for each optimization level) shared by lean runs in a thread.
+struct LeanJit {
+    context: &'static Context,
+    engines: Vec<(OptimizationLevel, ExecutionEngine<'static>)>,
+    // Number of runs so far, which makes names of entry functions unique in the engines.
+    runs: usize,
+}
+
+impl LeanJit {
+    // Get the execution engine of an optimization level, creating it (with an empty module) at the first use.
+    fn engine(
+        &mut self,
+        opt_level: OptimizationLevel,
+    ) -> Result<&ExecutionEngine<'static>, CompileError> {
+        if let Some(i) = self
+            .engines
+            .iter()
+            .position(|(level, _)| *level == opt_level)
+        {
+            return Ok(&self.engines[i].1);
+        }
+        let engine = self
+            .context
+            .create_module("lean")
+            .create_jit_execution_engine(opt_level)
+            .map_err(|e| CompileError::Jit(e.to_string()))?;
+        self.engines.push((opt_level, engine));
+        Ok(&self.engines.last().unwrap().1)
+    }
+}
+
+thread_local! {
+    static LEAN_JIT: RefCell<LeanJit> = RefCell::new(LeanJit {
+        context: Box::leak(Box::new(Context::create())),
+        engines: vec![],
+        runs: 0,
+    });
+}
+
+// Run a program by a lean path for benchmarking the essential compile-and-run work: the LLVM context and the execution engine
+// are reused, and the module is not verified and no phase time is measured.
+// The module of a run is added to the engine and removed after the run. Since MCJIT doesn't free the code of removed modules,
+// the code of each run stays in the engine, so the entry function of each run has a unique name,
+// and the other functions are made internal so that they are not confused with the ones of previous runs.
+// The result is the same as `run_source` for a valid program.
+pub fn run_source_lean(source: &str, opt_level: OptimizationLevel) -> Result<i64, CompileError> {
+    LEAN_JIT.with(|lean_jit| {
+        let mut lean_jit = lean_jit.borrow_mut();
+        let options = CompileOptions::default();
+        let program = try_parse_source(source).map_err(|e| CompileError::Parse(e.to_string()))?;
+        let entry_name = format!("main_{}", lean_jit.runs);
+        lean_jit.runs += 1;
+
+        // Build LLVM module.
+        let context = lean_jit.context;
+        let module = context.create_module("main");
+        let mut gc = GenerationContext::new(context, &module);
+        build_runtime(&mut gc);
+        build_entry_function(&mut gc, program, &entry_name, &options)?;
+        drop(gc);
+        for func in module.get_functions() {
+            if func.count_basic_blocks() > 0 && func.get_name().to_str().unwrap() != entry_name {
+                func.set_linkage(Linkage::Internal);
+            }
+        }
+
+        // Run the module on the cached engine.
+        load_runtime_library(&options.runtime_library_path()).map_err(CompileError::Jit)?;
+        let execution_engine = lean_jit.engine(opt_level)?;
+        execution_engine
+            .add_module(&module)
+            .map_err(|_| CompileError::Jit(String::from("Failed to add module to the engine.")))?;
+        let ret = unsafe {
+            execution_engine
+                .get_function::<unsafe extern "C" fn() -> i64>(&entry_name)
+                .map(|func| func.call())
+                .map_err(|e| CompileError::Jit(e.to_string()))
+        };
+        execution_engine
+            .remove_module(&module)
+            .map_err(|e| CompileError::Jit(format!("{:?}", e)))?;
+        ret
+    })
+}
+
+// Run a program whose result is a float.
+pub fn run_source_f64(source: &str, opt_level: OptimizationLevel) -> Result<f64, CompileError> {
+    let ast = try_parse_source(source).map_err(|e| CompileError::Parse(e.to_string()))?;
+    let ast = app(var(FLOAT_BITS_NAME), ast);
+    let bits = run_ast(ast, opt_level, &CompileOptions::default())?;
+    Ok(f64::from_bits(bits as u64))
+}
+
+// Compile multiple programs into one module as entry functions main_0, main_1, ..., and run them on a single execution engine.
+// Runtime functions and dtors are shared between programs.
+pub fn run_sources_batch(
+    sources: &[&str],
+    opt_level: OptimizationLevel,
+) -> Result<Vec<i64>, CompileError> {
+    // Build LLVM module.
+    let context = Context::create();
+    let module = context.create_module("main");
+    let mut gc = GenerationContext::new(&context, &module);
+    build_runtime(&mut gc);
+    let entry_names: Vec<String> = (0..sources.len()).map(|i| format!("main_{}", i)).collect();
+    for (source, entry_name) in sources.iter().zip(&entry_names) {
+        let program = try_parse_source(source).map_err(|e| CompileError::Parse(e.to_string()))?;
+        build_entry_function(&mut gc, program, entry_name, &CompileOptions::default())?;
+    }
+    drop(gc);
+    try_verify_module(&module).map_err(CompileError::Verify)?;
+
+    // Run entry functions.
+    load_runtime_library(&CompileOptions::default().runtime_library_path())
+        .map_err(CompileError::Jit)?;
+    let execution_engine = module
+        .create_jit_execution_engine(opt_level)
+        .map_err(|e| CompileError::Jit(e.to_string()))?;
+    entry_names
+        .iter()
+        .map(|entry_name| -> Result<i64, CompileError> {
+            let func = unsafe {
+                execution_engine
+                    .get_function::<unsafe extern "C" fn() -> i64>(entry_name)
+                    .map_err(|e| CompileError::Jit(e.to_string()))?
+            };
+            Ok(unsafe { func.call() })
+        })
+        .collect()
+}
+
+// Build a function of a given name and type i64(i64), which evaluates the program (of type Int -> Int),
+// applies the result to the argument and returns the int value of the result.
+fn build_int_fn_entry_function<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    program: Arc<ExprInfo>,
+    name: &str,
+    options: &CompileOptions,
+) -> Result<FunctionValue<'c>, CompileError> {
+    let context = gc.context;
+    let program = prepare_program(program, options, EntryKind::IntFn)?;
+
+    // Add entry function.
+    let entry_fn_type = context
+        .i64_type()
+        .fn_type(&[context.i64_type().into()], false);
+    let entry_function = gc.module.add_function(name, entry_fn_type, None);
+    let entry_bb = context.append_basic_block(entry_function, "entry");
+    gc.builder().position_at_end(entry_bb);
+
+    // Evaluate program and box the argument.
+    let func = gc.eval_expr(program);
+    let arg_val = entry_function.get_first_param().unwrap().into_int_value();
+    let arg = ObjectType::int_obj_type().create_obj(gc, "argument of jit function");
+    gc.store_obj_field(arg, int_type(context), 1, arg_val);
+
+    // Apply the function and unbox the result.
+    let program_result = gc.apply_lambda(func, arg);
+    let result = gc
+        .load_obj_field(program_result, int_type(context), 1)
+        .into_int_value();
+    gc.release(program_result);
+
+    // Perform leak check
+    if SANITIZE_MEMORY {
+        gc.call_runtime(RuntimeFunctions::CheckLeak, &[]);
+    }
+
+    gc.builder().build_return(Some(&result));
+    Ok(entry_function)
 }
 
-pub fn run_source(source: &str, opt_level: OptimizationLevel) -> i64 {
-    let ast = parse_source(source);
-    run_ast(ast, opt_level)
+// Function of type Int -> Int compiled by JIT, which owns the LLVM context, the module and the execution engine of its code.
+// Fields are dropped in the order of declaration, so the context is dropped after everything borrowing it.
+struct JitIntFn {
+    func: JitFunction<'static, unsafe extern "C" fn(i64) -> i64>,
+    _execution_engine: ExecutionEngine<'static>,
+    _module: Module<'static>,
+    _context: Box<Context>,
 }
 
-pub fn run_file(path: &Path, opt_level: OptimizationLevel) -> i64 {
+impl JitIntFn {
+    fn call(&self, x: i64) -> i64 {
+        unsafe { self.func.call(x) }
+    }
+}
+
+// Compile a program of type Int -> Int into a JIT function, and return a Rust closure calling it.
+// The closure owns the LLVM context and the execution engine, which are released when the closure is dropped.
+pub fn jit_compile_int_fn(source: &str) -> Result<impl Fn(i64) -> i64, CompileError> {
+    let options = CompileOptions::default();
+    let program = try_parse_source(source).map_err(|e| CompileError::Parse(e.to_string()))?;
+
+    // The context is boxed so that its address is not changed by moving it into JitIntFn.
+    // Locals borrowing it are declared after it, so they are dropped before it also on errors.
+    let context = Box::new(Context::create());
+    let context_ref: &'static Context = unsafe { &*(&*context as *const Context) };
+
+    // Build LLVM module.
+    let module = context_ref.create_module("main");
+    let mut gc = GenerationContext::new(context_ref, &module);
+    build_runtime(&mut gc);
+    build_int_fn_entry_function(&mut gc, program, "main", &options)?;
+    drop(gc);
+    try_verify_module(&module).map_err(CompileError::Verify)?;
+
+    // Create JIT function.
+    load_runtime_library(&options.runtime_library_path()).map_err(CompileError::Jit)?;
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Default)
+        .map_err(|e| CompileError::Jit(e.to_string()))?;
+    let func = unsafe {
+        execution_engine
+            .get_function::<unsafe extern "C" fn(i64) -> i64>("main")
+            .map_err(|e| CompileError::Jit(e.to_string()))?
+    };
+    let jit_fn = JitIntFn {
+        func,
+        _execution_engine: execution_engine,
+        _module: module,
+        _context: context,
+    };
+    // Call a method so that the closure captures the whole JitIntFn, not only the field func.
+    Ok(move |x| jit_fn.call(x))
+}
+
+pub fn run_file(
+    path: &Path,
+    opt_level: OptimizationLevel,
+    options: &CompileOptions,
+) -> Result<i64, CompileError> {
     let display = path.display();
 
     let mut file = match File::open(&path) {
-        Err(why) => panic!("Couldn't open {}: {}", display, why),
+        Err(why) => {
+            return Err(CompileError::Io(format!(
+                "Couldn't open {}: {}",
+                display, why
+            )))
+        }
         Ok(file) => file,
     };
 
@@ -95,9 +1035,14 @@ pub fn run_file(path: &Path, opt_level: OptimizationLevel) -> i64 {
     // ファイルの中身を文字列に読み込む。`io::Result<useize>`を返す。
     let mut s = String::new();
     match file.read_to_string(&mut s) {
-        Err(why) => panic!("Couldn't read {}: {}", display, why),
+        Err(why) => {
+            return Err(CompileError::Io(format!(
+                "Couldn't read {}: {}",
+                display, why
+            )))
+        }
         Ok(_) => (),
     }
 
-    run_source(s.as_str(), opt_level)
+    run_source_with_options(s.as_str(), opt_level, options)
 }
@@ -3,14 +3,32 @@ use super::*;
 #[derive(Eq, Hash, PartialEq, Clone)]
 pub enum RuntimeFunctions {
     Abort,
+    Exit,
     Printf,
+    Error,
+    PutStr,
+    GetEnv,
+    StrToLL,
+    ErrnoLocation,
+    GetEnvInt,
+    ParseInt,
+    GetLine,
+    Free,
+    ReadLineInt,
     Panic,
     ReportMalloc,
     ReportRetain,
     ReportRelease,
     CheckLeak,
+    ReportTrace,
+    GetKind,
+    GetNumberOfLiveObjects,
+    ReportKindMismatch,
     RetainObj,
     ReleaseObj,
+    RetainWeakCell,
+    ReleaseWeakCell,
+    DtorWeakTarget,
     Dtor(ObjectType),
 }
 
@@ -19,6 +37,402 @@ fn build_abort_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>) -> FunctionV
     gc.module.add_function("abort", fn_ty, None)
 }
 
+fn build_exit_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let fn_ty = gc
+        .context
+        .void_type()
+        .fn_type(&[gc.context.i32_type().into()], false);
+    gc.module.add_function("exit", fn_ty, None)
+}
+
+// Build function which prints the given error code and exits with nonzero status.
+fn build_error_function<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let context = gc.context;
+    let fn_ty = context
+        .void_type()
+        .fn_type(&[context.i64_type().into()], false);
+    let error_func = gc.module.add_function("fix_error", fn_ty, None);
+    let bb = context.append_basic_block(error_func, "entry");
+
+    let _builder_guard = gc.push_builder();
+    gc.builder().position_at_end(bb);
+
+    let code = error_func.get_first_param().unwrap().into_int_value();
+    let format = gc
+        .builder()
+        .build_global_string_ptr("error is called with code %lld!\n", "error_format")
+        .as_pointer_value();
+    gc.call_runtime(RuntimeFunctions::Printf, &[format.into(), code.into()]);
+    let one = context.i32_type().const_int(1, false);
+    gc.call_runtime(RuntimeFunctions::Exit, &[one.into()]);
+    gc.builder().build_unreachable();
+    error_func
+}
+
+// Build function which prints a buffer of the given size to stdout and returns the number of bytes written.
+fn build_put_str_function<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let context = gc.context;
+    let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::Generic);
+    let i64_type = context.i64_type();
+    let fn_ty = i64_type.fn_type(&[i8_ptr_type.into(), i64_type.into()], false);
+    let func = gc.module.add_function("fix_put_str", fn_ty, None);
+    let bb = context.append_basic_block(func, "entry");
+
+    let _builder_guard = gc.push_builder();
+    gc.builder().position_at_end(bb);
+
+    let buffer = func.get_nth_param(0).unwrap().into_pointer_value();
+    let size = func.get_nth_param(1).unwrap().into_int_value();
+    let size = gc
+        .builder()
+        .build_int_truncate(size, context.i32_type(), "size_i32");
+    let format = gc
+        .builder()
+        .build_global_string_ptr("%.*s", "put_str_format")
+        .as_pointer_value();
+    let written = gc
+        .call_runtime(
+            RuntimeFunctions::Printf,
+            &[format.into(), size.into(), buffer.into()],
+        )
+        .try_as_basic_value()
+        .unwrap_left()
+        .into_int_value();
+    let written = gc
+        .builder()
+        .build_int_s_extend(written, i64_type, "written");
+    gc.builder().build_return(Some(&written));
+    func
+}
+
+fn build_getenv_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let i8_ptr_type = gc.context.i8_type().ptr_type(AddressSpace::Generic);
+    let fn_ty = i8_ptr_type.fn_type(&[i8_ptr_type.into()], false);
+    gc.module.add_function("getenv", fn_ty, None)
+}
+
+fn build_strtoll_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let i8_ptr_type = gc.context.i8_type().ptr_type(AddressSpace::Generic);
+    let fn_ty = gc.context.i64_type().fn_type(
+        &[
+            i8_ptr_type.into(),
+            i8_ptr_type.ptr_type(AddressSpace::Generic).into(),
+            gc.context.i32_type().into(),
+        ],
+        false,
+    );
+    gc.module.add_function("strtoll", fn_ty, None)
+}
+
+// Declare the function of glibc which returns the pointer to errno of the current thread.
+fn build_errno_location_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let i32_ptr_type = gc.context.i32_type().ptr_type(AddressSpace::Generic);
+    let fn_ty = i32_ptr_type.fn_type(&[], false);
+    gc.module.add_function("__errno_location", fn_ty, None)
+}
+
+// Value of errno set by strtoll when the value is out of range (on Linux).
+const ERANGE: u64 = 34;
+
+// Call strtoll on a null-terminated string in base 10, and store the end of the parsed part to ptr_to_end.
+// Returns the parsed value and whether the string is acceptable as an integer so far, which is stricter than strtoll:
+// the string must start with a digit or a sign (strtoll skips leading whitespaces),
+// and the value must be in the range of i64 (strtoll saturates it otherwise).
+fn build_strict_strtoll_call<'c, 'm>(
+    gc: &GenerationContext<'c, 'm>,
+    str: PointerValue<'c>,
+    ptr_to_end: PointerValue<'c>,
+) -> (IntValue<'c>, IntValue<'c>) {
+    let context = gc.context;
+    let i8_type = context.i8_type();
+
+    // Check the first character. It is a digit if it minus '0' is less than 10 as unsigned.
+    let first_char = gc.builder().build_load(str, "first_char").into_int_value();
+    let digit =
+        gc.builder()
+            .build_int_sub(first_char, i8_type.const_int(b'0' as u64, false), "digit");
+    let is_digit = gc.builder().build_int_compare(
+        IntPredicate::ULT,
+        digit,
+        i8_type.const_int(10, false),
+        "is_digit",
+    );
+    let is_minus = gc.builder().build_int_compare(
+        IntPredicate::EQ,
+        first_char,
+        i8_type.const_int(b'-' as u64, false),
+        "is_minus",
+    );
+    let is_plus = gc.builder().build_int_compare(
+        IntPredicate::EQ,
+        first_char,
+        i8_type.const_int(b'+' as u64, false),
+        "is_plus",
+    );
+    let is_sign = gc.builder().build_or(is_minus, is_plus, "is_sign");
+    let is_valid_start = gc.builder().build_or(is_digit, is_sign, "is_valid_start");
+
+    // Clear errno, since strtoll sets it only on failure.
+    let errno_ptr = gc
+        .call_runtime(RuntimeFunctions::ErrnoLocation, &[])
+        .try_as_basic_value()
+        .unwrap_left()
+        .into_pointer_value();
+    gc.builder()
+        .build_store(errno_ptr, context.i32_type().const_zero());
+    let ten = context.i32_type().const_int(10, false);
+    let parsed = gc
+        .call_runtime(
+            RuntimeFunctions::StrToLL,
+            &[str.into(), ptr_to_end.into(), ten.into()],
+        )
+        .try_as_basic_value()
+        .unwrap_left()
+        .into_int_value();
+    let errno = gc.builder().build_load(errno_ptr, "errno").into_int_value();
+    let is_in_range = gc.builder().build_int_compare(
+        IntPredicate::NE,
+        errno,
+        context.i32_type().const_int(ERANGE, false),
+        "is_in_range",
+    );
+    let is_acceptable = gc
+        .builder()
+        .build_and(is_valid_start, is_in_range, "is_acceptable");
+    (parsed, is_acceptable)
+}
+
+// Build function which reads an environment variable of the given (null-terminated) name as an integer.
+// Returns the given default value if the variable is unset or is not an integer in the range of i64.
+fn build_getenv_int_function<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let context = gc.context;
+    let i8_type = context.i8_type();
+    let i8_ptr_type = i8_type.ptr_type(AddressSpace::Generic);
+    let i64_type = context.i64_type();
+    let fn_ty = i64_type.fn_type(&[i8_ptr_type.into(), i64_type.into()], false);
+    let func = gc.module.add_function("fix_getenv_int", fn_ty, None);
+    let entry_bb = context.append_basic_block(func, "entry");
+    let set_bb = context.append_basic_block(func, "set_bb");
+    let default_bb = context.append_basic_block(func, "default_bb");
+
+    let _builder_guard = gc.push_builder();
+    gc.builder().position_at_end(entry_bb);
+    let name = func.get_first_param().unwrap().into_pointer_value();
+    let default = func.get_nth_param(1).unwrap().into_int_value();
+    let ptr_to_end = gc.builder().build_alloca(i8_ptr_type, "ptr_to_end");
+
+    // Get value of the variable.
+    let value = gc
+        .call_runtime(RuntimeFunctions::GetEnv, &[name.into()])
+        .try_as_basic_value()
+        .unwrap_left()
+        .into_pointer_value();
+    let is_unset = gc.builder().build_is_null(value, "is_unset");
+    gc.builder()
+        .build_conditional_branch(is_unset, default_bb, set_bb);
+
+    // Parse value. It is an integer if it is acceptable, not empty and strtoll consumed all of it.
+    gc.builder().position_at_end(set_bb);
+    let (parsed, is_acceptable) = build_strict_strtoll_call(gc, value, ptr_to_end);
+    let end = gc
+        .builder()
+        .build_load(ptr_to_end, "end")
+        .into_pointer_value();
+    let end_char = gc.builder().build_load(end, "end_char").into_int_value();
+    let is_empty = gc.builder().build_int_compare(
+        IntPredicate::EQ,
+        gc.builder().build_ptr_to_int(end, i64_type, "end_addr"),
+        gc.builder().build_ptr_to_int(value, i64_type, "value_addr"),
+        "is_empty",
+    );
+    let is_rest =
+        gc.builder()
+            .build_int_compare(IntPredicate::NE, end_char, i8_type.const_zero(), "is_rest");
+    let is_invalid = gc.builder().build_or(is_empty, is_rest, "is_invalid");
+    let is_unacceptable = gc.builder().build_not(is_acceptable, "is_unacceptable");
+    let is_invalid = gc
+        .builder()
+        .build_or(is_invalid, is_unacceptable, "is_invalid");
+    let ret = gc
+        .builder()
+        .build_select(is_invalid, default, parsed, "getenv_int");
+    gc.builder().build_return(Some(&ret));
+
+    gc.builder().position_at_end(default_bb);
+    gc.builder().build_return(Some(&default));
+    func
+}
+
+// Build function which parses a string of the given size as an integer, and stores it to the given pointer.
+// Returns 1 if the string is a valid integer in the range of i64, and 0 otherwise.
+fn build_parse_int_function<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let context = gc.context;
+    let i8_type = context.i8_type();
+    let i8_ptr_type = i8_type.ptr_type(AddressSpace::Generic);
+    let i64_type = context.i64_type();
+    let fn_ty = i8_type.fn_type(
+        &[
+            i8_ptr_type.into(),
+            i64_type.into(),
+            i64_type.ptr_type(AddressSpace::Generic).into(),
+        ],
+        false,
+    );
+    let func = gc.module.add_function("fix_parse_int", fn_ty, None);
+    let entry_bb = context.append_basic_block(func, "entry");
+
+    let _builder_guard = gc.push_builder();
+    gc.builder().position_at_end(entry_bb);
+    let buffer = func.get_first_param().unwrap().into_pointer_value();
+    let size = func.get_nth_param(1).unwrap().into_int_value();
+    let ptr_to_result = func.get_nth_param(2).unwrap().into_pointer_value();
+    let ptr_to_end = gc.builder().build_alloca(i8_ptr_type, "ptr_to_end");
+
+    // Parse buffer and store the result.
+    let (parsed, is_acceptable) = build_strict_strtoll_call(gc, buffer, ptr_to_end);
+    gc.builder().build_store(ptr_to_result, parsed);
+
+    // The string is an integer if it is acceptable, not empty and strtoll consumed all of it.
+    let end = gc
+        .builder()
+        .build_load(ptr_to_end, "end")
+        .into_pointer_value();
+    let buffer_end = unsafe { gc.builder().build_gep(buffer, &[size.into()], "buffer_end") };
+    let is_consumed = gc.builder().build_int_compare(
+        IntPredicate::EQ,
+        gc.builder().build_ptr_to_int(end, i64_type, "end_addr"),
+        gc.builder()
+            .build_ptr_to_int(buffer_end, i64_type, "buffer_end_addr"),
+        "is_consumed",
+    );
+    let is_nonempty = gc.builder().build_int_compare(
+        IntPredicate::SGT,
+        size,
+        i64_type.const_zero(),
+        "is_nonempty",
+    );
+    let is_valid = gc.builder().build_and(is_consumed, is_nonempty, "is_valid");
+    let is_valid = gc.builder().build_and(is_valid, is_acceptable, "is_valid");
+    let is_valid = gc
+        .builder()
+        .build_int_z_extend(is_valid, i8_type, "is_valid_i8");
+    gc.builder().build_return(Some(&is_valid));
+    func
+}
+
+fn build_getline_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let i8_ptr_type = gc.context.i8_type().ptr_type(AddressSpace::Generic);
+    let i64_type = gc.context.i64_type();
+    let fn_ty = i64_type.fn_type(
+        &[
+            i8_ptr_type.ptr_type(AddressSpace::Generic).into(),
+            i64_type.ptr_type(AddressSpace::Generic).into(),
+            i8_ptr_type.into(),
+        ],
+        false,
+    );
+    gc.module.add_function("getline", fn_ty, None)
+}
+
+fn build_free_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let i8_ptr_type = gc.context.i8_type().ptr_type(AddressSpace::Generic);
+    let fn_ty = gc.context.void_type().fn_type(&[i8_ptr_type.into()], false);
+    gc.module.add_function("free", fn_ty, None)
+}
+
+// Build function which reads a line from the given stream (FILE*) and parses it as an integer, and stores it to the given pointer.
+// Returns 1 if a line is read and it is an integer (except the trailing newline), and 0 otherwise (e.g., at EOF).
+fn build_read_line_int_function<'c, 'm, 'b>(
+    gc: &mut GenerationContext<'c, 'm>,
+) -> FunctionValue<'c> {
+    let context = gc.context;
+    let i8_type = context.i8_type();
+    let i8_ptr_type = i8_type.ptr_type(AddressSpace::Generic);
+    let i64_type = context.i64_type();
+    let fn_ty = i8_type.fn_type(
+        &[
+            i8_ptr_type.into(),
+            i64_type.ptr_type(AddressSpace::Generic).into(),
+        ],
+        false,
+    );
+    let func = gc.module.add_function("fix_read_line_int", fn_ty, None);
+    let entry_bb = context.append_basic_block(func, "entry");
+    let line_bb = context.append_basic_block(func, "line_bb");
+    let end_bb = context.append_basic_block(func, "end_bb");
+
+    let _builder_guard = gc.push_builder();
+    gc.builder().position_at_end(entry_bb);
+    let stream = func.get_first_param().unwrap().into_pointer_value();
+    let ptr_to_result = func.get_nth_param(1).unwrap().into_pointer_value();
+
+    // Read a line into a buffer allocated by getline.
+    let ptr_to_line = gc.builder().build_alloca(i8_ptr_type, "ptr_to_line");
+    gc.builder()
+        .build_store(ptr_to_line, i8_ptr_type.const_null());
+    let ptr_to_cap = gc.builder().build_alloca(i64_type, "ptr_to_cap");
+    gc.builder().build_store(ptr_to_cap, i64_type.const_zero());
+    let len = gc
+        .call_runtime(
+            RuntimeFunctions::GetLine,
+            &[ptr_to_line.into(), ptr_to_cap.into(), stream.into()],
+        )
+        .try_as_basic_value()
+        .unwrap_left()
+        .into_int_value();
+    let has_line =
+        gc.builder()
+            .build_int_compare(IntPredicate::SGT, len, i64_type.const_zero(), "has_line");
+    gc.builder()
+        .build_conditional_branch(has_line, line_bb, end_bb);
+
+    // Parse the line without the trailing newline.
+    gc.builder().position_at_end(line_bb);
+    let line = gc
+        .builder()
+        .build_load(ptr_to_line, "line")
+        .into_pointer_value();
+    let last_idx = gc
+        .builder()
+        .build_int_sub(len, i64_type.const_int(1, false), "last_idx");
+    let ptr_to_last = unsafe {
+        gc.builder()
+            .build_gep(line, &[last_idx.into()], "ptr_to_last")
+    };
+    let last = gc
+        .builder()
+        .build_load(ptr_to_last, "last")
+        .into_int_value();
+    let is_newline = gc.builder().build_int_compare(
+        IntPredicate::EQ,
+        last,
+        i8_type.const_int('\n' as u64, false),
+        "is_newline",
+    );
+    let len = gc
+        .builder()
+        .build_select(is_newline, last_idx, len, "len_without_newline")
+        .into_int_value();
+    let parsed = gc
+        .call_runtime(
+            RuntimeFunctions::ParseInt,
+            &[line.into(), len.into(), ptr_to_result.into()],
+        )
+        .try_as_basic_value()
+        .unwrap_left()
+        .into_int_value();
+    gc.builder().build_unconditional_branch(end_bb);
+
+    // Free the buffer (getline may allocate it even at EOF) and return.
+    gc.builder().position_at_end(end_bb);
+    let is_valid = gc.builder().build_phi(i8_type, "is_valid");
+    is_valid.add_incoming(&[(&i8_type.const_zero(), entry_bb), (&parsed, line_bb)]);
+    let line = gc.builder().build_load(ptr_to_line, "line");
+    gc.call_runtime(RuntimeFunctions::Free, &[line.into()]);
+    gc.builder().build_return(Some(&is_valid.as_basic_value()));
+    func
+}
+
 fn build_printf_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
     let context = gc.context;
     let module = gc.module;
@@ -38,6 +452,7 @@ fn build_report_malloc_function<'c, 'm>(gc: &GenerationContext<'c, 'm>) -> Funct
         &[
             ptr_to_object_type(gc.context).into(),
             gc.context.i8_type().ptr_type(AddressSpace::Generic).into(),
+            gc.context.i64_type().into(),
         ],
         false,
     );
@@ -68,11 +483,49 @@ fn build_report_release_function<'c, 'm>(gc: &GenerationContext<'c, 'm>) -> Func
     gc.module.add_function("report_release", fn_ty, None)
 }
 
+fn build_report_trace_function<'c, 'm>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let fn_ty = gc.context.void_type().fn_type(
+        &[
+            ptr_to_object_type(gc.context).into(),
+            obj_id_type(gc.context).into(),
+            refcnt_type(gc.context).into(),
+        ],
+        false,
+    );
+    gc.module.add_function("report_trace", fn_ty, None)
+}
+
 fn build_check_leak_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
     let fn_ty = gc.context.void_type().fn_type(&[], false);
     gc.module.add_function("check_leak", fn_ty, None)
 }
 
+fn build_get_kind_function<'c, 'm>(gc: &GenerationContext<'c, 'm>) -> FunctionValue<'c> {
+    let fn_ty = gc
+        .context
+        .i64_type()
+        .fn_type(&[obj_id_type(gc.context).into()], false);
+    gc.module.add_function("get_kind", fn_ty, None)
+}
+
+fn build_get_number_of_live_objects_function<'c, 'm>(
+    gc: &GenerationContext<'c, 'm>,
+) -> FunctionValue<'c> {
+    let fn_ty = gc.context.i64_type().fn_type(&[], false);
+    gc.module
+        .add_function("get_number_of_live_objects", fn_ty, None)
+}
+
+fn build_report_kind_mismatch_function<'c, 'm>(
+    gc: &GenerationContext<'c, 'm>,
+) -> FunctionValue<'c> {
+    let fn_ty = gc.context.void_type().fn_type(
+        &[gc.context.i64_type().into(), gc.context.i64_type().into()],
+        false,
+    );
+    gc.module.add_function("report_kind_mismatch", fn_ty, None)
+}
+
 fn build_retain_function<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>) -> FunctionValue<'c> {
     let context = gc.context;
     let module = gc.module;
@@ -170,11 +623,261 @@ fn build_release_function<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>) -> Fun
     // TODO: Add code for leak detector
 }
 
+// Get the pointer to weak cell from the dtor field of an object tagged by the lowest bit.
+fn untag_weak_cell<'c, 'm>(
+    gc: &GenerationContext<'c, 'm>,
+    tagged: PointerValue<'c>,
+) -> PointerValue<'c> {
+    let i64_type = gc.context.i64_type();
+    let addr = gc
+        .builder()
+        .build_ptr_to_int(tagged, i64_type, "tagged_addr");
+    let addr = gc
+        .builder()
+        .build_and(addr, i64_type.const_int(!1, false), "cell_addr");
+    gc.builder()
+        .build_int_to_ptr(addr, ptr_to_weak_cell_type(gc.context), "ptr_to_cell")
+}
+
+// Build function which returns the weak cell of an object for a new weak reference.
+// If the object has no weak cell, then it is allocated and the dtor field of the object is replaced by the tagged pointer to it.
+fn build_retain_weak_cell_function<'c, 'm, 'b>(
+    gc: &mut GenerationContext<'c, 'm>,
+) -> FunctionValue<'c> {
+    let context = gc.context;
+    let i64_type = context.i64_type();
+    let cell_type = weak_cell_type(context);
+    let func_type =
+        ptr_to_weak_cell_type(context).fn_type(&[ptr_to_object_type(context).into()], false);
+    let func = gc.module.add_function("retain_weak_cell", func_type, None);
+    let entry_bb = context.append_basic_block(func, "entry");
+    let has_cell_bb = context.append_basic_block(func, "has_cell_bb");
+    let new_cell_bb = context.append_basic_block(func, "new_cell_bb");
+
+    let _builder_guard = gc.push_builder();
+    gc.builder().position_at_end(entry_bb);
+    let ptr_to_obj = func.get_first_param().unwrap().into_pointer_value();
+    let ptr_to_dtor_field = gc.get_dtor_ptr(ptr_to_obj);
+    let dtor = gc
+        .builder()
+        .build_load(ptr_to_dtor_field, "dtor")
+        .into_pointer_value();
+    let dtor_addr = gc.builder().build_ptr_to_int(dtor, i64_type, "dtor_addr");
+    let tag = gc
+        .builder()
+        .build_and(dtor_addr, i64_type.const_int(1, false), "dtor_tag");
+    let has_cell =
+        gc.builder()
+            .build_int_compare(IntPredicate::NE, tag, i64_type.const_zero(), "has_cell");
+    gc.builder()
+        .build_conditional_branch(has_cell, has_cell_bb, new_cell_bb);
+
+    // If the object has a weak cell, increment its weakcnt.
+    gc.builder().position_at_end(has_cell_bb);
+    let ptr_to_cell = untag_weak_cell(gc, dtor);
+    let ptr_to_weakcnt = gc
+        .builder()
+        .build_struct_gep(ptr_to_cell, 0, "ptr_to_weakcnt")
+        .unwrap();
+    let weakcnt = gc
+        .builder()
+        .build_load(ptr_to_weakcnt, "weakcnt")
+        .into_int_value();
+    let one = i64_type.const_int(1, false);
+    let weakcnt = gc.builder().build_int_add(weakcnt, one, "weakcnt");
+    gc.builder().build_store(ptr_to_weakcnt, weakcnt);
+    gc.builder().build_return(Some(&ptr_to_cell));
+
+    // Otherwise, allocate a weak cell which moves the dtor from the object.
+    gc.builder().position_at_end(new_cell_bb);
+    let ptr_to_cell = gc.builder().build_malloc(cell_type, "ptr_to_cell").unwrap();
+    let fields: [BasicValueEnum<'c>; 3] = [one.into(), dtor.into(), ptr_to_obj.into()];
+    for (i, field) in fields.into_iter().enumerate() {
+        let ptr_to_field = gc
+            .builder()
+            .build_struct_gep(ptr_to_cell, i as u32, "ptr_to_cell_field")
+            .unwrap();
+        gc.builder().build_store(ptr_to_field, field);
+    }
+    let cell_addr = gc
+        .builder()
+        .build_ptr_to_int(ptr_to_cell, i64_type, "cell_addr");
+    let tagged_addr = gc.builder().build_or(cell_addr, one, "tagged_addr");
+    let tagged =
+        gc.builder()
+            .build_int_to_ptr(tagged_addr, ptr_to_dtor_type(context), "tagged_cell");
+    gc.builder().build_store(ptr_to_dtor_field, tagged);
+    gc.builder().build_return(Some(&ptr_to_cell));
+    func
+}
+
+// Build function which decrements weakcnt of a weak cell and frees it if weakcnt becomes zero.
+// If the object of the cell is still alive at that time, its dtor is moved back to the object.
+fn build_release_weak_cell_function<'c, 'm, 'b>(
+    gc: &mut GenerationContext<'c, 'm>,
+) -> FunctionValue<'c> {
+    let context = gc.context;
+    let i64_type = context.i64_type();
+    let void_type = context.void_type();
+    let func_type = void_type.fn_type(&[ptr_to_weak_cell_type(context).into()], false);
+    let func = gc.module.add_function("release_weak_cell", func_type, None);
+    let bb = context.append_basic_block(func, "entry");
+
+    let _builder_guard = gc.push_builder();
+    gc.builder().position_at_end(bb);
+
+    // Decrement weakcnt.
+    let ptr_to_cell = func.get_first_param().unwrap().into_pointer_value();
+    let ptr_to_weakcnt = gc
+        .builder()
+        .build_struct_gep(ptr_to_cell, 0, "ptr_to_weakcnt")
+        .unwrap();
+    let weakcnt = gc
+        .builder()
+        .build_load(ptr_to_weakcnt, "weakcnt")
+        .into_int_value();
+    let one = i64_type.const_int(1, false);
+    let weakcnt = gc.builder().build_int_sub(weakcnt, one, "weakcnt");
+    gc.builder().build_store(ptr_to_weakcnt, weakcnt);
+
+    // Free cell if weakcnt is zero.
+    let zero = i64_type.const_zero();
+    let is_weakcnt_zero =
+        gc.builder()
+            .build_int_compare(IntPredicate::EQ, weakcnt, zero, "is_weakcnt_zero");
+    let weakcnt_zero_bb = context.append_basic_block(func, "weakcnt_zero");
+    let alive_bb = context.append_basic_block(func, "alive_bb");
+    let free_bb = context.append_basic_block(func, "free_bb");
+    let cont_bb = context.append_basic_block(func, "end");
+    gc.builder()
+        .build_conditional_branch(is_weakcnt_zero, weakcnt_zero_bb, cont_bb);
+    gc.builder().position_at_end(weakcnt_zero_bb);
+    let ptr_to_target = gc
+        .builder()
+        .build_struct_gep(ptr_to_cell, 2, "ptr_to_target")
+        .unwrap();
+    let target = gc
+        .builder()
+        .build_load(ptr_to_target, "target")
+        .into_pointer_value();
+    let is_expired = gc.builder().build_is_null(target, "is_expired");
+    gc.builder()
+        .build_conditional_branch(is_expired, free_bb, alive_bb);
+
+    // If the object is alive, move the dtor back to it.
+    gc.builder().position_at_end(alive_bb);
+    let ptr_to_dtor = gc
+        .builder()
+        .build_struct_gep(ptr_to_cell, 1, "ptr_to_dtor")
+        .unwrap();
+    let dtor = gc.builder().build_load(ptr_to_dtor, "dtor");
+    let ptr_to_dtor_field = gc.get_dtor_ptr(target);
+    gc.builder().build_store(ptr_to_dtor_field, dtor);
+    gc.builder().build_unconditional_branch(free_bb);
+
+    gc.builder().position_at_end(free_bb);
+    gc.builder().build_free(ptr_to_cell);
+    gc.builder().build_unconditional_branch(cont_bb);
+
+    gc.builder().position_at_end(cont_bb);
+    gc.builder().build_return(None);
+    func
+}
+
+// Build function which destructs an object having a weak cell: the cell is expired and the dtor kept in it is called.
+// The cell itself is kept alive by weak references, so it is not freed here.
+fn build_dtor_weak_target_function<'c, 'm, 'b>(
+    gc: &mut GenerationContext<'c, 'm>,
+) -> FunctionValue<'c> {
+    let context = gc.context;
+    let void_type = context.void_type();
+    let func_type = void_type.fn_type(&[ptr_to_object_type(context).into()], false);
+    let func = gc.module.add_function("dtor_weak_target", func_type, None);
+    let bb = context.append_basic_block(func, "entry");
+
+    let _builder_guard = gc.push_builder();
+    gc.builder().position_at_end(bb);
+    let ptr_to_obj = func.get_first_param().unwrap().into_pointer_value();
+    let ptr_to_dtor_field = gc.get_dtor_ptr(ptr_to_obj);
+    let tagged = gc
+        .builder()
+        .build_load(ptr_to_dtor_field, "tagged_cell")
+        .into_pointer_value();
+    let ptr_to_cell = untag_weak_cell(gc, tagged);
+
+    // Expire the cell before calling dtor, since dtor may release the last weak reference and free the cell.
+    let ptr_to_target = gc
+        .builder()
+        .build_struct_gep(ptr_to_cell, 2, "ptr_to_target")
+        .unwrap();
+    gc.builder()
+        .build_store(ptr_to_target, ptr_to_object_type(context).const_null());
+    let ptr_to_dtor = gc
+        .builder()
+        .build_struct_gep(ptr_to_cell, 1, "ptr_to_dtor")
+        .unwrap();
+    let dtor = gc
+        .builder()
+        .build_load(ptr_to_dtor, "dtor")
+        .into_pointer_value();
+    let dtor_func = CallableValue::try_from(dtor).unwrap();
+    gc.builder()
+        .build_call(dtor_func, &[ptr_to_obj.into()], "call_dtor");
+    gc.builder().build_return(None);
+    func
+}
+
+// Get signatures of runtime functions declared in the module, as pairs of name and type (e.g., "void (i8*)"), sorted by name.
+// This is useful to check that the runtime library implements functions with expected signatures.
+pub fn runtime_function_signatures<'c, 'm>(
+    gc: &GenerationContext<'c, 'm>,
+) -> Vec<(String, String)> {
+    let mut signatures: Vec<(String, String)> = gc
+        .runtimes
+        .values()
+        .map(|func| {
+            (
+                func.get_name().to_str().unwrap().to_string(),
+                func.get_type().print_to_string().to_string(),
+            )
+        })
+        .collect();
+    signatures.sort();
+    signatures
+}
+
 pub fn build_runtime<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>) {
     gc.runtimes
         .insert(RuntimeFunctions::Abort, build_abort_function(gc));
+    gc.runtimes
+        .insert(RuntimeFunctions::Exit, build_exit_function(gc));
     gc.runtimes
         .insert(RuntimeFunctions::Printf, build_printf_function(gc));
+    let error_func = build_error_function(gc);
+    gc.runtimes.insert(RuntimeFunctions::Error, error_func);
+    let put_str_func = build_put_str_function(gc);
+    gc.runtimes.insert(RuntimeFunctions::PutStr, put_str_func);
+    gc.runtimes
+        .insert(RuntimeFunctions::GetEnv, build_getenv_function(gc));
+    gc.runtimes
+        .insert(RuntimeFunctions::StrToLL, build_strtoll_function(gc));
+    gc.runtimes.insert(
+        RuntimeFunctions::ErrnoLocation,
+        build_errno_location_function(gc),
+    );
+    let getenv_int_func = build_getenv_int_function(gc);
+    gc.runtimes
+        .insert(RuntimeFunctions::GetEnvInt, getenv_int_func);
+    let parse_int_func = build_parse_int_function(gc);
+    gc.runtimes
+        .insert(RuntimeFunctions::ParseInt, parse_int_func);
+    gc.runtimes
+        .insert(RuntimeFunctions::GetLine, build_getline_function(gc));
+    gc.runtimes
+        .insert(RuntimeFunctions::Free, build_free_function(gc));
+    let read_line_int_func = build_read_line_int_function(gc);
+    gc.runtimes
+        .insert(RuntimeFunctions::ReadLineInt, read_line_int_func);
     if SANITIZE_MEMORY {
         gc.runtimes.insert(
             RuntimeFunctions::ReportMalloc,
@@ -190,9 +893,32 @@ pub fn build_runtime<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>) {
         );
         gc.runtimes
             .insert(RuntimeFunctions::CheckLeak, build_check_leak_function(gc));
+        gc.runtimes.insert(
+            RuntimeFunctions::ReportTrace,
+            build_report_trace_function(gc),
+        );
+        gc.runtimes
+            .insert(RuntimeFunctions::GetKind, build_get_kind_function(gc));
+        gc.runtimes.insert(
+            RuntimeFunctions::GetNumberOfLiveObjects,
+            build_get_number_of_live_objects_function(gc),
+        );
+        gc.runtimes.insert(
+            RuntimeFunctions::ReportKindMismatch,
+            build_report_kind_mismatch_function(gc),
+        );
     }
     let retain_func = build_retain_function(gc);
     gc.runtimes.insert(RuntimeFunctions::RetainObj, retain_func);
+    let retain_weak_cell_func = build_retain_weak_cell_function(gc);
+    gc.runtimes
+        .insert(RuntimeFunctions::RetainWeakCell, retain_weak_cell_func);
+    let release_weak_cell_func = build_release_weak_cell_function(gc);
+    gc.runtimes
+        .insert(RuntimeFunctions::ReleaseWeakCell, release_weak_cell_func);
+    let dtor_weak_target_func = build_dtor_weak_target_function(gc);
+    gc.runtimes
+        .insert(RuntimeFunctions::DtorWeakTarget, dtor_weak_target_func);
     let release_func = build_release_function(gc);
     gc.runtimes
         .insert(RuntimeFunctions::ReleaseObj, release_func);
@@ -1,7 +1,31 @@
 use super::*;
+use inkwell::targets::TargetData;
+use std::os::unix::process::ExitStatusExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 fn test_run_source(source: &str, answer: i64, opt_level: OptimizationLevel) {
-    assert_eq!(run_source(source, opt_level), answer)
+    assert_eq!(run_source(source, opt_level).unwrap(), answer)
+}
+
+// Get the number of objects allocated so far, which is counted by the sanitizer (0 if the sanitizer is disabled).
+fn get_number_of_allocations() -> i64 {
+    if !SANITIZE_MEMORY {
+        return 0;
+    }
+    unsafe {
+        let lib =
+            libloading::Library::new(CompileOptions::default().runtime_library_path()).unwrap();
+        let func: libloading::Symbol<unsafe extern "C" fn() -> i64> =
+            lib.get(b"get_number_of_allocations").unwrap();
+        func()
+    }
+}
+
+// Run a program, and get the result and the number of objects allocated by it.
+fn run_counting_allocations(source: &str, options: &CompileOptions) -> (i64, i64) {
+    let before = get_number_of_allocations();
+    let res = run_source_with_options(source, OptimizationLevel::Default, options).unwrap();
+    (res, get_number_of_allocations() - before)
 }
 
 // Tests should run sequentially, since OBJECT_TABLE in libfixsanitizer.so is shared between tests and check_leak() asserts OBJECT_TABLE is empty.
@@ -328,3 +352,3155 @@ pub fn test28() {
     let answer = 832040;
     test_run_source(source, answer, OptimizationLevel::Default);
 }
+
+#[test]
+#[serial]
+pub fn test29() {
+    // Test eqArray on equal arrays.
+    let source = r"
+            let a = newArray 3 7;
+            let b = writeArray (newArray 3 0) 0 7;
+            let b = writeArray b 1 7;
+            let b = writeArray b 2 7;
+            let emptyEq = eqArray (newArray 0 1) (newArray 0 2);
+            if eqArray a b then (if emptyEq then 1 else 2) else 0
+        ";
+    let answer = 1;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test30() {
+    // Test eqArray on arrays of different sizes.
+    let source = r"
+            let a = newArray 3 7;
+            let b = newArray 4 7;
+            if eqArray a b then 1 else 0
+        ";
+    let answer = 0;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test31() {
+    // Test eqArray on arrays with a differing element.
+    let source = r"
+            let a = newArray 3 7;
+            let b = writeArray (newArray 3 7) 1 8;
+            if eqArray a b then 1 else 0
+        ";
+    let answer = 0;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test32() {
+    // Test loop built-in: the state is [sum, counter].
+    let n = 1000000;
+    let source = format!(
+        r"
+            let start = writeArray! (newArray 2 0) 1 1;
+            let cond = \s -> if eq (readArray s 1) {} then false else true;
+            let step = \s ->
+                let i = readArray s 1;
+                let acc = readArray s 0;
+                let s = writeArray! s 0 (add acc i);
+                writeArray! s 1 (add i 1);
+            readArray (loop start cond step) 0
+        ",
+        n + 1
+    );
+    let answer = (n * (n + 1)) / 2;
+    test_run_source(source.as_str(), answer, OptimizationLevel::Default);
+}
+
+// Tests pinning the wrap-around semantics of integer built-ins on overflow.
+mod overflow_tests {
+    use super::*;
+
+    #[test]
+    #[serial]
+    pub fn test_add_max_one() {
+        let source = format!(r"add {} 1", i64::MAX);
+        test_run_source(source.as_str(), i64::MIN, OptimizationLevel::Default);
+    }
+
+    #[test]
+    #[serial]
+    pub fn test_add_min_minus_one() {
+        let source = format!(r"add {} -1", i64::MIN);
+        test_run_source(source.as_str(), i64::MAX, OptimizationLevel::Default);
+    }
+
+    #[test]
+    #[serial]
+    pub fn test_add_max_max() {
+        let source = format!(r"add {} {}", i64::MAX, i64::MAX);
+        test_run_source(
+            source.as_str(),
+            i64::MAX.wrapping_add(i64::MAX),
+            OptimizationLevel::Default,
+        );
+    }
+}
+
+// A library function `leakId` which leaks its argument and returns its id in the object table of the sanitizer.
+fn leak_id() -> Arc<ExprInfo> {
+    let leak_id_generator: Arc<LiteralGenerator> = Arc::new(|gc| {
+        let obj = gc.scope_get("x").ptr;
+        let id = gc.get_obj_id(obj);
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, "leakId x");
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, id);
+        ptr_to_int_obj
+    });
+    lam(
+        var_var("x"),
+        lit(
+            leak_id_generator,
+            vec![String::from("x")],
+            String::from("leakId x"),
+        ),
+    )
+}
+
+#[test]
+#[serial]
+pub fn test33() {
+    // Test that all objects are named in reports of sanitizer.
+    let source = r"
+            let arr = newArray 2 5;
+            let f = \x -> add x 1;
+            if eq (f 2) 3 then 1 else 0
+        ";
+    let context = Context::create();
+    let module = build_module(&context, parse_source(source), &CompileOptions::default()).unwrap();
+    let ir = module.print_to_string().to_string();
+    assert!(ir.contains("newArray size value"));
+    assert!(ir.contains("add lhs rhs"));
+    assert!(ir.contains("eq lhs rhs"));
+    assert!(!ir.contains("N/A"));
+
+    // A leaked int is reported by its literal.
+    if !SANITIZE_MEMORY {
+        return;
+    }
+    let options = CompileOptions {
+        extra_builtins: vec![(String::from("leakId"), leak_id())],
+        ..Default::default()
+    };
+    let (id, json) = run_ast_with_object_dump(
+        parse_source(r"leakId 42"),
+        OptimizationLevel::Default,
+        &options,
+    )
+    .unwrap();
+    let entry = format!(
+        "{{\"id\":{},\"name\":\"42\",\"refcnt\":1,\"kind\":\"Int\"}}",
+        id
+    );
+    assert!(json.contains(&entry), "{}", json);
+}
+
+// Run `i64 main()` of a module built by hand, mapping the given symbols to host functions as `CompileOptions::host_symbols` does.
+fn run_module(module: &Module, host_symbols: &HashMap<String, usize>) -> i64 {
+    load_runtime_library(&CompileOptions::default().runtime_library_path()).unwrap();
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Default)
+        .unwrap();
+    for (symbol, addr) in host_symbols {
+        let func = module.get_function(symbol).unwrap();
+        execution_engine.add_global_mapping(&func, *addr);
+    }
+    unsafe {
+        let func = execution_engine
+            .get_function::<unsafe extern "C" fn() -> i64>("main")
+            .unwrap();
+        func.call()
+    }
+}
+
+static TEST34_FINALIZER_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn test34_finalizer(_obj: *const i8) {
+    TEST34_FINALIZER_CALLS.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+#[serial]
+pub fn test34() {
+    // Test that the finalizer of an object type runs exactly once when the object is freed.
+    let context = Context::create();
+    let module = context.create_module("main");
+    let mut gc = GenerationContext::new(&context, &module);
+    build_runtime(&mut gc);
+    let main_fn_type = context.i64_type().fn_type(&[], false);
+    let main_function = module.add_function("main", main_fn_type, None);
+    let entry_bb = context.append_basic_block(main_function, "entry");
+    gc.builder().position_at_end(entry_bb);
+    let obj_type = ObjectType::int_obj_type().with_finalizer("test34_finalizer");
+    let obj = obj_type.create_obj(&mut gc, "finalized int");
+    gc.retain(obj);
+    gc.release(obj);
+    gc.release(obj);
+    if SANITIZE_MEMORY {
+        gc.call_runtime(RuntimeFunctions::CheckLeak, &[]);
+    }
+    gc.builder()
+        .build_return(Some(&context.i64_type().const_zero()));
+    assert!(module.verify().is_ok());
+
+    TEST34_FINALIZER_CALLS.store(0, Ordering::SeqCst);
+    let host_symbols =
+        HashMap::from([(String::from("test34_finalizer"), test34_finalizer as usize)]);
+    run_module(&module, &host_symbols);
+    assert_eq!(TEST34_FINALIZER_CALLS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+#[serial]
+pub fn test35() {
+    // Test that a local definition shadows a built-in function.
+    let source = r"
+            let eq = add;
+            eq 3 4
+        ";
+    let answer = 7;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test36() {
+    // Test that a local definition shadows a built-in function, while the built-in one is still visible in the bound expression since let is non-recursive.
+    let source = r"
+            let add = \x -> \y -> add x (add y y);
+            add 3 4
+        ";
+    let answer = 3 + 4 + 4;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test37() {
+    // Test splitAtArray.
+    let source = r"
+            let arr = newArray 5 0;
+            let arr = writeArray! arr 0 1;
+            let arr = writeArray! arr 1 2;
+            let arr = writeArray! arr 2 3;
+            let arr = writeArray! arr 3 4;
+            let arr = writeArray! arr 4 5;
+            let p = splitAtArray 2 arr;
+            let l = writeArray! (writeArray! (newArray 2 0) 0 1) 1 2;
+            let r = writeArray! (writeArray! (writeArray! (newArray 3 0) 0 3) 1 4) 2 5;
+            if eqArray (fst p) l then (if eqArray (snd p) r then 1 else 0) else 0
+        ";
+    let answer = 1;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test38() {
+    // Test splitAtArray with an index out of range.
+    let source = r"
+            let arr = writeArray! (newArray 3 0) 1 2;
+            let p = splitAtArray -1 arr;
+            let q = splitAtArray 3 arr;
+            let empty = newArray 0 0;
+            let ok1 = if eqArray (fst p) empty then eqArray (snd p) arr else false;
+            let ok2 = if eqArray (fst q) arr then eqArray (snd q) empty else false;
+            if ok1 then (if ok2 then 1 else 0) else 0
+        ";
+    let answer = 1;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test39() {
+    // Test that reporting time passes doesn't change the result.
+    let source = r"add 2 3";
+    let options = CompileOptions {
+        time_passes: true,
+        ..Default::default()
+    };
+    for opt_level in [OptimizationLevel::None, OptimizationLevel::Aggressive] {
+        assert_eq!(
+            run_source_with_options(source, opt_level, &options).unwrap(),
+            5
+        );
+    }
+}
+
+#[test]
+#[serial]
+pub fn test40() {
+    // Test lenArray.
+    let source = r"
+            let arr = newArray 100 42;
+            let arr = writeArray arr 50 21;
+            add (lenArray arr) (lenArray (newArray 0 1))
+        ";
+    let answer = 100;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test41() {
+    // Test a variable used both in function and argument position of an application.
+    let source = r"
+            let x = writeArray! (newArray 2 0) 1 5;
+            let y = (readArray x) (add (lenArray x) -1);
+            let f = \a -> \b -> add a b;
+            add y (f (lenArray x) (lenArray x))
+        ";
+    let answer = 5 + 2 + 2;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test42() {
+    // Test structural equality of parsed expressions.
+    let expected = app(app(var("add"), int(1)), int(2));
+    assert_eq!(parse_source(r"add 1 2"), expected);
+    assert_eq!(parse_source(r"(add 1) (2)"), expected);
+    assert_ne!(parse_source(r"add 2 1"), expected);
+    let expected = let_in(var_var("x"), bool(true), lam(var_var("y"), var("x")));
+    assert_eq!(parse_source(r"let x = true in \y -> x"), expected);
+}
+
+#[test]
+#[serial]
+pub fn test43() {
+    // Test option built-ins.
+    let source = r"
+            let x = some 3;
+            let y = none;
+            let a = if isSome x then unwrap x else 0;
+            let b = if isSome y then 10 else 0;
+            add a b
+        ";
+    let answer = 3;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test44() {
+    // Test findIndexArray finding an element.
+    let source = r"
+            let arr = writeArray! (writeArray! (writeArray! (newArray 3 0) 0 5) 1 6) 2 7;
+            unwrap (findIndexArray arr (eq 6))
+        ";
+    let answer = 1;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test45() {
+    // Test findIndexArray not finding an element.
+    let source = r"
+            let arr = writeArray! (writeArray! (writeArray! (newArray 3 0) 0 5) 1 6) 2 7;
+            if isSome (findIndexArray arr (eq 8)) then 1 else 0
+        ";
+    let answer = 0;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test46() {
+    // Test that application to a null object is checked in sanitize mode.
+    let context = Context::create();
+    let module = context.create_module("main");
+    let mut gc = GenerationContext::new(&context, &module);
+    build_runtime(&mut gc);
+    let main_fn_type = context.i64_type().fn_type(&[], false);
+    let main_function = module.add_function("main", main_fn_type, None);
+    let entry_bb = context.append_basic_block(main_function, "entry");
+    gc.builder().position_at_end(entry_bb);
+    let null = ptr_to_object_type(&context).const_null();
+    let ret = gc.apply_lambda(null, null);
+    let ret = gc.load_obj_field(ret, int_type(&context), 1);
+    gc.builder().build_return(Some(&ret));
+    assert!(module.verify().is_ok());
+    let ir = module.print_to_string().to_string();
+    assert_eq!(ir.contains("Null object dereference!"), SANITIZE_MEMORY);
+}
+
+#[test]
+#[serial]
+pub fn test47() {
+    // Test running multiple programs compiled into one module.
+    let sources = [
+        r"add 3 5",
+        r"let f = \x -> add x 1; f (f 1)",
+        r"readArray (writeArray (newArray 3 0) 1 7) 1",
+    ];
+    let results = run_sources_batch(&sources, OptimizationLevel::Default).unwrap();
+    assert_eq!(results, vec![8, 3, 7]);
+}
+
+#[test]
+#[serial]
+pub fn test48() {
+    // Test that LLVM IR is written only when verification fails.
+    let ir_path = Path::new(IR_PATH_ON_VERIFY_FAILURE);
+    let _ = std::fs::remove_file(ir_path);
+    test_run_source(r"add 1 2", 3, OptimizationLevel::Default);
+    assert!(!ir_path.exists());
+
+    // Build a module with a block lacking terminator.
+    let context = Context::create();
+    let module = context.create_module("main");
+    let fn_type = context.i64_type().fn_type(&[], false);
+    let function = module.add_function("main", fn_type, None);
+    context.append_basic_block(function, "entry");
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| verify_module(&module)));
+    assert!(res.is_err());
+    assert!(ir_path.exists());
+    std::fs::remove_file(ir_path).unwrap();
+}
+
+#[test]
+#[serial]
+pub fn test49() {
+    // Test foldArray and foldRightArray with a non-commutative function.
+    let source = r"
+            let arr = writeArray! (writeArray! (writeArray! (newArray 3 0) 0 1) 1 2) 2 3;
+            let f = \x -> \y -> add x (add y y);
+            let l = foldArray arr f 0;
+            let r = foldRightArray arr f 0;
+            if eq l 12 then r else 0
+        ";
+    // l = f (f (f 0 1) 2) 3 = f (f 2 2) 3 = f 6 3 = 12.
+    // r = f 1 (f 2 (f 3 0)) = f 1 (f 2 3) = f 1 8 = 17.
+    let answer = 17;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test50() {
+    // Test foldRightArray on a large array.
+    let n = 1000000;
+    let source = format!(r"foldRightArray (newArray {} 1) add 0", n);
+    test_run_source(source.as_str(), n, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+#[should_panic(expected = "if branches have incompatible types")]
+pub fn test51() {
+    // Test that if branches of different types are rejected.
+    let source = r"if true then 5 else true";
+    test_run_source(source, 5, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test52() {
+    // Test diagnostics of if branches directly.
+    assert!(check_if_branch_types(&parse_source(r"if true then 5 else 3")).is_ok());
+    assert!(check_if_branch_types(&parse_source(r"if true then let x = 1 in x else 3")).is_ok());
+    assert!(
+        check_if_branch_types(&parse_source(r"if true then (let x = 1 in false) else 3")).is_err()
+    );
+    assert!(check_if_branch_types(&parse_source(
+        r"\x -> if x then 1 else (if x then 2 else false)"
+    ))
+    .is_err());
+
+    // Every entry path reports the error instead of panicking.
+    let is_branch_type_error = |err: Option<CompileError>| match err {
+        Some(CompileError::Type(msg)) => msg.contains("if branches have incompatible types"),
+        _ => false,
+    };
+    let source = r"if true then 5 else true";
+    let context = Context::create();
+    assert!(is_branch_type_error(
+        build_module(&context, parse_source(source), &CompileOptions::default()).err()
+    ));
+    assert!(is_branch_type_error(
+        run_sources_batch(&["1", source], OptimizationLevel::Default).err()
+    ));
+    assert!(is_branch_type_error(
+        run_source_lean(source, OptimizationLevel::Default).err()
+    ));
+    assert!(is_branch_type_error(
+        jit_compile_int_fn(r"\x -> if eq x 0 then 1 else false").err()
+    ));
+}
+
+#[test]
+#[serial]
+pub fn test53() {
+    // Test a recursive function (defined by fix) which returns a closure.
+    let source = r"
+            let mk = fix \self -> \n ->
+                if eq n 0 then
+                    \x -> x
+                else
+                    let g = self (add n -1);
+                    \x -> add 1 (g x);
+            (mk 3) 10
+        ";
+    let answer = 13;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test54() {
+    // Test concatStr and lengthStr.
+    let source = r#"
+            let s = concatStr "ab" "cde";
+            add (lengthStr s) (lengthStr (concatStr s ""))
+        "#;
+    let answer = 10;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test55() {
+    // Test that programs run correctly with tail calls disabled.
+    let source = r"
+            let sum = fix \f -> \n -> if eq n 0 then 0 else add n (f (add n -1));
+            sum 100
+        ";
+    let options = CompileOptions {
+        disable_tail_calls: true,
+        ..Default::default()
+    };
+    let context = Context::create();
+    let module = build_module(&context, parse_source(source), &options).unwrap();
+    let ir = module.print_to_string().to_string();
+    assert!(!ir.contains("tail call"));
+    for opt_level in [OptimizationLevel::None, OptimizationLevel::Default] {
+        assert_eq!(
+            run_source_with_options(source, opt_level, &options).unwrap(),
+            5050
+        );
+    }
+}
+
+#[test]
+#[serial]
+pub fn test56() {
+    // Test that only library functions used in the program are emitted.
+    let builtin_names = ["add lhs rhs", "eq lhs rhs", "newArray size value"];
+    let context = Context::create();
+    let options = CompileOptions::default();
+
+    let module = build_module(&context, parse_source("5"), &options).unwrap();
+    let ir = module.print_to_string().to_string();
+    for name in builtin_names {
+        assert!(!ir.contains(name));
+    }
+
+    let module = build_module(&context, parse_source("add 2 3"), &options).unwrap();
+    let ir = module.print_to_string().to_string();
+    assert!(ir.contains("add lhs rhs"));
+    assert!(!ir.contains("eq lhs rhs"));
+    assert!(!ir.contains("newArray size value"));
+}
+
+#[test]
+#[serial]
+pub fn test57() {
+    // Test if expressions whose branches contain applications, which add basic blocks in sanitize mode.
+    let source = r"
+            let f = \x -> add x 1;
+            if eq (f 1) 2 then f (f 3) else f 0
+        ";
+    let answer = 5;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test58() {
+    // Test comparison and chained comparison.
+    let source = r"
+            let f = \x -> add x 1;
+            let a = if 1 < 2 < 3 then 1 else 0;
+            let b = if 1 < 5 < 3 then 10 else 0;
+            let c = if f 1 < f 2 < f 3 < 5 then 100 else 0;
+            let d = if 3 < 2 then 1000 else 0;
+            add a (add b (add c d))
+        ";
+    let answer = 101;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+pub fn test59() {
+    // Test that middle operands of a comparison chain are bound to variables.
+    let parsed = parse_source("a < f b < c");
+    let expected = let_in(
+        var_var("%cmp0%"),
+        app(var("f"), var("b")),
+        conditional(
+            app(app(var("lt"), var("a")), var("%cmp0%")),
+            app(app(var("lt"), var("%cmp0%")), var("c")),
+            bool(false),
+        ),
+    );
+    assert_eq!(parsed, expected);
+}
+
+#[test]
+pub fn test60() {
+    // Test a visitor counting App nodes.
+    struct AppCounter {
+        count: usize,
+    }
+    impl Visitor for AppCounter {
+        fn visit_app(&mut self, func: &Arc<ExprInfo>, arg: &Arc<ExprInfo>) {
+            self.count += 1;
+            self.visit_expr(func);
+            self.visit_expr(arg);
+        }
+    }
+    let program = parse_source(r"let f = \x -> add x 1; if eq (f 1) 2 then f 3 else 0");
+    let mut counter = AppCounter { count: 0 };
+    counter.visit_expr(&program);
+    assert_eq!(counter.count, 6);
+
+    // Test a folder renaming free occurrences of a variable, which tracks shadowing by the binder hooks.
+    struct Renamer {
+        shadowed: usize,
+    }
+    impl Folder for Renamer {
+        fn enter_binder(&mut self, var: &Arc<Var>) {
+            if var.name() == "x" {
+                self.shadowed += 1;
+            }
+        }
+        fn exit_binder(&mut self, var: &Arc<Var>) {
+            if var.name() == "x" {
+                self.shadowed -= 1;
+            }
+        }
+        fn fold_var(&mut self, ei: Arc<ExprInfo>, var: &Arc<Var>) -> Arc<ExprInfo> {
+            if var.name() == "x" && self.shadowed == 0 {
+                return crate::ast::var("y");
+            }
+            ei
+        }
+    }
+    let program = parse_source(r"add x (let x = x in \y -> add x ((\x -> x) y))");
+    let expected = parse_source(r"add y (let x = y in \y -> add x ((\x -> x) y))");
+    assert_eq!(Renamer { shadowed: 0 }.fold_expr(program), expected);
+}
+
+#[test]
+#[serial]
+pub fn test61() {
+    // Test an array captured by a closure and also used directly after the closure is created.
+    let source = r"
+            let a = newArray 3 0 in
+            let f = \i -> readArray a i in
+            add (f 0) (readArray a 1)
+        ";
+    let answer = 0;
+    test_run_source(source, answer, OptimizationLevel::Default);
+
+    let source = r"
+            let a = writeArray (writeArray (newArray 3 0) 0 3) 1 4 in
+            let f = \i -> readArray a i in
+            let g = \i -> add (f i) (readArray a 2) in
+            add (add (f 0) (g 1)) (readArray a 1)
+        ";
+    let answer = 11;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test62() {
+    // Test modifyArray.
+    let source = r"readArray (modifyArray (newArray 3 10) 1 (add 5)) 1";
+    let answer = 15;
+    test_run_source(source, answer, OptimizationLevel::Default);
+
+    // Modifying a shared array doesn't change the original one.
+    let source = r"
+            let arr = newArray 3 10;
+            let arr2 = modifyArray arr 2 (\x -> add x x);
+            add (readArray arr 2) (readArray arr2 2)
+        ";
+    let answer = 30;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test63() {
+    // Test pair, firstP and secondP.
+    let source = r"fst (firstP (add 1) (pair 3 4))";
+    let answer = 4;
+    test_run_source(source, answer, OptimizationLevel::Default);
+
+    let source = r"snd (firstP (add 1) (pair 3 4))";
+    let answer = 4;
+    test_run_source(source, answer, OptimizationLevel::Default);
+
+    let source = r"
+            let p = secondP (\x -> add x x) (pair 3 4);
+            add (fst p) (snd p)
+        ";
+    let answer = 11;
+    test_run_source(source, answer, OptimizationLevel::Default);
+
+    // Components may be objects other than integers.
+    let source = r"
+            let p = firstP (\arr -> writeArray arr 0 7) (pair (newArray 2 1) (newArray 2 2));
+            add (readArray (fst p) 0) (readArray (snd p) 1)
+        ";
+    let answer = 9;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test64() {
+    // Test library functions defined by the embedder.
+    let triple = lam(
+        var_var("x"),
+        app(
+            app(var("add"), var("x")),
+            app(app(var("add"), var("x")), var("x")),
+        ),
+    );
+    let sextuple = lam(
+        var_var("x"),
+        app(
+            app(var("add"), app(var("triple"), var("x"))),
+            app(var("triple"), var("x")),
+        ),
+    );
+    let unused = lam(var_var("x"), var("x"));
+    let options = CompileOptions {
+        extra_builtins: vec![
+            (String::from("triple"), triple),
+            (String::from("sextuple"), sextuple),
+            (String::from("unused"), unused),
+        ],
+        ..Default::default()
+    };
+    assert_eq!(
+        run_source_with_options("triple 7", OptimizationLevel::Default, &options).unwrap(),
+        21
+    );
+    assert_eq!(
+        run_source_with_options("sextuple 7", OptimizationLevel::Default, &options).unwrap(),
+        42
+    );
+
+    // Only referenced definitions are emitted.
+    let context = Context::create();
+    let module = build_module(&context, parse_source("triple 7"), &options).unwrap();
+    let ir = module.print_to_string().to_string();
+    assert!(ir.contains("add lhs rhs"));
+    assert!(!ir.contains("x->(x)"));
+}
+
+#[test]
+#[serial]
+pub fn test65() {
+    // Test if expressions whose branch never reaches the continuation.
+    let trap_generator: Arc<LiteralGenerator> = Arc::new(|gc| {
+        gc.panic("trap!\n");
+        gc.build_unreachable_and_continue();
+        ptr_to_object_type(gc.context).get_undef()
+    });
+    let trap_lit = || lit(trap_generator.clone(), vec![], String::from("trap"));
+    let trap = lam(var_var("x"), trap_lit());
+    let options = CompileOptions {
+        extra_builtins: vec![(String::from("trap"), trap)],
+        ..Default::default()
+    };
+    let sources = [
+        (r"if eq 1 1 then 5 else trap 0", 5),
+        (r"if eq 1 2 then trap 0 else 6", 6),
+        (r"if eq 1 1 then 7 else add (trap 0) 1", 7),
+        (
+            r"if eq 1 1 then 8 else if eq 1 2 then trap 0 else trap 1",
+            8,
+        ),
+    ];
+    for (source, answer) in sources {
+        assert_eq!(
+            run_source_with_options(source, OptimizationLevel::Default, &options).unwrap(),
+            answer
+        );
+    }
+
+    // A branch which is the never-returning literal itself, not a call of a function containing it.
+    let cond = |rhs: i64| app(app(var("eq"), int(1)), int(rhs));
+    let programs = [
+        (conditional(cond(1), int(5), trap_lit()), 5),
+        (conditional(cond(2), trap_lit(), int(6)), 6),
+    ];
+    for (program, answer) in programs {
+        let context = Context::create();
+        let module = build_module(&context, program.clone(), &CompileOptions::default()).unwrap();
+        verify_module(&module);
+        // The dead end of the trapping branch is not an incoming block of the phi.
+        let ir = module.print_to_string().to_string();
+        let phis: Vec<&str> = ir
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| line.starts_with("%phi") && line.contains(" = phi "))
+            .collect();
+        assert_eq!(phis.len(), 1);
+        assert_eq!(phis[0].matches('[').count(), 1);
+
+        let result = run_ast(
+            program,
+            OptimizationLevel::Default,
+            &CompileOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result, answer);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test66() {
+    // Test error in a branch which is not taken.
+    let source = r"if eq 1 1 then 7 else error 1";
+    let answer = 7;
+    test_run_source(source, answer, OptimizationLevel::Default);
+
+    let source = r"
+            let f = \x -> if eq x 0 then error 2 else add x 1;
+            add (f 1) (if eq 1 2 then error 3 else f 2)
+        ";
+    let answer = 5;
+    test_run_source(source, answer, OptimizationLevel::Default);
+
+    // A branch which ends with the unreachable instruction itself, by taking the body of `error` out of the lambda.
+    let error_body = match &*error().expr {
+        Expr::Lam(_, body) => body.clone(),
+        _ => panic!("error is not a lambda"),
+    };
+    let program = let_in(
+        var_var("code"),
+        int(4),
+        conditional(app(app(var("eq"), int(1)), int(1)), int(7), error_body),
+    );
+    let context = Context::create();
+    let module = build_module(&context, program.clone(), &CompileOptions::default()).unwrap();
+    verify_module(&module);
+    let ir = module.print_to_string().to_string();
+    let phi = ir
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| line.starts_with("%phi") && line.contains(" = phi "))
+        .unwrap();
+    assert_eq!(phi.matches('[').count(), 1);
+    let result = run_ast(
+        program,
+        OptimizationLevel::Default,
+        &CompileOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(result, 7);
+}
+
+#[test]
+#[serial]
+pub fn test67() {
+    // Test getEnvInt.
+    std::env::set_var("FIX_TEST_VAL", "42");
+    std::env::set_var("FIX_TEST_NOT_INT", "4x2");
+    std::env::set_var("FIX_TEST_SPACED", " 42");
+    std::env::set_var("FIX_TEST_HUGE", "99999999999999999999");
+    std::env::remove_var("FIX_TEST_UNSET");
+    let source = r#"getEnvInt "FIX_TEST_VAL" 0"#;
+    test_run_source(source, 42, OptimizationLevel::Default);
+    let source = r#"getEnvInt "FIX_TEST_UNSET" 7"#;
+    test_run_source(source, 7, OptimizationLevel::Default);
+    let source = r#"getEnvInt "FIX_TEST_NOT_INT" 8"#;
+    test_run_source(source, 8, OptimizationLevel::Default);
+    let source = r#"getEnvInt (concatStr "FIX_TEST" "_VAL") 0"#;
+    test_run_source(source, 42, OptimizationLevel::Default);
+    // Leading whitespaces and values out of range of Int fall back to the default.
+    let source = r#"getEnvInt "FIX_TEST_SPACED" 9"#;
+    test_run_source(source, 9, OptimizationLevel::Default);
+    let source = r#"getEnvInt "FIX_TEST_HUGE" 10"#;
+    test_run_source(source, 10, OptimizationLevel::Default);
+}
+
+#[test]
+pub fn test68() {
+    // Test layout of int objects.
+    let context = Context::create();
+    let target_data = TargetData::create("e-m:e-i64:64-f80:128-n8:16:32:64-S128");
+    let layout = ObjectType::int_obj_type().layout(&context, &target_data);
+    let cb_size = if SANITIZE_MEMORY { 24 } else { 16 };
+    let mut expected = vec![
+        FieldLayout {
+            name: String::from("control_block.refcnt"),
+            offset: 0,
+            size: 8,
+        },
+        FieldLayout {
+            name: String::from("control_block.dtor"),
+            offset: 8,
+            size: 8,
+        },
+    ];
+    if SANITIZE_MEMORY {
+        expected.push(FieldLayout {
+            name: String::from("control_block.obj_id"),
+            offset: 16,
+            size: 8,
+        });
+    }
+    expected.push(FieldLayout {
+        name: String::from("int"),
+        offset: cb_size,
+        size: 8,
+    });
+    assert_eq!(layout.fields, expected);
+    assert_eq!(layout.size, cb_size + 8);
+    assert!(layout.to_string().contains("control_block.refcnt"));
+}
+
+#[test]
+pub fn test69() {
+    // Test that a wrong path to the runtime library yields a descriptive error.
+    let options = CompileOptions {
+        runtime_library_path: Some(String::from("no/such/libfixsanitizer.so")),
+        ..Default::default()
+    };
+    let res = load_runtime_library(&options.runtime_library_path());
+    if SANITIZE_MEMORY {
+        let msg = res.unwrap_err();
+        assert!(msg.contains("no/such/libfixsanitizer.so"));
+        assert!(msg.contains(RUNTIME_LIBRARY_PATH_ENV));
+    } else {
+        assert!(res.is_ok());
+    }
+}
+
+#[test]
+#[serial]
+pub fn test70() {
+    // Test sliceView / readView / writeView.
+    let source = r"
+            let arr = writeArray! (writeArray! (newArray 4 0) 1 3) 2 5;
+            let v = sliceView arr 1 2;
+            add (readView v 0) (readView v 1)
+        ";
+    let answer = 8;
+    test_run_source(source, answer, OptimizationLevel::Default);
+
+    // The parent stays alive while the view is alive, even if the parent is released first.
+    let source = r"
+            let v = sliceView (writeArray! (newArray 3 0) 2 7) 1 2;
+            let v = writeView v 0 4;
+            add (readView v 0) (readView v 1)
+        ";
+    let answer = 11;
+    test_run_source(source, answer, OptimizationLevel::Default);
+
+    // Writes through a view are visible through the parent.
+    let source = r"
+            let arr = newArray 3 1;
+            let v = writeView (sliceView arr 1 2) 1 6;
+            add (readArray arr 2) (readView v 0)
+        ";
+    let answer = 7;
+    test_run_source(source, answer, OptimizationLevel::Default);
+}
+
+#[test]
+#[serial]
+pub fn test71() {
+    // Test remarks of dead let elimination.
+    let mut remarks = vec![];
+    let program = optimize_ast(parse_source(r"let x = add 1 2 in 9"), &mut remarks);
+    assert_eq!(remarks.len(), 1);
+    assert_eq!(remarks[0].pass, "dead-let");
+    assert!(remarks[0].message.contains("`x`"));
+    assert_eq!(remarks[0].location, Some(Span { start: 8, end: 15 }));
+    assert!(remarks[0].to_string().ends_with("at 8..15"));
+    assert_eq!(*program, *int(9));
+
+    // Bindings which may have effects are kept.
+    let mut remarks = vec![];
+    optimize_ast(
+        parse_source(r"let add = \x -> \y -> error 1; let x = add 1 2 in 9"),
+        &mut remarks,
+    );
+    assert!(remarks.is_empty());
+
+    // A binding which becomes unused by elimination of an inner binding is also eliminated.
+    let mut remarks = vec![];
+    let program = optimize_ast(parse_source(r"let x = 1 in let y = x in 9"), &mut remarks);
+    assert_eq!(remarks.len(), 2);
+    assert_eq!(*program, *int(9));
+
+    let options = CompileOptions {
+        ast_optimization: true,
+        opt_remarks: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        run_source_with_options(
+            r"let x = add 1 2 in 9",
+            OptimizationLevel::Default,
+            &options
+        )
+        .unwrap(),
+        9
+    );
+}
+
+#[test]
+#[serial]
+pub fn test72() {
+    // Test compiling a function into a Rust closure.
+    let f = jit_compile_int_fn(r"\x -> add x 1").unwrap();
+    assert_eq!(f(0), 1);
+    assert_eq!(f(41), 42);
+    assert_eq!(f(-10), -9);
+
+    // Functions compiled repeatedly are released when dropped, together with their LLVM contexts.
+    for n in 0..10 {
+        let f = jit_compile_int_fn(&format!(r"\x -> mul x {}", n)).unwrap();
+        assert_eq!(f(3), 3 * n);
+    }
+    assert_eq!(f(1), 2);
+}
+
+static TEST73_KIND_MISMATCHES: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn test73_report_kind_mismatch(expected: i64, actual: i64) {
+    assert_eq!(expected, ObjectKind::Int as i64);
+    assert_eq!(actual, ObjectKind::Bool as i64);
+    TEST73_KIND_MISMATCHES.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+#[serial]
+pub fn test73() {
+    // Test that writing an object of a different kind into an array is reported by sanitizer.
+    if !SANITIZE_MEMORY {
+        return;
+    }
+    // Such a program is ill-typed, so a bool is written into an int array through `coerce` of type a -> b.
+    let coerce_generator: Arc<LiteralGenerator> = Arc::new(|gc| gc.scope_get("x").ptr);
+    let coerce = lam(
+        var_var("x"),
+        lit(
+            coerce_generator,
+            vec![String::from("x")],
+            String::from("coerce"),
+        ),
+    );
+    let options = CompileOptions {
+        extra_builtins: vec![(String::from("coerce"), coerce)],
+        host_symbols: HashMap::from([(
+            String::from("report_kind_mismatch"),
+            test73_report_kind_mismatch as usize,
+        )]),
+        ..Default::default()
+    };
+    let sources = [
+        (
+            r"readArray (writeArray (newArray 2 0) 0 (coerce true)) 1",
+            1,
+        ),
+        (r"readArray (writeArray! (newArray 2 0) 0 5) 0", 0),
+    ];
+    for (source, mismatches) in sources {
+        TEST73_KIND_MISMATCHES.store(0, Ordering::SeqCst);
+        run_ast(parse_source(source), OptimizationLevel::Default, &options).unwrap();
+        assert_eq!(TEST73_KIND_MISMATCHES.load(Ordering::SeqCst), mismatches);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test74() {
+    // Test liveObjects.
+    let base = run_source(r"liveObjects 0", OptimizationLevel::Default).unwrap();
+    let with_array = run_source(
+        r"let a = newArray 10 0 in liveObjects a",
+        OptimizationLevel::Default,
+    )
+    .unwrap();
+    let released = run_source(
+        r"let a = newArray 10 0 in let n = lenArray a in liveObjects n",
+        OptimizationLevel::Default,
+    )
+    .unwrap();
+    if SANITIZE_MEMORY {
+        assert!(base > 0);
+        assert!(with_array > base);
+        assert!(released < with_array);
+    } else {
+        assert_eq!(base, 0);
+        assert_eq!(with_array, 0);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test75() {
+    // Test iterate.
+    let source = r"iterate 10 (\x -> add x x) 1";
+    test_run_source(source, 1024, OptimizationLevel::Default);
+    let source = r"iterate -1 (add 1) 5";
+    test_run_source(source, 5, OptimizationLevel::Default);
+
+    // Test that `iterate n (add k) x` is fused and allocates no objects per step, also without AST optimization.
+    let mut remarks = vec![];
+    fuse_iterate(parse_source(r"iterate 1000000 (add 1) 0"), &mut remarks);
+    assert_eq!(remarks.len(), 1);
+    assert_eq!(remarks[0].pass, "fuse-iterate");
+
+    let options = CompileOptions::default();
+    let (res, allocations) = run_counting_allocations(r"iterate 1000000 (add 1) 0", &options);
+    assert_eq!(res, 1000000);
+    assert_eq!(
+        run_counting_allocations(r"iterate 1 (add 1) 0", &options),
+        (1, allocations)
+    );
+    // A closure which is not `add k` is called per step, allocating its result.
+    let (_, step_allocations) =
+        run_counting_allocations(r"iterate 1000 (\x -> add x 1) 0", &options);
+    if SANITIZE_MEMORY {
+        assert!(step_allocations >= 1000);
+    }
+}
+
+#[test]
+pub fn test76() {
+    // Test interning of types.
+    let mut interner = TypeInterner::default();
+    let int1 = interner.intern(int_lit_ty());
+    let int2 = interner.intern(int_lit_ty());
+    assert!(Arc::ptr_eq(&int1, &int2));
+    let bool1 = interner.intern(bool_lit_ty());
+    assert!(!Arc::ptr_eq(&int1, &bool1));
+}
+
+static TEST77_TRACED_OBJECTS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn test77_report_trace(_obj: *const i8, obj_id: i64, refcnt: i64) {
+    assert!(obj_id > 0);
+    assert_eq!(refcnt, 1);
+    TEST77_TRACED_OBJECTS.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+#[serial]
+pub fn test77() {
+    // Test trace.
+    let source = r"lenArray (trace (newArray 1 0))";
+    test_run_source(source, 1, OptimizationLevel::Default);
+    if !SANITIZE_MEMORY {
+        return;
+    }
+
+    // Test that the traced object is reported to sanitizer.
+    let options = CompileOptions {
+        host_symbols: HashMap::from([(String::from("report_trace"), test77_report_trace as usize)]),
+        ..Default::default()
+    };
+    TEST77_TRACED_OBJECTS.store(0, Ordering::SeqCst);
+    let res = run_ast(parse_source(source), OptimizationLevel::Default, &options).unwrap();
+    assert_eq!(res, 1);
+    assert_eq!(TEST77_TRACED_OBJECTS.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+pub fn test78() {
+    // Test that the generated code is deterministic for closures capturing multiple objects.
+    let source = r"
+            let a = 1;
+            let b = 2;
+            let c = 3;
+            let d = 4;
+            let f = \x -> add (add a b) (add (add c d) x);
+            let g = \x -> if eq x 0 then add a b else add c d;
+            add (f 5) (g 0)
+        ";
+    let generate_ir = || {
+        let context = Context::create();
+        let module =
+            build_module(&context, parse_source(source), &CompileOptions::default()).unwrap();
+        module.print_to_string().to_string()
+    };
+    let ir = generate_ir();
+    for _ in 0..5 {
+        assert_eq!(generate_ir(), ir);
+    }
+}
+
+static TICKS: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn test_tick(_obj: *const i8) {
+    TICKS.fetch_add(1, Ordering::SeqCst);
+}
+
+// Options with a library function `tick x`, which returns x and counts its calls in TICKS.
+fn tick_options() -> CompileOptions {
+    let tick_generator: Arc<LiteralGenerator> = Arc::new(|gc| {
+        let func_type = gc
+            .context
+            .void_type()
+            .fn_type(&[ptr_to_object_type(gc.context).into()], false);
+        let tick_func = match gc.module.get_function("test_tick") {
+            Some(func) => func,
+            None => gc.module.add_function("test_tick", func_type, None),
+        };
+        let x = gc.scope_get("x").ptr;
+        gc.builder().build_call(tick_func, &[x.into()], "call_tick");
+        x
+    });
+    let tick = lam(
+        var_var("x"),
+        lit(
+            tick_generator,
+            vec![String::from("x")],
+            String::from("tick"),
+        ),
+    );
+    CompileOptions {
+        extra_builtins: vec![(String::from("tick"), tick)],
+        host_symbols: HashMap::from([(String::from("test_tick"), test_tick as usize)]),
+        ..Default::default()
+    }
+}
+
+#[test]
+#[serial]
+pub fn test79() {
+    // Test that the thunk given to newArrayLazy is forced only if the size is positive, and only once.
+    let options = tick_options();
+    let sources = [
+        (r"lenArray (newArrayLazy 0 (\u -> tick 7))", 0, 0),
+        (r"readArray (newArrayLazy 3 (\u -> tick 7)) 2", 7, 1),
+    ];
+    for (source, answer, ticks) in sources {
+        TICKS.store(0, Ordering::SeqCst);
+        let res = run_ast(parse_source(source), OptimizationLevel::Default, &options).unwrap();
+        assert_eq!(res, answer);
+        assert_eq!(TICKS.load(Ordering::SeqCst), ticks);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test80() {
+    // Test parseInt.
+    let source = r#"unwrap (parseInt "42")"#;
+    test_run_source(source, 42, OptimizationLevel::Default);
+    let source = r#"unwrap (parseInt "-7")"#;
+    test_run_source(source, -7, OptimizationLevel::Default);
+    let source = r#"if isSome (parseInt "abc") then 1 else 0"#;
+    test_run_source(source, 0, OptimizationLevel::Default);
+    let source = r#"if isSome (parseInt "12x") then 1 else 0"#;
+    test_run_source(source, 0, OptimizationLevel::Default);
+    let source = r#"if isSome (parseInt "") then 1 else 0"#;
+    test_run_source(source, 0, OptimizationLevel::Default);
+
+    // Leading whitespaces and values out of range of Int are rejected, although strtoll accepts them.
+    let source = r#"if isSome (parseInt " 42") then 1 else 0"#;
+    test_run_source(source, 0, OptimizationLevel::Default);
+    let source = r#"if isSome (parseInt "99999999999999999999") then 1 else 0"#;
+    test_run_source(source, 0, OptimizationLevel::Default);
+    let source = r#"if isSome (parseInt "-99999999999999999999") then 1 else 0"#;
+    test_run_source(source, 0, OptimizationLevel::Default);
+    let source = r#"unwrap (parseInt "+9223372036854775807")"#;
+    test_run_source(source, i64::MAX, OptimizationLevel::Default);
+}
+
+#[test]
+pub fn test81() {
+    // Test that optimize_module reduces instructions of a foldable program.
+    let source = r"
+            let x = add 1 2;
+            let y = add x 3;
+            let z = if eq y 6 then add y 4 else 0;
+            add z 5
+        ";
+    let count_instructions = |module: &Module| {
+        let mut count = 0;
+        for func in module.get_functions() {
+            for bb in func.get_basic_blocks() {
+                let mut inst = bb.get_first_instruction();
+                while let Some(i) = inst {
+                    count += 1;
+                    inst = i.get_next_instruction();
+                }
+            }
+        }
+        count
+    };
+    let context = Context::create();
+    let module = build_module(&context, parse_source(source), &CompileOptions::default()).unwrap();
+    verify_module(&module);
+    let unoptimized_count = count_instructions(&module);
+    optimize_module(&module, OptimizationLevel::Aggressive);
+    verify_module(&module);
+    let optimized_count = count_instructions(&module);
+    assert!(optimized_count < unoptimized_count);
+}
+
+#[test]
+#[serial]
+pub fn test82() {
+    // Test that seq forces its first argument and is not eliminated by optimization.
+    let mut remarks = vec![];
+    optimize_ast(parse_source(r"let x = seq (add 1 2) 3 in 9"), &mut remarks);
+    assert!(remarks.is_empty());
+
+    let options = CompileOptions {
+        ast_optimization: true,
+        ..tick_options()
+    };
+    let source = r"let x = seq (tick 1) 5 in add (seq (tick 2) 3) 4";
+    TICKS.store(0, Ordering::SeqCst);
+    let res = run_ast(parse_source(source), OptimizationLevel::Default, &options).unwrap();
+    assert_eq!(res, 7);
+    assert_eq!(TICKS.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+#[serial]
+pub fn test83() {
+    // Test clz and popcount.
+    test_run_source(r"clz 1", 63, OptimizationLevel::Default);
+    test_run_source(r"clz 0", 64, OptimizationLevel::Default);
+    test_run_source(r"popcount 7", 3, OptimizationLevel::Default);
+    test_run_source(r"popcount 0", 0, OptimizationLevel::Default);
+}
+
+extern "C" fn test84_double(x: i64) -> i64 {
+    x * 2
+}
+
+#[test]
+#[serial]
+pub fn test84() {
+    // Test calling a host function from a program.
+    let options = CompileOptions {
+        extra_builtins: vec![(String::from("hostDouble"), host_int_fn("test84_double"))],
+        host_symbols: HashMap::from([(String::from("test84_double"), test84_double as usize)]),
+        ..Default::default()
+    };
+    let source = r"add (hostDouble 21) (hostDouble 0)";
+    let res = run_ast(parse_source(source), OptimizationLevel::Default, &options).unwrap();
+    assert_eq!(res, 42);
+
+    // The map may have symbols which the program doesn't use.
+    let res = run_ast(
+        parse_source(r"add 1 2"),
+        OptimizationLevel::Default,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(res, 3);
+}
+
+#[test]
+#[serial]
+pub fn test85() {
+    // Test sub.
+    test_run_source(r"sub 10 3", 7, OptimizationLevel::Default);
+    test_run_source(r"sub 3 10", -7, OptimizationLevel::Default);
+    test_run_source(
+        r"let f = sub 100 in sub (f 1) (f 50)",
+        49,
+        OptimizationLevel::Default,
+    );
+}
+
+#[test]
+#[serial]
+pub fn test86() {
+    // Test that the variable bound by let is removed from scope even if generation of the body fails.
+    let failing_generator: Arc<LiteralGenerator> = Arc::new(|gc| {
+        assert!(gc.scope_has("x"));
+        panic!("generation failed");
+    });
+    let program = let_in(
+        var_var("x"),
+        int(1),
+        lit(failing_generator, vec![], String::from("fail")),
+    );
+    let program = calculate_free_vars(program);
+
+    let context = Context::create();
+    let module = context.create_module("main");
+    let mut gc = GenerationContext::new(&context, &module);
+    build_runtime(&mut gc);
+    let fn_type = context.i64_type().fn_type(&[], false);
+    let function = module.add_function("main", fn_type, None);
+    let entry_bb = context.append_basic_block(function, "entry");
+    gc.builder().position_at_end(entry_bb);
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| gc.eval_expr(program)));
+    assert!(res.is_err());
+    assert!(!gc.scope_has("x"));
+}
+
+#[test]
+#[serial]
+pub fn test87() {
+    // Test mul and div.
+    test_run_source(r"mul 6 7", 42, OptimizationLevel::Default);
+    test_run_source(r"mul (sub 0 6) 7", -42, OptimizationLevel::Default);
+    test_run_source(r"div 20 4", 5, OptimizationLevel::Default);
+    test_run_source(r"div (sub 0 7) 2", -3, OptimizationLevel::Default);
+    test_run_source(r"div 9 -1", -9, OptimizationLevel::Default);
+    test_run_source(
+        r"div -9223372036854775807 -1",
+        i64::MAX,
+        OptimizationLevel::Default,
+    );
+
+    // The minimum divided by -1 overflows, so it aborts at runtime and is not folded as a constant.
+    let context = Context::create();
+    let module = build_module(
+        &context,
+        parse_source(r"let x = -9223372036854775808; div x -1"),
+        &CompileOptions::default(),
+    )
+    .unwrap();
+    verify_module(&module);
+    let ir = module.print_to_string().to_string();
+    assert!(ir.contains("Overflow in division!"));
+    assert!(try_parse_source(r"const x = div -9223372036854775808 -1; x").is_err());
+
+    // Division may abort, so an unused division is not eliminated.
+    let mut remarks = vec![];
+    optimize_ast(parse_source(r"let x = div 10 0 in 5"), &mut remarks);
+    assert!(remarks.is_empty());
+    // A library function shadowed inside the bound expression is not regarded as pure.
+    optimize_ast(
+        parse_source(r"let r = (let add = div in add 1 0) in 5"),
+        &mut remarks,
+    );
+    assert!(remarks.is_empty());
+}
+
+#[test]
+#[serial]
+pub fn test88() {
+    // Test pruning unused functions.
+    // A module whose entry function returns a constant needs no runtime function.
+    let context = Context::create();
+    let module = context.create_module("main");
+    let mut gc = GenerationContext::new(&context, &module);
+    build_runtime(&mut gc);
+    drop(gc);
+    let fn_type = context.i64_type().fn_type(&[], false);
+    let function = module.add_function("main", fn_type, None);
+    let builder = context.create_builder();
+    builder.position_at_end(context.append_basic_block(function, "entry"));
+    builder.build_return(Some(&context.i64_type().const_int(5, false)));
+    assert!(module.get_function("retain_obj").is_some());
+    prune_unused_functions(&module, &["main"]);
+    verify_module(&module);
+    assert!(module.get_function("retain_obj").is_none());
+    assert!(module.get_function("release_obj").is_none());
+    assert_eq!(module.get_functions().count(), 1);
+    assert!(!module.print_to_string().to_string().contains("declare"));
+
+    // A pruned module of a program is still runnable.
+    let context = Context::create();
+    let module = build_module(
+        &context,
+        parse_source(r"add 1 2"),
+        &CompileOptions::default(),
+    )
+    .unwrap();
+    prune_unused_functions(&module, &["main"]);
+    verify_module(&module);
+    assert!(module.get_function("main").is_some());
+    assert_eq!(run_module(&module, &HashMap::new()), 3);
+}
+
+static TEST89_STREAM: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" {
+    fn fmemopen(buf: *mut i8, size: usize, mode: *const i8) -> usize;
+    fn fclose(stream: usize) -> i32;
+}
+
+#[test]
+#[serial]
+pub fn test89() {
+    // Test readLineInt, reading lines from an in-memory stream in place of stdin.
+    let source = r"
+            let a = readLineInt 0;
+            let b = readLineInt 0;
+            let c = readLineInt 0;
+            let d = readLineInt 0;
+            let e = if isSome c then 100 else 0;
+            let f = if isSome d then 1000 else 0;
+            add (add (unwrap a) (unwrap b)) (add e f)
+        ";
+    let context = Context::create();
+    let module = build_module(&context, parse_source(source), &CompileOptions::default()).unwrap();
+    verify_module(&module);
+    load_runtime_library(&CompileOptions::default().runtime_library_path()).unwrap();
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::Default)
+        .unwrap();
+    let input = CString::new("40\n2\nabc\n").unwrap();
+    let mode = CString::new("r").unwrap();
+    let stream = unsafe {
+        fmemopen(
+            input.as_ptr() as *mut i8,
+            input.as_bytes().len(),
+            mode.as_ptr(),
+        )
+    };
+    assert_ne!(stream, 0);
+    TEST89_STREAM.store(stream, Ordering::SeqCst);
+    let stdin_global = module.get_global("stdin").unwrap();
+    execution_engine.add_global_mapping(&stdin_global, &TEST89_STREAM as *const _ as usize);
+    let res = unsafe {
+        let func = execution_engine
+            .get_function::<unsafe extern "C" fn() -> i64>("main")
+            .unwrap();
+        func.call()
+    };
+    unsafe { fclose(stream) };
+    assert_eq!(res, 42);
+}
+
+#[test]
+#[serial]
+pub fn test90() {
+    // Test comparison of integers.
+    let cases = [
+        (r"if lt 3 5 then 1 else 0", 1),
+        (r"if lt 5 5 then 1 else 0", 0),
+        (r"if le 5 5 then 1 else 0", 1),
+        (r"if le 6 5 then 1 else 0", 0),
+        (r"if gt 5 3 then 1 else 0", 1),
+        (r"if gt 5 5 then 1 else 0", 0),
+        (r"if ge 5 5 then 1 else 0", 1),
+        (r"if ge (sub 0 1) 0 then 1 else 0", 0),
+    ];
+    for (source, answer) in cases {
+        test_run_source(source, answer, OptimizationLevel::Default);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test91() {
+    // Test not, and and or.
+    let cases = [
+        (r"if not false then 1 else 0", 1),
+        (r"if not (eq 1 1) then 1 else 0", 0),
+        (r"if and (eq 1 1) (eq 2 2) then 7 else 9", 7),
+        (r"if and (eq 1 1) (eq 2 3) then 7 else 9", 9),
+        (r"if and false true then 7 else 9", 9),
+        (r"if or false true then 1 else 0", 1),
+        (r"if or true false then 1 else 0", 1),
+        (r"if or false false then 1 else 0", 0),
+        (r"let f = and true in if f (not false) then 1 else 0", 1),
+    ];
+    for (source, answer) in cases {
+        test_run_source(source, answer, OptimizationLevel::Default);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test92() {
+    // Test that the lean path gives the same results as the full path.
+    let sources = [
+        r"add 2 3",
+        r"let a = newArray 3 4 in add (readArray a 1) (lenArray a)",
+        r"let f = \x -> mul x x in f (f 3)",
+        r"if eq (popcount 7) 3 then 1 else 0",
+    ];
+    // Programs run twice on the cached engine of each optimization level, which holds the code of previous runs.
+    for opt_level in [OptimizationLevel::None, OptimizationLevel::Default] {
+        for _ in 0..2 {
+            for source in sources {
+                assert_eq!(
+                    run_source_lean(source, opt_level).unwrap(),
+                    run_source(source, opt_level).unwrap()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+pub fn test93() {
+    // Test free variables of long chains of application.
+    let mut expr = var("f");
+    for i in 0..20 {
+        expr = app(expr, var(&format!("x{}", i)));
+    }
+    let expr = calculate_free_vars(expr);
+    assert_eq!(expr.free_vars.len(), 21);
+    assert!(expr.free_vars.contains("f"));
+    assert!(expr.free_vars.contains("x19"));
+    match &*expr.expr {
+        Expr::App(func, arg) => {
+            assert_eq!(arg.free_vars.len(), 1);
+            assert!(arg.free_vars.contains("x19"));
+            // An application in function position has its own free variables.
+            assert_eq!(func.free_vars.len(), 20);
+            assert!(func.free_vars.contains("x18"));
+            assert!(!func.free_vars.contains("x19"));
+        }
+        _ => panic!("not an application"),
+    }
+
+    // A very long chain is processed without overflowing the stack in reasonable time.
+    let mut expr = var("f");
+    for i in 0..2000 {
+        expr = app(expr, var(&format!("x{}", i)));
+    }
+    let start = std::time::Instant::now();
+    let expr = calculate_free_vars(expr);
+    assert_eq!(expr.free_vars.len(), 2001);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+#[serial]
+pub fn test94() {
+    // Test float literals and arithmetic.
+    assert_eq!(
+        run_source_f64(r"addF 1.5 2.5", OptimizationLevel::Default).unwrap(),
+        4.0
+    );
+    assert_eq!(
+        run_source_f64(r"mulF (addF 0.5 -2.0) 3.0", OptimizationLevel::Default).unwrap(),
+        -4.5
+    );
+    assert_eq!(
+        run_source_f64(
+            r"let x = 1.25; let f = mulF x in f (f 2.0)",
+            OptimizationLevel::Default
+        )
+        .unwrap(),
+        3.125
+    );
+}
+
+#[test]
+#[serial]
+pub fn test95() {
+    // Test case on integers and booleans.
+    let arms = |default: i64| {
+        vec![
+            (Arc::new(Pattern::Int(1)), int(10)),
+            (
+                Arc::new(Pattern::Int(2)),
+                app(app(var("add"), var("y")), int(20)),
+            ),
+            (Arc::new(Pattern::Wildcard), int(default)),
+        ]
+    };
+    let cases = [(1, 10), (2, 25), (3, 99), (-1, 99)];
+    for (scrutinee, answer) in cases {
+        let program = let_in(var_var("y"), int(5), case_of(int(scrutinee), arms(99)));
+        let res = run_ast(
+            program,
+            OptimizationLevel::Default,
+            &CompileOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(res, answer);
+    }
+
+    let program = case_of(
+        app(app(var("eq"), int(3)), int(3)),
+        vec![
+            (Arc::new(Pattern::Bool(false)), int(0)),
+            (Arc::new(Pattern::Bool(true)), int(1)),
+        ],
+    );
+    assert_eq!(
+        run_ast(
+            program,
+            OptimizationLevel::Default,
+            &CompileOptions::default()
+        )
+        .unwrap(),
+        1
+    );
+
+    // A case with only a wildcard doesn't read the scrutinee, whose type is unknown to code generation.
+    let program = case_of(
+        app(app(var("eq"), int(3)), int(4)),
+        vec![(Arc::new(Pattern::Wildcard), int(7))],
+    );
+    let res = run_ast(
+        program,
+        OptimizationLevel::Default,
+        &CompileOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(res, 7);
+
+    // Patterns of different types are rejected, even without type inference.
+    let program = case_of(
+        int(1),
+        vec![
+            (Arc::new(Pattern::Int(1)), int(0)),
+            (Arc::new(Pattern::Bool(true)), int(1)),
+        ],
+    );
+    assert!(check_case_patterns(&program).is_err());
+    let context = Context::create();
+    match build_module(&context, program, &CompileOptions::default()) {
+        Err(CompileError::Type(msg)) => {
+            assert!(msg.contains("case patterns have incompatible types"))
+        }
+        _ => panic!("case patterns of different types are accepted"),
+    }
+}
+
+#[test]
+pub fn test96() {
+    // Test signatures of runtime functions.
+    let context = Context::create();
+    let module = context.create_module("main");
+    let mut gc = GenerationContext::new(&context, &module);
+    build_runtime(&mut gc);
+    let signatures = runtime_function_signatures(&gc);
+    let signature_of = |name: &str| {
+        signatures
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, ty)| ty.clone())
+            .unwrap()
+    };
+    assert_eq!(signature_of("retain_obj"), "void (i8*)");
+    assert_eq!(signature_of("release_obj"), "void (i8*)");
+    // report_malloc takes the address, the name and the kind of an object, and returns its id.
+    assert_eq!(signature_of("report_malloc"), "i64 (i8*, i8*, i64)");
+}
+
+#[test]
+#[serial]
+pub fn test97() {
+    // Test fst and snd. The leak check ensures that the dtor of pair releases both elements.
+    test_run_source(r"fst (pair 7 9)", 7, OptimizationLevel::Default);
+    test_run_source(r"snd (pair 7 9)", 9, OptimizationLevel::Default);
+    test_run_source(
+        r"let p = pair (newArray 2 3) 4; add (readArray (fst p) 1) (snd p)",
+        7,
+        OptimizationLevel::Default,
+    );
+}
+
+#[test]
+#[serial]
+pub fn test98() {
+    // Test unrolling of recursive functions by fix.
+    let source = r"
+            let g = \n -> fix (\f -> \x -> if eq x 0 then 1 else add x (f (sub x 1))) n;
+            add (g 10) (fix (\f -> \x -> if eq x 0 then 0 else add 2 (f (sub x 1))) 3)
+        ";
+    let mut remarks = vec![];
+    unroll_fix(parse_source(source), 4, &mut remarks);
+    assert_eq!(remarks.len(), 2);
+    assert!(remarks.iter().all(|remark| remark.pass == "unroll-fix"));
+    assert!(remarks[0].message.contains("4 times"));
+    assert!(remarks[1].message.contains("4 times"));
+    for depth in [0, 1, 4, 100] {
+        let options = CompileOptions {
+            fix_unroll_depth: depth,
+            ..Default::default()
+        };
+        assert_eq!(
+            run_source_with_options(source, OptimizationLevel::Default, &options).unwrap(),
+            56 + 6
+        );
+    }
+
+    // The body is inlined with constant conditions folded, and a recursion within the bound is fully unrolled.
+    let fix_fn = r"fix (\f -> \x -> if eq x 0 then 0 else add 2 (f (sub x 1)))";
+    let unrolled = unroll_fix(parse_source(&format!("{} 3", fix_fn)), 4, &mut remarks);
+    assert_eq!(unrolled, parse_source(r"add 2 (add 2 (add 2 0))"));
+    // Recursive calls beyond the bound fall back to the function bound by let.
+    let unrolled = unroll_fix(
+        parse_source(&format!("let g = {} in g 10", fix_fn)),
+        2,
+        &mut remarks,
+    );
+    assert_eq!(
+        unrolled,
+        parse_source(&format!(
+            "let g = {} in let f = g in add 2 (add 2 (f 8))",
+            fix_fn
+        ))
+    );
+    // A function whose library functions are shadowed is not unrolled.
+    let mut remarks = vec![];
+    let source = format!("let eq = \\x -> \\y -> true in {} 3", fix_fn);
+    unroll_fix(parse_source(&source), 4, &mut remarks);
+    assert!(remarks.is_empty());
+    let source = format!("let g = {} in let sub = add in g 3", fix_fn);
+    unroll_fix(parse_source(&source), 4, &mut remarks);
+    assert!(remarks.is_empty());
+
+    // `g 3` for a factorial-like function gives the same result with fewer allocations.
+    let source = r"
+            let g = fix (\f -> \x -> if eq x 0 then 1 else mul x (f (sub x 1)));
+            g 3
+        ";
+    let rolled = run_counting_allocations(source, &CompileOptions::default());
+    let options = CompileOptions {
+        fix_unroll_depth: 8,
+        ..Default::default()
+    };
+    let unrolled = run_counting_allocations(source, &options);
+    assert_eq!(rolled.0, 6);
+    assert_eq!(unrolled.0, 6);
+    if SANITIZE_MEMORY {
+        assert!(unrolled.1 < rolled.1);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test99() {
+    // Test that errors are returned instead of panicking.
+    let res = run_source(r"add 1 (", OptimizationLevel::Default);
+    assert!(matches!(res, Err(CompileError::Parse(_))));
+    let res = run_file(
+        Path::new("this_file_does_not_exist.fix"),
+        OptimizationLevel::Default,
+        &CompileOptions::default(),
+    );
+    assert!(matches!(res, Err(CompileError::Io(_))));
+    let options = CompileOptions {
+        runtime_library_path: Some(String::from("no_such_dir/libfixsanitizer.so")),
+        ..Default::default()
+    };
+    let res = run_source_with_options(r"add 1 2", OptimizationLevel::Default, &options);
+    if SANITIZE_MEMORY {
+        assert!(matches!(res, Err(CompileError::Jit(_))));
+    }
+}
+
+#[test]
+#[serial]
+pub fn test100() {
+    // Test writing LLVM IR to a given path.
+    let path = std::env::temp_dir().join("fixlang_test100.ll");
+    let _ = std::fs::remove_file(&path);
+    let options = CompileOptions {
+        ir_dump_path: Some(path.clone()),
+        ..Default::default()
+    };
+    let res = run_source_with_options(r"add 1 2", OptimizationLevel::Default, &options).unwrap();
+    assert_eq!(res, 3);
+    let ir = std::fs::read_to_string(&path).unwrap();
+    assert!(ir.contains("define i64 @main()"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[serial]
+pub fn test101() {
+    // Test isUnique.
+    let cases = [
+        (r"if isUnique (newArray 3 0) then 1 else 0", 1),
+        (
+            r"let a = newArray 3 0; let u = isUnique a; if u then 1 else 0",
+            1,
+        ),
+        (
+            r"let a = newArray 3 0; if isUnique a then 0 else lenArray a",
+            3,
+        ),
+        (
+            r"let a = newArray 3 0; let p = pair a 5; if isUnique a then 0 else snd p",
+            5,
+        ),
+    ];
+    for (source, answer) in cases {
+        test_run_source(source, answer, OptimizationLevel::Default);
+    }
+}
+
+#[test]
+pub fn test102() {
+    // Test counters of variables in scope at the boundary.
+    let context = Context::create();
+    let ptr = ptr_to_object_type(&context).const_null();
+    let mut scope = Scope::default();
+    scope.push("x", &ptr);
+    let names: HashSet<String> = vec![String::from("x")].into_iter().collect();
+    scope.modify_used_later(&names, 2);
+    scope.modify_used_later(&names, -2);
+    assert_eq!(scope.get("x").used_later, 0);
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        scope.modify_used_later(&names, -1);
+    }));
+    if cfg!(debug_assertions) {
+        assert!(res.is_err());
+    } else {
+        assert_eq!(scope.get("x").used_later, 0);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test103() {
+    // Test copyArray and deepCopyArray.
+    let cases = [
+        (
+            r"let a = newArray 3 7; let b = copyArray a; readArray b 2",
+            7,
+        ),
+        (
+            r"let a = newArray 2 (newArray 1 5);
+            let b = copyArray a;
+            let b = writeArray b 0 (newArray 1 7);
+            add (readArray (readArray a 0) 0) (readArray (readArray b 0) 0)",
+            12,
+        ),
+        (
+            r"let a = newArray 2 (newArray 1 5);
+            let b = copyArray a;
+            let e = readArray a 0;
+            if isUnique e then 0 else readArray (readArray b 1) 0",
+            5,
+        ),
+        (
+            r"let a = newArray 3 1;
+            let b = deepCopyArray a (\x -> add x 10);
+            add (readArray a 0) (readArray b 2)",
+            12,
+        ),
+        (
+            r"let a = newArray 2 (newArray 1 5);
+            let b = deepCopyArray a copyArray;
+            let b = writeArray b 0 (writeArray (readArray b 0) 0 8);
+            add (readArray (readArray a 0) 0) (readArray (readArray b 0) 0)",
+            13,
+        ),
+    ];
+    for (source, answer) in cases {
+        test_run_source(source, answer, OptimizationLevel::Default);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test104() {
+    // Test dump of the object table of the sanitizer.
+    if !SANITIZE_MEMORY {
+        return;
+    }
+    let (res, json) = run_source_with_object_dump(
+        r"let a = newArray 3 1; readArray a 0",
+        OptimizationLevel::Default,
+    )
+    .unwrap();
+    assert_eq!(res, 1);
+    assert_eq!(json, "[]");
+
+    let options = CompileOptions {
+        extra_builtins: vec![(String::from("leakId"), leak_id())],
+        ..Default::default()
+    };
+    let (id, json) = run_ast_with_object_dump(
+        parse_source(r"leakId (newArray 2 7)"),
+        OptimizationLevel::Default,
+        &options,
+    )
+    .unwrap();
+    assert!(json.starts_with('[') && json.ends_with(']'));
+    let entry = format!(
+        "{{\"id\":{},\"name\":\"newArray size value\",\"refcnt\":1,\"kind\":\"Array\"}}",
+        id
+    );
+    assert!(json.contains(&entry), "{}", json);
+
+    // The table is cleared after the dump, so that later leak checks pass.
+    assert_eq!(
+        run_source(r"readArray (newArray 2 7) 1", OptimizationLevel::Default).unwrap(),
+        7
+    );
+}
+
+#[test]
+#[serial]
+pub fn test105() {
+    // Test fromOption and mapOption.
+    let cases = [
+        (r"fromOption 0 (some 5)", 5),
+        (r"fromOption 3 none", 3),
+        (
+            r"let arr = newArray 100 0;
+            let arr = writeArray arr 50 42;
+            fromOption 0 (findIndexArray arr (eq 42))",
+            50,
+        ),
+        (
+            r"let arr = newArray 100 0;
+            fromOption 0 (findIndexArray arr (eq 42))",
+            0,
+        ),
+        (
+            r"readArray (fromOption (newArray 1 7) (some (newArray 2 8))) 1",
+            8,
+        ),
+        (r"fromOption 0 (mapOption (add 1) (some 5))", 6),
+        (r"fromOption 0 (mapOption (add 1) none)", 0),
+        (r"if isSome (mapOption (add 1) none) then 1 else 2", 2),
+        (
+            r"let arr = newArray 3 4; fromOption 0 (mapOption lenArray (some arr))",
+            3,
+        ),
+    ];
+    for (source, answer) in cases {
+        test_run_source(source, answer, OptimizationLevel::Default);
+    }
+}
+
+#[test]
+pub fn test106() {
+    // Test that accesses to arrays are bounds-checked: each of them branches to abort with a message on violation.
+    let sources = [
+        r"let arr = newArray 100 0; readArray arr 100",
+        r"let arr = newArray 100 0; readArray (writeArray arr 100 1) 0",
+        r"let arr = newArray 100 0; readArray (writeArray! arr 100 1) 0",
+    ];
+    for source in sources {
+        let context = Context::create();
+        let module =
+            build_module(&context, parse_source(source), &CompileOptions::default()).unwrap();
+        verify_module(&module);
+        let ir = module.print_to_string().to_string();
+        assert!(ir.contains("Index out of range!"), "{}", source);
+        assert!(ir.contains("call void @abort()"), "{}", source);
+    }
+
+    // Run an out-of-range access in a child process running only this test, and check that it is aborted.
+    if std::env::var("FIX_TEST106_CHILD").is_ok() {
+        run_source(sources[0], OptimizationLevel::Default).unwrap();
+        panic!("an out-of-range access is not aborted");
+    }
+    let status = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "tests::test106", "--test-threads=1"])
+        .env("FIX_TEST106_CHILD", "1")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    // The child is killed by SIGABRT (6).
+    assert_eq!(status.signal(), Some(6));
+}
+
+#[test]
+#[serial]
+pub fn test107() {
+    // Test that the dtor of an array releases its elements: after dropping an array of pairs,
+    // as many objects are alive as after dropping an array of ints.
+    let ints = run_source(
+        r"let a = newArray 3 0 in let n = lenArray a in liveObjects n",
+        OptimizationLevel::Default,
+    )
+    .unwrap();
+    let pairs = run_source(
+        r"let a = newArray 3 (pair 1 (newArray 2 0)) in let n = lenArray a in liveObjects n",
+        OptimizationLevel::Default,
+    )
+    .unwrap();
+    let written = run_source(
+        r"let a = newArray 3 (pair 1 2) in
+        let a = writeArray a 0 (pair 3 4) in
+        let n = lenArray a in liveObjects n",
+        OptimizationLevel::Default,
+    )
+    .unwrap();
+    assert_eq!(pairs, ints);
+    assert_eq!(written, ints);
+}
+
+#[test]
+#[serial]
+pub fn test108() {
+    // Test strict mode, where lints are errors.
+    let strict = CompileOptions {
+        strict: true,
+        ..Default::default()
+    };
+
+    // An unused let binding is a warning by default, and an error in strict mode.
+    let source = r"let x = 3 in 5";
+    assert_eq!(run_source(source, OptimizationLevel::Default).unwrap(), 5);
+    let res = run_source_with_options(source, OptimizationLevel::Default, &strict);
+    assert!(matches!(res, Err(CompileError::Lint(_))));
+
+    // Other lints.
+    let names = vec![String::from("add")];
+    let cases = [
+        (r"let x = 3 in add x 1", 0),
+        (r"let y = 3 in 5", 1),
+        (r"let add = 3 in add", 1),
+        (r"(\add -> add) 3", 1),
+        (r"add (5 3) 1", 1),
+        (r"\x -> x", 1),
+        (r"let x = 3 in if eq x 3 then true else false", 0),
+        (r"2.5", 1),
+    ];
+    for (source, count) in cases {
+        let warnings = lint_program(&parse_source(source), &names);
+        assert_eq!(warnings.len(), count, "{}: {:?}", source, warnings);
+    }
+    let res = run_source_with_options(r"let x = 3 in add x 1", OptimizationLevel::Default, &strict);
+    assert_eq!(res.unwrap(), 4);
+}
+
+#[test]
+pub fn test109() {
+    // Test type inference.
+    let mut env = TypeEnv::default();
+    for name in ["add", "eq", "pair", "fst", "newArray", "readArray"] {
+        env.insert(name, generalize(&env, &builtin_type(name).unwrap()));
+    }
+    let infer_source = |source: &str| infer(&parse_source(source), &env);
+    let cases = [
+        (r"(\x -> x) 5", int_lit_ty()),
+        (r"\x -> add x 1", lambda_ty(int_lit_ty(), int_lit_ty())),
+        (r"eq 1 2", bool_lit_ty()),
+        (
+            r"let id = \x -> x in if id true then id 1 else 2",
+            int_lit_ty(),
+        ),
+        (r"fst (pair (newArray 2 true) 3)", array_ty(bool_lit_ty())),
+        (r"let a = newArray 2 2.5; readArray a 0", float_lit_ty()),
+    ];
+    for (source, ty) in cases {
+        assert_eq!(infer_source(source).unwrap(), ty, "{}", source);
+    }
+    let ty = infer_source(r"\x -> x").unwrap();
+    assert!(matches!(&*ty, Type::FunTy(src, dst) if src == dst));
+
+    // Ill-typed programs.
+    for source in [
+        r"add true 1",
+        r"if 3 then 1 else 2",
+        r"if true then 1 else false",
+        r"\f -> f f",
+    ] {
+        assert!(infer_source(source).is_err(), "{}", source);
+    }
+}
+
+#[test]
+pub fn test110() {
+    // Test that ill-typed programs are rejected before code generation.
+    let res = run_source(r"add true 1", OptimizationLevel::Default);
+    assert!(matches!(res, Err(CompileError::Type(_))));
+    let res = run_source(r"if 3 then 1 else 2", OptimizationLevel::Default);
+    assert!(matches!(res, Err(CompileError::Type(_))));
+    let res = run_source(r"add unknown 1", OptimizationLevel::Default);
+    assert!(matches!(res, Err(CompileError::Type(_))));
+    // The result of a program must be a scalar, and the error tells the type of the program.
+    let cases = [
+        (
+            r"add",
+            lambda_ty(int_lit_ty(), lambda_ty(int_lit_ty(), int_lit_ty())),
+        ),
+        (r"newArray 1 0", array_ty(int_lit_ty())),
+        (r"pair 1 true", pair_ty(int_lit_ty(), bool_lit_ty())),
+    ];
+    for (source, found_type) in cases {
+        let res = run_source(source, OptimizationLevel::Default);
+        assert_eq!(
+            res,
+            Err(CompileError::NonRunnable { found_type }),
+            "{}",
+            source
+        );
+    }
+    assert_eq!(
+        run_source(r"add", OptimizationLevel::Default)
+            .unwrap_err()
+            .to_string(),
+        "Type error: the program can't be run since it has type (Int) -> ((Int) -> (Int))"
+    );
+    // The result of a program must be an array of ints if it is copied into a buffer.
+    let cases = [
+        (r"1", int_lit_ty()),
+        (r"newArray 1 true", array_ty(bool_lit_ty())),
+    ];
+    for (source, found_type) in cases {
+        let res = run_ast_with_out_array(
+            parse_source(source),
+            OptimizationLevel::Default,
+            &CompileOptions::default(),
+            &mut [0; 1],
+        );
+        assert_eq!(
+            res,
+            Err(CompileError::NonRunnable { found_type }),
+            "{}",
+            source
+        );
+    }
+    // The thunk of newArrayLazy is given an Int (which is a dummy), so it can't be used as another type.
+    let res = run_source(
+        r"readArray (newArrayLazy 1 (\u -> readArray u 0)) 0",
+        OptimizationLevel::Default,
+    );
+    assert!(matches!(res, Err(CompileError::Type(_))));
+}
+
+#[test]
+pub fn test111() {
+    // Test source spans of parse errors and parsed expressions.
+    let source = r"let x = in x";
+    let err = try_parse_source(source).unwrap_err();
+    assert_eq!(err.span.start, 8);
+    assert_eq!(err.span.render(source), "1:9\nlet x = in x\n        ^");
+
+    let source = "let x = 1;\nlet y = in y";
+    let err = try_parse_source(source).unwrap_err();
+    assert_eq!(err.span.start, 19);
+    assert_eq!(err.span.line_col(source), (2, 9));
+
+    let source = r"add 1 (add 2 3)";
+    let ast = parse_source(source);
+    assert_eq!(ast.source, Some(Span { start: 0, end: 15 }));
+    match &*ast.expr {
+        Expr::App(func, arg) => {
+            assert_eq!(func.source, Some(Span { start: 0, end: 5 }));
+            assert_eq!(arg.source, Some(Span { start: 6, end: 15 }));
+            assert_eq!(
+                arg.source.unwrap().render(source),
+                "1:7\nadd 1 (add 2 3)\n      ^^^^^^^^^"
+            );
+        }
+        _ => panic!("add 1 (add 2 3) is not parsed as an application"),
+    }
+}
+
+#[test]
+#[serial]
+pub fn test112() {
+    // Test array literals.
+    let cases = [
+        (r"readArray [10, 20, 30] 1", 20),
+        (r"lenArray []", 0),
+        (r"lenArray [ 1 ,2,3 ]", 3),
+        (r"readArray [add 1 1, 2] 0", 2),
+        (
+            r"let a = [[1, 2], [3]]; add (lenArray (readArray a 0)) (readArray (readArray a 1) 0)",
+            5,
+        ),
+        (r"let x = 5; readArray [x, add x 1, add x 2] 2", 7),
+    ];
+    for (source, answer) in cases {
+        test_run_source(source, answer, OptimizationLevel::Default);
+    }
+    assert_eq!(
+        parse_source(r"[1, 2]"),
+        app(
+            app(
+                app(
+                    var("writeArray!"),
+                    app(app(var("newArray"), int(2)), int(1))
+                ),
+                int(1)
+            ),
+            int(2)
+        )
+    );
+}
+
+#[test]
+#[serial]
+pub fn test113() {
+    // Test that a condition variable also used in branches is released exactly once (checked by the leak check).
+    let cases = [
+        (r"let b = true in if b then (if b then 1 else 2) else 3", 1),
+        (r"let b = false in if b then (if b then 1 else 2) else 3", 3),
+        (r"let b = false in if b then 1 else (if b then 2 else 4)", 4),
+        (
+            r"let b = eq 1 1 in let c = b in if b then (if c then 5 else 6) else (if b then 7 else 8)",
+            5,
+        ),
+        (
+            r"let f = \b -> if b then (if b then 1 else 2) else 3 in add (f true) (f false)",
+            4,
+        ),
+    ];
+    for (source, answer) in cases {
+        test_run_source(source, answer, OptimizationLevel::Default);
+    }
+}
+
+#[test]
+#[serial]
+pub fn test114() {
+    // Test lambdas of multiple arguments.
+    test_run_source(r"(\x y -> add x y) 3 4", 7, OptimizationLevel::Default);
+    let n = 10000;
+    let source = format!(
+        r"
+                let g = fix \f x -> if eq x 0 then 0 else add x (f (add x -1));
+                g {}
+        ",
+        n
+    );
+    test_run_source(
+        source.as_str(),
+        (n * (n + 1)) / 2,
+        OptimizationLevel::Default,
+    );
+    assert_eq!(
+        parse_source(r"\x y  z -> add x y"),
+        parse_source(r"\x -> \y -> \z -> add x y")
+    );
+}
+
+#[test]
+pub fn test115() {
+    // Test that the control block has an object id and allocations are reported only if the sanitizer is enabled.
+    let context = Context::create();
+    let num_fields = control_block_type(&context).count_fields();
+    assert_eq!(num_fields, if SANITIZE_MEMORY { 3 } else { 2 });
+    let module = build_module(
+        &context,
+        parse_source(r"let p = pair 1 2 in fst p"),
+        &CompileOptions::default(),
+    )
+    .unwrap();
+    verify_module(&module);
+    let ir = module.print_to_string().to_string();
+    assert_eq!(ir.contains("call i64 @report_malloc("), SANITIZE_MEMORY);
+}
+
+#[test]
+#[serial]
+pub fn test116() {
+    // Test line and block comments.
+    let source = r"
+            // Program of test12 with comments.
+            let x = 5 in // x is five
+            /* y is
+               minus three */
+            let y = -3 in
+            let z = /* nested /* block */ comment */ 12 in
+            let xy = add x y in // add x and y
+            add xy/**/z
+        ";
+    test_run_source(source, 14, OptimizationLevel::Default);
+
+    let source = "let x = 1;\n/* /* */ x";
+    let err = try_parse_source(source).unwrap_err();
+    assert_eq!(err.span.line_col(source), (2, 1));
+
+    let source = r#"let x = "//"; 1"#;
+    assert!(try_parse_source(source).is_ok());
+}
+
+#[test]
+#[serial]
+pub fn test117() {
+    // Test escapes in string literals and print.
+    test_run_source(r#"lengthStr "hello""#, 5, OptimizationLevel::Default);
+    test_run_source(r#"lengthStr "a\tb\n\"\\""#, 6, OptimizationLevel::Default);
+    test_run_source(
+        r#"print (concatStr "Hello, " "World!\n")"#,
+        14,
+        OptimizationLevel::Default,
+    );
+    assert!(try_parse_source(r#"lengthStr "\q""#).is_err());
+}
+
+#[test]
+#[serial]
+pub fn test118() {
+    // Test returning an array of ints through a buffer given by the caller.
+    let mut out = [0; 4];
+    let ret = run_ast_with_out_array(
+        parse_source(r"[1, 2, 3]"),
+        OptimizationLevel::Default,
+        &CompileOptions::default(),
+        &mut out,
+    )
+    .unwrap();
+    assert_eq!(ret, 3);
+    assert_eq!(out, [1, 2, 3, 0]);
+
+    // Elements beyond the length of the buffer are not copied.
+    let mut out = [0; 2];
+    let ret = run_ast_with_out_array(
+        parse_source(r"newArray 5 7"),
+        OptimizationLevel::Default,
+        &CompileOptions::default(),
+        &mut out,
+    )
+    .unwrap();
+    assert_eq!(ret, 5);
+    assert_eq!(out, [7, 7]);
+}
+
+#[test]
+#[serial]
+pub fn test119() {
+    // Test pipeline operator.
+    test_run_source(r"5 |> add 1 |> mul 2", 12, OptimizationLevel::Default);
+    test_run_source(r"mul 2 (add 1 5)", 12, OptimizationLevel::Default);
+    test_run_source(
+        r"let f = \x -> x |> add 3; 1 |> f |> f",
+        7,
+        OptimizationLevel::Default,
+    );
+    assert_eq!(parse_source(r"x |> f y |> g"), parse_source(r"g (f y x)"));
+}
+
+#[test]
+#[serial]
+pub fn test120() {
+    // Test neg and negative literals.
+    test_run_source(r"neg 7", -7, OptimizationLevel::Default);
+    test_run_source(r"neg (neg 4)", 4, OptimizationLevel::Default);
+    test_run_source(r"let x = 5; neg x", -5, OptimizationLevel::Default);
+    test_run_source(r"add 3 -2", 1, OptimizationLevel::Default);
+    assert_eq!(parse_source(r"add 3 -2"), parse_source(r"add 3 (-2)"));
+}
+
+#[test]
+pub fn test121() {
+    // Test compiling a program into a native object file.
+    let path = std::env::temp_dir().join(format!("fixlang_test121_{}.o", std::process::id()));
+    compile_to_object(r"add 2 3", &path, OptimizationLevel::Default).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(!bytes.is_empty());
+    let is_elf = bytes.starts_with(b"\x7fELF");
+    let is_mach_o = bytes.starts_with(&[0xcf, 0xfa, 0xed, 0xfe]);
+    let is_coff = bytes.starts_with(&[0x64, 0x86]);
+    assert!(is_elf || is_mach_o || is_coff);
+
+    // Runtime functions and external functions which the program doesn't use are not carried by the object.
+    let contains = |name: &[u8]| bytes.windows(name.len()).any(|w| w == name);
+    for name in [
+        &b"retain_weak_cell"[..],
+        b"release_weak_cell",
+        b"fix_getenv_int",
+        b"getenv",
+        b"strtoll",
+        b"getline",
+    ] {
+        assert!(!contains(name), "{}", String::from_utf8_lossy(name));
+    }
+    assert!(contains(b"main"));
+
+    // Errors of the source are reported without writing the file.
+    let err = compile_to_object(r"let x = in x", &path, OptimizationLevel::Default);
+    assert!(matches!(err, Err(CompileError::Parse(_))));
+    assert!(!path.exists());
+}
+
+#[test]
+#[serial]
+pub fn test122() {
+    // Test that fields of an object type with reordered layout are accessed by logical indices.
+    // Obj = [ControlBlock, Int, SubObject, Int] laid out as [ControlBlock, SubObject, Int, Int].
+    fn obj_type() -> ObjectType {
+        ObjectType {
+            field_types: vec![
+                ObjectFieldType::ControlBlock,
+                ObjectFieldType::Int,
+                ObjectFieldType::SubObject,
+                ObjectFieldType::Int,
+            ],
+            finalizer: None,
+            field_order: None,
+        }
+        .with_field_order(vec![0, 3, 1, 2])
+    }
+    let context = Context::create();
+    let target_data = TargetData::create("e-m:e-i64:64-f80:128-n8:16:32:64-S128");
+    let layout = obj_type().layout(&context, &target_data);
+    let offsets: Vec<u64> = layout.fields.iter().map(|f| f.offset).collect();
+    let cb_end = target_data.get_abi_size(&control_block_type(&context));
+    assert_eq!(
+        offsets[offsets.len() - 3..],
+        [cb_end + 16, cb_end, cb_end + 8]
+    );
+
+    // Build an object from x and 10, 20, and read its fields back after dtor releases x.
+    let generator: Arc<LiteralGenerator> = Arc::new(|gc| {
+        let obj_type = obj_type();
+        let x = gc.scope_get("x").ptr;
+        let obj = obj_type.create_obj(gc, "reordered x");
+        let ten = gc.context.i64_type().const_int(10, false);
+        let twenty = gc.context.i64_type().const_int(20, false);
+        obj_type.store_field(gc, obj, 1, ten);
+        obj_type.store_field(gc, obj, 2, x);
+        obj_type.store_field(gc, obj, 3, twenty);
+        let first = obj_type.load_field(gc, obj, 1).into_int_value();
+        let sub = obj_type.load_field(gc, obj, 2).into_pointer_value();
+        let sub = gc
+            .load_obj_field(sub, int_type(gc.context), 1)
+            .into_int_value();
+        let third = obj_type.load_field(gc, obj, 3).into_int_value();
+        gc.release(obj);
+        let hundred = gc.context.i64_type().const_int(100, false);
+        let value = gc.builder().build_int_mul(sub, hundred, "value");
+        let value = gc.builder().build_int_add(value, third, "value");
+        let value = gc.builder().build_int_mul(value, hundred, "value");
+        let value = gc.builder().build_int_add(value, first, "value");
+        let ptr_to_int_obj = ObjectType::int_obj_type().create_obj(gc, "reordered x");
+        gc.store_obj_field(ptr_to_int_obj, int_type(gc.context), 1, value);
+        ptr_to_int_obj
+    });
+    let reordered = lam(
+        var_var("x"),
+        lit(
+            generator,
+            vec![String::from("x")],
+            String::from("reordered x"),
+        ),
+    );
+    let options = CompileOptions {
+        extra_builtins: vec![(String::from("reordered"), reordered)],
+        ..Default::default()
+    };
+    let res = run_source_with_options(r"reordered 3", OptimizationLevel::Default, &options);
+    assert_eq!(res, Ok(32010));
+}
+
+#[test]
+#[serial]
+pub fn test123() {
+    // Test REPL: bindings are visible from subsequent lines, and errors don't stop it.
+    let input = "let x = 5\nadd x 3\nlet x = mul x 2\nlet y = in\nadd x 1\n";
+    let mut output: Vec<u8> = vec![];
+    let mut repl = Repl::new(OptimizationLevel::Default, &CompileOptions::default());
+    repl.run(input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "x is defined.");
+    assert_eq!(lines[1], "8");
+    assert_eq!(lines[2], "x is defined.");
+    assert!(lines[3].starts_with("Parse error"));
+    assert_eq!(lines[lines.len() - 1], "11");
+
+    let mut repl = Repl::new(OptimizationLevel::Default, &CompileOptions::default());
+    assert_eq!(
+        repl.eval_line("let f = \\a -> add a 1"),
+        Ok(ReplOutput::Bound(String::from("f")))
+    );
+    assert_eq!(repl.eval_line("f 41"), Ok(ReplOutput::Value(42)));
+}
+
+#[test]
+pub fn test124() {
+    // Test dump of free variables: let is non-recursive, so x bound by "let x = f x" is free in the whole.
+    let ast = calculate_free_vars(parse_source(r"let x = f x in g x"));
+    assert!(ast.free_vars.contains("x"));
+    let expected = [
+        "let x=(f) (x) in ((g) (x)) : {f, g, x}",
+        "  (f) (x) : {f, x}",
+        "    f : {f}",
+        "    x : {x}",
+        "  (g) (x) : {g, x}",
+        "    g : {g}",
+        "    x : {x}",
+    ];
+    assert_eq!(dump_free_vars(&ast), expected.join("\n") + "\n");
+
+    let ast = calculate_free_vars(parse_source(r"\y -> add y z"));
+    assert_eq!(
+        dump_free_vars(&ast).lines().next().unwrap(),
+        r"\y->((add) (y)) (z) : {add, z}"
+    );
+
+    // Each application of a spine has its free variables.
+    let ast = calculate_free_vars(parse_source(r"f a b"));
+    let expected = [
+        "((f) (a)) (b) : {a, b, f}",
+        "  (f) (a) : {a, f}",
+        "    f : {f}",
+        "    a : {a}",
+        "  b : {b}",
+    ];
+    assert_eq!(dump_free_vars(&ast), expected.join("\n") + "\n");
+}
+
+#[test]
+#[serial]
+pub fn test125() {
+    // Test weak references: an array holding a closure which refers to the array weakly is fully released.
+    let source = r"
+        let a = newArray 1 (\x -> x);
+        let w = makeWeak a;
+        let a = writeArray! a 0 (\x -> if isSome (derefWeak w) then add x 1 else x);
+        let y = (readArray a 0) 41;
+        add y (lenArray a)
+    ";
+    test_run_source(source, 43, OptimizationLevel::Default);
+
+    // The closure sees the array expired after the array is released.
+    let source = r"
+        let a = newArray 1 (\x -> x);
+        let w = makeWeak a;
+        let a = writeArray! a 0 (\x -> if isSome (derefWeak w) then add x 1 else x);
+        let f = readArray a 0;
+        f 41
+    ";
+    test_run_source(source, 41, OptimizationLevel::Default);
+
+    // A weak reference made from the last strong reference is expired at once.
+    test_run_source(
+        r"if isSome (derefWeak (makeWeak (newArray 1 0))) then 1 else 0",
+        0,
+        OptimizationLevel::Default,
+    );
+    test_run_source(
+        r"let a = newArray 2 0; let w = makeWeak a; let n = fromOption 0 (mapOption lenArray (derefWeak w)); add n (lenArray a)",
+        4,
+        OptimizationLevel::Default,
+    );
+
+    // Weak references to an object share its weak cell, and the object outlives them.
+    let source = r"
+        let a = newArray 3 0;
+        let w0 = makeWeak a;
+        let w1 = makeWeak a;
+        let n0 = fromOption 0 (mapOption lenArray (derefWeak w0));
+        let n1 = fromOption 0 (mapOption lenArray (derefWeak w1));
+        add (add n0 n1) (lenArray a)
+    ";
+    test_run_source(source, 9, OptimizationLevel::Default);
+
+    // Release frees an object directly, and only an object having a weak cell is destructed through the cell.
+    let context = Context::create();
+    let module = build_module(
+        &context,
+        parse_source(r"lenArray (newArray 3 0)"),
+        &CompileOptions::default(),
+    )
+    .unwrap();
+    let release_ir = module
+        .get_function("release_obj")
+        .unwrap()
+        .print_to_string()
+        .to_string();
+    assert!(release_ir.contains("call void @dtor_weak_target("));
+    assert!(!release_ir.contains("weak_cell("));
+    assert!(release_ir.contains("call void @free("));
+}
+
+#[test]
+pub fn test126() {
+    // Test normalization of LLVM IR.
+    let ir0 = r#"; ModuleID = 'main'
+source_filename = "main"
+target triple = "x86_64-unknown-linux-gnu"
+
+declare void @abort()
+
+declare i32 @printf(i8*, ...)
+
+define i64 @f() #0 {
+entry:
+  ret i64 1
+}
+
+define i64 @main() {
+entry:
+  %0 = call i64 @f()
+  ret i64 %0
+}
+
+attributes #0 = { nounwind readnone }
+
+!llvm.module.flags = !{!0}
+!0 = !{i32 1, !"wchar_size", i32 4}
+"#;
+    let ir1 = r#"; ModuleID = 'other'
+source_filename = "other"
+
+declare i32 @printf(i8*, ...)
+
+declare void @abort()
+
+define i64 @main() {
+entry:
+  %0 = call i64 @f()
+  ret i64 %0
+}
+
+define i64 @f() #0 {
+entry:
+  ret i64 1
+}
+
+attributes #0 = { readnone nounwind }
+"#;
+    assert_ne!(ir0, ir1);
+    assert_eq!(normalize_ir(ir0), normalize_ir(ir1));
+    assert!(normalize_ir(ir0).contains("attributes #0 = { nounwind readnone }"));
+    assert!(!normalize_ir(ir0).contains("target"));
+
+    // Modules of the same program built with different targets are the same after normalization.
+    let context = Context::create();
+    let source = r"let f = \x -> add x 1; f 2";
+    let module0 = build_module(&context, parse_source(source), &CompileOptions::default()).unwrap();
+    let module1 = build_module(&context, parse_source(source), &CompileOptions::default()).unwrap();
+    module1.set_triple(&TargetMachine::get_default_triple());
+    let ir0 = module0.print_to_string().to_string();
+    let ir1 = module1.print_to_string().to_string();
+    assert_ne!(ir0, ir1);
+    assert_eq!(normalize_ir(&ir0), normalize_ir(&ir1));
+}
+
+#[test]
+#[serial]
+pub fn test127() {
+    // Test rotateArray.
+    let cases = [
+        ("1", 0, 2),
+        ("1", 3, 1),
+        ("-1", 0, 4),
+        ("-1", 3, 3),
+        ("5", 0, 2),
+        ("-6", 0, 3),
+    ];
+    for (shift, idx, answer) in cases {
+        let source = format!(
+            r"let a = rotateArray {} [1, 2, 3, 4]; readArray a {}",
+            shift, idx
+        );
+        test_run_source(source.as_str(), answer, OptimizationLevel::Default);
+    }
+    test_run_source(
+        r"let a = rotateArray 3 [7]; readArray a 0",
+        7,
+        OptimizationLevel::Default,
+    );
+    test_run_source(
+        r"lenArray (rotateArray -2 (newArray 0 0))",
+        0,
+        OptimizationLevel::Default,
+    );
+    // Elements are shared with the source array.
+    test_run_source(
+        r"let a = newArray 3 (newArray 2 5); let b = rotateArray 1 a; add (readArray (readArray b 0) 1) (lenArray a)",
+        8,
+        OptimizationLevel::Default,
+    );
+}
+
+#[test]
+pub fn test128() {
+    // Test that objects of the same layout share a dtor: adding closures of the same number of captures adds no dtor.
+    fn count_dtors(source: &str) -> usize {
+        let context = Context::create();
+        let module =
+            build_module(&context, parse_source(source), &CompileOptions::default()).unwrap();
+        verify_module(&module);
+        let count = module
+            .get_functions()
+            .filter(|func| func.get_name().to_str().unwrap().starts_with("dtor"))
+            .count();
+        count
+    }
+    let one = count_dtors(r"let a = 1; let f = \x -> add x a; f 2");
+    let many = count_dtors(
+        r"
+        let a = 1;
+        let f = \x -> add x a;
+        let g = \x -> mul x a;
+        let h = \x -> sub x a;
+        let k = \x -> add a x;
+        k (h (g (f 2)))
+        ",
+    );
+    assert_eq!(one, many);
+}
+
+#[test]
+pub fn test129() {
+    // Test calculation of free variables of deeply nested expressions.
+    // Dropping a deeply nested expression is recursive, so it is done in a thread with a large stack.
+    std::thread::Builder::new()
+        .stack_size(256 * 1024 * 1024)
+        .spawn(|| {
+            let n = 5000;
+            // let x0 = y in let x1 = add x0 1 in ... in x{n}
+            let mut expr = var(&format!("x{}", n));
+            for i in (1..=n).rev() {
+                let bound = app(app(var("add"), var(&format!("x{}", i - 1))), int(1));
+                expr = let_in(var_var(&format!("x{}", i)), bound, expr);
+            }
+            let expr = let_in(var_var("x0"), var("y"), expr);
+            // \z -> if z then (the nesting above) else w
+            let expr = lam(var_var("z"), conditional(var("z"), expr, var("w")));
+            let start = Instant::now();
+            let expr = calculate_free_vars(expr);
+            assert!(start.elapsed() < Duration::from_secs(5));
+            let mut free_vars: Vec<&String> = expr.free_vars.iter().collect();
+            free_vars.sort();
+            assert_eq!(free_vars, ["add", "w", "y"]);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    // The result doesn't change from the documented semantics of let.
+    let ast = calculate_free_vars(parse_source(r"let x = f x in g x"));
+    let mut free_vars: Vec<&String> = ast.free_vars.iter().collect();
+    free_vars.sort();
+    assert_eq!(free_vars, ["f", "g", "x"]);
+}
+
+#[test]
+#[serial]
+pub fn test130() {
+    // Test release of variables around if: the leak check ensures that each variable is released exactly once on both paths.
+    // The variable is used only in the then block.
+    let source = r"
+        let a = newArray 3 1;
+        let f = \c -> if c then lenArray a else 0;
+        add (f true) (f false)
+    ";
+    test_run_source(source, 3, OptimizationLevel::Default);
+    for c in ["true", "false"] {
+        // Used only in the then block.
+        let source = format!("let a = newArray 3 1; if {} then lenArray a else 0", c);
+        test_run_source(
+            &source,
+            if c == "true" { 3 } else { 0 },
+            OptimizationLevel::Default,
+        );
+        // Used only in the else block.
+        let source = format!("let a = newArray 3 1; if {} then 0 else lenArray a", c);
+        test_run_source(
+            &source,
+            if c == "true" { 0 } else { 3 },
+            OptimizationLevel::Default,
+        );
+        // Used in both blocks.
+        let source = format!(
+            "let a = newArray 3 1; if {} then lenArray a else add (lenArray a) 1",
+            c
+        );
+        test_run_source(
+            &source,
+            if c == "true" { 3 } else { 4 },
+            OptimizationLevel::Default,
+        );
+        // Used in neither block, but in the condition.
+        let source = format!(
+            "let a = newArray 3 1; if eq (lenArray a) {} then 1 else 0",
+            if c == "true" { 3 } else { 2 }
+        );
+        test_run_source(
+            &source,
+            if c == "true" { 1 } else { 0 },
+            OptimizationLevel::Default,
+        );
+        // Used in neither block nor the condition, but after the if.
+        let source = format!(
+            "let a = newArray 3 1; let x = if {} then 1 else 2; add x (lenArray a)",
+            c
+        );
+        test_run_source(
+            &source,
+            if c == "true" { 4 } else { 5 },
+            OptimizationLevel::Default,
+        );
+    }
+}
+
+#[test]
+#[serial]
+pub fn test131() {
+    // Test closures capturing many variables, with and without packing captures into an array.
+    let n = 300;
+    let mut source = String::new();
+    for i in 0..n {
+        source += &format!("let v{} = {};\n", i, i);
+    }
+    // The body depends on the order of captures: ((x * 3 + v0) * 3 + v1) * 3 + ...
+    let mut body = String::from("x");
+    for i in 0..n {
+        body = format!("add (mul {} 3) v{}", body, i);
+    }
+    source += &format!("let f = \\x -> {};\nadd (f 1) (f 2)", body);
+    let eval = |x: i64| (0..n).fold(x, |acc, i| acc.wrapping_mul(3).wrapping_add(i));
+    let answer = eval(1).wrapping_add(eval(2));
+    for max_closure_captures in [None, Some(0), Some(8), Some(n as usize)] {
+        let options = CompileOptions {
+            max_closure_captures,
+            ..Default::default()
+        };
+        let res = run_source_with_options(&source, OptimizationLevel::Default, &options);
+        assert_eq!(res, Ok(answer));
+    }
+}
+
+#[test]
+#[serial]
+pub fn test132() {
+    // Test mod and pow.
+    test_run_source(r"mod 17 5", 2, OptimizationLevel::Default);
+    // The remainder has the sign of the dividend.
+    test_run_source(r"mod -17 5", -2, OptimizationLevel::Default);
+    test_run_source(r"mod 17 -5", 2, OptimizationLevel::Default);
+    test_run_source(
+        r"mod -9223372036854775807 -1",
+        0,
+        OptimizationLevel::Default,
+    );
+    // The minimum modulo -1 aborts at runtime as div does, so it is not folded as a constant.
+    let context = Context::create();
+    let module = build_module(
+        &context,
+        parse_source(r"let x = -9223372036854775808; mod x -1"),
+        &CompileOptions::default(),
+    )
+    .unwrap();
+    verify_module(&module);
+    let ir = module.print_to_string().to_string();
+    assert!(ir.contains("Overflow in division!"));
+    assert!(try_parse_source(r"const x = mod -9223372036854775808 -1; x").is_err());
+    assert_eq!(
+        run_source(r"const x = mod -17 5; x", OptimizationLevel::Default),
+        run_source(r"mod -17 5", OptimizationLevel::Default)
+    );
+    test_run_source(r"pow 2 10", 1024, OptimizationLevel::Default);
+    test_run_source(r"pow 7 0", 1, OptimizationLevel::Default);
+    test_run_source(r"pow -3 3", -27, OptimizationLevel::Default);
+    test_run_source(r"pow 2 -1", 1, OptimizationLevel::Default);
+    // The loop runs only log2(exponent) times, and wraps around on overflow.
+    test_run_source(r"pow 1 9000000000000000000", 1, OptimizationLevel::Default);
+    test_run_source(
+        r"pow 3 41",
+        3i64.wrapping_pow(41),
+        OptimizationLevel::Default,
+    );
+    // The leak check ensures that no accumulator is left in the loop, also when pow is called repeatedly.
+    test_run_source(
+        r"
+        let sum = fix \f -> \n -> if eq n 0 then 0 else add (pow n 2) (f (add n -1));
+        sum 10
+        ",
+        385,
+        OptimizationLevel::Default,
+    );
+}
+
+#[test]
+#[serial]
+pub fn test133() {
+    // Test const declarations, which are folded into int literals at compile time.
+    let source = r"
+        const answer = mul 6 7;
+        add answer answer
+    ";
+    test_run_source(source, 84, OptimizationLevel::Default);
+    assert_eq!(parse_source(source), parse_source(r"add 42 42"));
+
+    // The folded program allocates fewer objects than the one binding the value at runtime.
+    let count_mallocs = |source: &str| {
+        let context = Context::create();
+        let module =
+            build_module(&context, parse_source(source), &CompileOptions::default()).unwrap();
+        verify_module(&module);
+        let ir = module.print_to_string().to_string();
+        ir.matches("call i64 @report_malloc(").count()
+    };
+    if SANITIZE_MEMORY {
+        let folded = count_mallocs(source);
+        let unfolded = count_mallocs(r"let answer = mul 6 7; add answer answer");
+        assert!(folded < unfolded);
+    }
+
+    // Constants may refer to preceding ones, and are shadowed by local bindings.
+    let source = r"
+        const n = 10;
+        const m = pow 2 n;
+        let f = \n -> add n m;
+        sub (f 1) n
+    ";
+    test_run_source(source, 1015, OptimizationLevel::Default);
+
+    // A constant must be a closed computation of ints.
+    let err = try_parse_source(r"const x = y; x").unwrap_err();
+    assert_eq!(err.message, "`y` is not a compile-time constant");
+    assert!(try_parse_source(r"const x = div 1 0; x").is_err());
+
+    // Powers with huge exponents are folded quickly, and agree with the runtime on overflow.
+    test_run_source(
+        r"const x = pow 1 9000000000000000000; x",
+        1,
+        OptimizationLevel::Default,
+    );
+    test_run_source(
+        r"const x = pow 3 41; let y = 41; sub x (pow 3 y)",
+        0,
+        OptimizationLevel::Default,
+    );
+
+    // Source spans are kept in the program with constants inlined.
+    let source = r"const k = 2; add k (mul 3 k)";
+    let ast = parse_source(source);
+    assert_eq!(ast.source, Some(Span { start: 13, end: 28 }));
+    match &*ast.expr {
+        Expr::App(func, arg) => {
+            assert_eq!(func.source, Some(Span { start: 13, end: 18 }));
+            assert_eq!(arg.source, Some(Span { start: 19, end: 28 }));
+            match &*func.expr {
+                Expr::App(_, k) => {
+                    assert_eq!(*k, *int(2));
+                    assert_eq!(k.source, Some(Span { start: 17, end: 18 }));
+                }
+                _ => panic!("add k is not an application"),
+            }
+        }
+        _ => panic!("add k (mul 3 k) is not an application"),
+    }
+}
+
+#[test]
+pub fn test134() {
+    // Test that source spans are kept on nodes rebuilt by passes.
+    let source = r"let x = add 1 2 in if eq 1 1 then add 3 (add 4 5) else 6";
+    let ast = parse_source(source);
+    let mut remarks = vec![];
+    let optimized = optimize_ast(ast.clone(), &mut remarks);
+    let with_free_vars = calculate_free_vars(ast);
+    assert_eq!(with_free_vars.source, Some(Span { start: 0, end: 56 }));
+    let val = match &*with_free_vars.expr {
+        Expr::Let(_, bound, val) => {
+            assert_eq!(bound.source, Some(Span { start: 8, end: 15 }));
+            val.clone()
+        }
+        _ => panic!("the let binding is lost"),
+    };
+    // The let binding is eliminated by the optimization.
+    for ast in [val, optimized] {
+        assert_eq!(ast.source, Some(Span { start: 19, end: 56 }));
+        let then = match &*ast.expr {
+            Expr::If(_, then, _) => then.clone(),
+            _ => panic!("if is not kept"),
+        };
+        assert_eq!(then.source, Some(Span { start: 34, end: 49 }));
+        match &*then.expr {
+            Expr::App(func, arg) => {
+                assert_eq!(func.source, Some(Span { start: 34, end: 39 }));
+                assert_eq!(arg.source, Some(Span { start: 40, end: 49 }));
+            }
+            _ => panic!("add 3 (add 4 5) is not an application"),
+        }
+    }
+
+    // A node rebuilt by fusion has the span of the original expression.
+    let source = r"add 1 (iterate 10 (add 1) 0)";
+    let mut remarks = vec![];
+    let ast = fuse_iterate(parse_source(source), &mut remarks);
+    assert_eq!(remarks[0].location, Some(Span { start: 6, end: 28 }));
+    match &*ast.expr {
+        Expr::App(_, arg) => assert_eq!(arg.source, Some(Span { start: 6, end: 28 })),
+        _ => panic!("add 1 (iterate 10 (add 1) 0) is not an application"),
+    }
+}
+
+#[test]
+#[serial]
+#[ignore]
+pub fn test135() {
+    // Benchmark of the lean path against the full path, which reports the overhead of the full path (verification, etc.).
+    // Run by `cargo test test135 -- --ignored --nocapture`.
+    let source = r"add 2 3";
+    let n = 20;
+    let start = std::time::Instant::now();
+    for _ in 0..n {
+        assert_eq!(run_source(source, OptimizationLevel::Default).unwrap(), 5);
+    }
+    let full_time = start.elapsed();
+    let start = std::time::Instant::now();
+    for _ in 0..n {
+        assert_eq!(
+            run_source_lean(source, OptimizationLevel::Default).unwrap(),
+            5
+        );
+    }
+    let lean_time = start.elapsed();
+    eprintln!(
+        "full path: {:?}, lean path: {:?} (per run)",
+        full_time / n,
+        lean_time / n
+    );
+}
+
+#[test]
+#[serial]
+pub fn test136() {
+    // Test that every entry path checks the type of the program before code generation.
+    let source = r"add true 1";
+    assert!(matches!(
+        run_source_lean(source, OptimizationLevel::Default),
+        Err(CompileError::Type(_))
+    ));
+    assert!(matches!(
+        run_sources_batch(&["1", source], OptimizationLevel::Default),
+        Err(CompileError::Type(_))
+    ));
+    assert!(matches!(
+        jit_compile_int_fn(source),
+        Err(CompileError::Type(_))
+    ));
+    let context = Context::create();
+    assert!(matches!(
+        build_module(&context, parse_source(source), &CompileOptions::default()),
+        Err(CompileError::Type(_))
+    ));
+
+    // The result of a batch program must be a scalar.
+    assert!(matches!(
+        run_sources_batch(&["1", r"newArray 1 0"], OptimizationLevel::Default),
+        Err(CompileError::NonRunnable { .. })
+    ));
+
+    // A JIT function must be of type Int -> Int.
+    for source in [r"5", r"\x -> eq x 0", r"\x -> newArray x 0"] {
+        assert!(
+            matches!(
+                jit_compile_int_fn(source),
+                Err(CompileError::NonRunnable { .. })
+            ),
+            "{}",
+            source
+        );
+    }
+    let f = jit_compile_int_fn(r"\x -> x").unwrap();
+    assert_eq!(f(7), 7);
+}
@@ -0,0 +1,458 @@
+// Type inference by Hindley-Milner type system (Algorithm W).
+use super::*;
+
+// Error found by type inference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+    pub message: String,
+}
+
+impl std::fmt::Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+fn type_error<T>(message: String) -> Result<T, TypeError> {
+    Err(TypeError { message })
+}
+
+// Substitution of types for type variables.
+#[derive(Default, Clone, Debug)]
+pub struct Substitution {
+    map: HashMap<String, Arc<Type>>,
+}
+
+impl Substitution {
+    fn single(name: &str, ty: Arc<Type>) -> Substitution {
+        let mut map = HashMap::default();
+        map.insert(String::from(name), ty);
+        Substitution { map }
+    }
+
+    // Apply the substitution to a type. Variables bound by forall are not substituted.
+    pub fn apply(&self, ty: &Arc<Type>) -> Arc<Type> {
+        if self.map.is_empty() {
+            return ty.clone();
+        }
+        Arc::new(self.apply_type(ty))
+    }
+
+    fn apply_type(&self, ty: &Type) -> Type {
+        match ty {
+            Type::TyVar(v) => match self.map.get(v.name()) {
+                Some(ty) => (**ty).clone(),
+                None => ty.clone(),
+            },
+            Type::LitTy(_) => ty.clone(),
+            Type::AppTy(fun, arg) => Type::AppTy(self.apply(fun), self.apply(arg)),
+            Type::TyConApp(con, args) => Type::TyConApp(
+                con.clone(),
+                args.iter().map(|arg| self.apply_type(arg)).collect(),
+            ),
+            Type::FunTy(src, dst) => Type::FunTy(self.apply(src), self.apply(dst)),
+            Type::ForAllTy(v, body) => {
+                let mut inner = self.clone();
+                inner.map.remove(v.name());
+                Type::ForAllTy(v.clone(), inner.apply(body))
+            }
+        }
+    }
+
+    // Compose two substitutions: applying the result is the same as applying self and then other.
+    pub fn compose(&self, other: &Substitution) -> Substitution {
+        let mut map: HashMap<String, Arc<Type>> = self
+            .map
+            .iter()
+            .map(|(name, ty)| (name.clone(), other.apply(ty)))
+            .collect();
+        for (name, ty) in &other.map {
+            map.entry(name.clone()).or_insert_with(|| ty.clone());
+        }
+        Substitution { map }
+    }
+}
+
+// Get names of type variables which are not bound by forall.
+fn free_tyvars(ty: &Type, ret: &mut HashSet<String>) {
+    match ty {
+        Type::TyVar(v) => {
+            ret.insert(v.name().clone());
+        }
+        Type::LitTy(_) => {}
+        Type::AppTy(fun, arg) => {
+            free_tyvars(fun, ret);
+            free_tyvars(arg, ret);
+        }
+        Type::TyConApp(_, args) => {
+            for arg in args {
+                free_tyvars(arg, ret);
+            }
+        }
+        Type::FunTy(src, dst) => {
+            free_tyvars(src, ret);
+            free_tyvars(dst, ret);
+        }
+        Type::ForAllTy(v, body) => {
+            let mut inner = HashSet::default();
+            free_tyvars(body, &mut inner);
+            inner.remove(v.name());
+            ret.extend(inner);
+        }
+    }
+}
+
+// Check if a type variable of a given name appears in a type.
+fn occurs(name: &str, ty: &Type) -> bool {
+    let mut tyvars = HashSet::default();
+    free_tyvars(ty, &mut tyvars);
+    tyvars.contains(name)
+}
+
+// Bind a type variable to a type.
+fn bind(name: &str, ty: &Arc<Type>) -> Result<Substitution, TypeError> {
+    if let Type::TyVar(v) = &**ty {
+        if v.name() == name {
+            return Ok(Substitution::default());
+        }
+    }
+    if occurs(name, ty) {
+        return type_error(format!(
+            "infinite type: {} occurs in {}",
+            name,
+            ty.to_string()
+        ));
+    }
+    Ok(Substitution::single(name, ty.clone()))
+}
+
+// Find the most general substitution which makes two types equal.
+pub fn unify(lhs: &Arc<Type>, rhs: &Arc<Type>) -> Result<Substitution, TypeError> {
+    match (&**lhs, &**rhs) {
+        (Type::TyVar(v), _) => bind(v.name(), rhs),
+        (_, Type::TyVar(v)) => bind(v.name(), lhs),
+        (Type::LitTy(l), Type::LitTy(r)) if l == r => Ok(Substitution::default()),
+        (Type::AppTy(lfun, larg), Type::AppTy(rfun, rarg)) => {
+            unify_pairs(&[(lfun.clone(), rfun.clone()), (larg.clone(), rarg.clone())])
+        }
+        (Type::FunTy(lsrc, ldst), Type::FunTy(rsrc, rdst)) => {
+            unify_pairs(&[(lsrc.clone(), rsrc.clone()), (ldst.clone(), rdst.clone())])
+        }
+        (Type::TyConApp(lcon, largs), Type::TyConApp(rcon, rargs))
+            if lcon == rcon && largs.len() == rargs.len() =>
+        {
+            let pairs: Vec<(Arc<Type>, Arc<Type>)> = largs
+                .iter()
+                .zip(rargs.iter())
+                .map(|(l, r)| (Arc::new(l.clone()), Arc::new(r.clone())))
+                .collect();
+            unify_pairs(&pairs)
+        }
+        _ => type_error(format!(
+            "cannot unify {} with {}",
+            lhs.to_string(),
+            rhs.to_string()
+        )),
+    }
+}
+
+// Unify each pair of types in order, applying substitutions found so far to later pairs.
+fn unify_pairs(pairs: &[(Arc<Type>, Arc<Type>)]) -> Result<Substitution, TypeError> {
+    let mut subst = Substitution::default();
+    for (lhs, rhs) in pairs {
+        let s = unify(&subst.apply(lhs), &subst.apply(rhs))?;
+        subst = subst.compose(&s);
+    }
+    Ok(subst)
+}
+
+// Types (or type schemes quantified by forall) of variables.
+#[derive(Default, Clone)]
+pub struct TypeEnv {
+    vars: HashMap<String, Arc<Type>>,
+}
+
+impl TypeEnv {
+    pub fn insert(&mut self, name: &str, ty: Arc<Type>) {
+        self.vars.insert(String::from(name), ty);
+    }
+
+    fn apply(&self, subst: &Substitution) -> TypeEnv {
+        TypeEnv {
+            vars: self
+                .vars
+                .iter()
+                .map(|(name, ty)| (name.clone(), subst.apply(ty)))
+                .collect(),
+        }
+    }
+
+    fn free_tyvars(&self) -> HashSet<String> {
+        let mut ret = HashSet::default();
+        for ty in self.vars.values() {
+            free_tyvars(ty, &mut ret);
+        }
+        ret
+    }
+}
+
+// Quantify type variables of a type which are not free in the environment.
+pub fn generalize(env: &TypeEnv, ty: &Arc<Type>) -> Arc<Type> {
+    let mut tyvars = HashSet::default();
+    free_tyvars(ty, &mut tyvars);
+    let env_tyvars = env.free_tyvars();
+    let mut tyvars: Vec<String> = tyvars
+        .into_iter()
+        .filter(|name| !env_tyvars.contains(name))
+        .collect();
+    tyvars.sort();
+    tyvars
+        .iter()
+        .rev()
+        .fold(ty.clone(), |ty, name| forall_ty(name, ty))
+}
+
+#[derive(Default)]
+struct TypeInferencer {
+    next_tyvar: usize,
+}
+
+impl TypeInferencer {
+    fn fresh(&mut self) -> Arc<Type> {
+        let ty = tyvar_ty(&format!("t{}", self.next_tyvar));
+        self.next_tyvar += 1;
+        ty
+    }
+
+    // Replace type variables bound by forall with fresh ones.
+    fn instantiate(&mut self, ty: &Arc<Type>) -> Arc<Type> {
+        let mut ty = ty.clone();
+        while let Type::ForAllTy(v, body) = &*ty.clone() {
+            ty = Substitution::single(v.name(), self.fresh()).apply(body);
+        }
+        ty
+    }
+
+    // Algorithm W: infer the type of an expression with a substitution to be applied to the environment.
+    fn infer(
+        &mut self,
+        env: &TypeEnv,
+        ei: &Arc<ExprInfo>,
+    ) -> Result<(Substitution, Arc<Type>), TypeError> {
+        match &*ei.expr {
+            Expr::Var(var) => match env.vars.get(var.name()) {
+                Some(ty) => Ok((Substitution::default(), self.instantiate(ty))),
+                None => type_error(format!("unknown variable `{}`", var.name())),
+            },
+            // Literals whose type is not known (e.g., bodies of library functions) may have any type.
+            Expr::Lit(lit) => {
+                let ty = match &lit.ty {
+                    Some(ty) => ty.clone(),
+                    None => self.fresh(),
+                };
+                Ok((Substitution::default(), ty))
+            }
+            Expr::App(func, arg) => {
+                let (s1, func_ty) = self.infer(env, func)?;
+                let (s2, arg_ty) = self.infer(&env.apply(&s1), arg)?;
+                let ret_ty = self.fresh();
+                let func_ty = s2.apply(&func_ty);
+                let s3 =
+                    unify(&func_ty, &lambda_ty(arg_ty.clone(), ret_ty.clone())).map_err(|_| {
+                        TypeError {
+                            message: format!(
+                                "cannot apply \"{}\" of type {} to \"{}\" of type {}",
+                                func.expr.to_string(),
+                                func_ty.to_string(),
+                                arg.expr.to_string(),
+                                arg_ty.to_string()
+                            ),
+                        }
+                    })?;
+                Ok((s1.compose(&s2).compose(&s3), s3.apply(&ret_ty)))
+            }
+            Expr::Lam(var, val) => {
+                let var_ty = self.fresh();
+                let mut inner_env = env.clone();
+                inner_env.insert(var.name(), var_ty.clone());
+                let (s1, val_ty) = self.infer(&inner_env, val)?;
+                Ok((s1.clone(), lambda_ty(s1.apply(&var_ty), val_ty)))
+            }
+            Expr::Let(var, bound, val) => {
+                let (s1, bound_ty) = self.infer(env, bound)?;
+                let mut inner_env = env.apply(&s1);
+                let scheme = generalize(&inner_env, &bound_ty);
+                inner_env.insert(var.name(), scheme);
+                let (s2, val_ty) = self.infer(&inner_env, val)?;
+                Ok((s1.compose(&s2), val_ty))
+            }
+            Expr::If(cond, then_expr, else_expr) => {
+                let (s1, cond_ty) = self.infer(env, cond)?;
+                let s2 = unify(&cond_ty, &bool_lit_ty()).map_err(|_| TypeError {
+                    message: format!(
+                        "condition of if must be Bool, but \"{}\" has type {}",
+                        cond.expr.to_string(),
+                        cond_ty.to_string()
+                    ),
+                })?;
+                let subst = s1.compose(&s2);
+                let (s3, then_ty) = self.infer(&env.apply(&subst), then_expr)?;
+                let subst = subst.compose(&s3);
+                let (s4, else_ty) = self.infer(&env.apply(&subst), else_expr)?;
+                let subst = subst.compose(&s4);
+                let then_ty = s4.apply(&then_ty);
+                let s5 = unify(&then_ty, &else_ty).map_err(|_| TypeError {
+                    message: format!(
+                        "if branches have incompatible types: {} and {} in \"{}\"",
+                        then_ty.to_string(),
+                        else_ty.to_string(),
+                        ei.expr.to_string()
+                    ),
+                })?;
+                Ok((subst.compose(&s5), s5.apply(&else_ty)))
+            }
+            Expr::Case(scrutinee, arms) => {
+                let (mut subst, scrutinee_ty) = self.infer(env, scrutinee)?;
+                let ret_ty = self.fresh();
+                for (pat, arm) in arms {
+                    let pat_ty = match **pat {
+                        Pattern::Int(_) => Some(int_lit_ty()),
+                        Pattern::Bool(_) => Some(bool_lit_ty()),
+                        Pattern::Wildcard => None,
+                    };
+                    if let Some(pat_ty) = pat_ty {
+                        let scrutinee_ty = subst.apply(&scrutinee_ty);
+                        let s = unify(&scrutinee_ty, &pat_ty).map_err(|_| TypeError {
+                            message: format!(
+                                "pattern {} doesn't match \"{}\" of type {}",
+                                pat.to_string(),
+                                scrutinee.expr.to_string(),
+                                scrutinee_ty.to_string()
+                            ),
+                        })?;
+                        subst = subst.compose(&s);
+                    }
+                    let (s, arm_ty) = self.infer(&env.apply(&subst), arm)?;
+                    subst = subst.compose(&s);
+                    let prev_ty = subst.apply(&ret_ty);
+                    let s = unify(&prev_ty, &arm_ty).map_err(|_| TypeError {
+                        message: format!(
+                            "case arms have incompatible types: {} and {} in \"{}\"",
+                            prev_ty.to_string(),
+                            arm_ty.to_string(),
+                            ei.expr.to_string()
+                        ),
+                    })?;
+                    subst = subst.compose(&s);
+                }
+                let ret_ty = subst.apply(&ret_ty);
+                Ok((subst, ret_ty))
+            }
+            Expr::Type(_) => type_error(String::from("type expressions are not supported")),
+        }
+    }
+}
+
+// Infer the type of an expression in an environment.
+pub fn infer(ei: &Arc<ExprInfo>, env: &TypeEnv) -> Result<Arc<Type>, TypeError> {
+    let mut inferencer = TypeInferencer::default();
+    let (subst, ty) = inferencer.infer(env, ei)?;
+    Ok(subst.apply(&ty))
+}
+
+// Type of a library function, if it is known.
+// Type variables are implicitly quantified.
+pub fn builtin_type(name: &str) -> Option<Arc<Type>> {
+    let int = int_lit_ty;
+    let bool = bool_lit_ty;
+    let float = float_lit_ty;
+    let string = string_lit_ty;
+    let a = || tyvar_ty("a");
+    let b = || tyvar_ty("b");
+    let c = || tyvar_ty("c");
+    // Type of function taking arguments of given types in order, the last one being the result type.
+    let fun = |tys: Vec<Arc<Type>>| {
+        let mut tys = tys.into_iter().rev();
+        let ret = tys.next().unwrap();
+        tys.fold(ret, |ret, arg| lambda_ty(arg, ret))
+    };
+    let ty = match name {
+        "add" | "sub" | "mul" | "div" | "mod" | "pow" => fun(vec![int(), int(), int()]),
+        "eq" | "lt" | "le" | "gt" | "ge" => fun(vec![int(), int(), bool()]),
+        "neg" => fun(vec![int(), int()]),
+        "not" => fun(vec![bool(), bool()]),
+        "and" | "or" => fun(vec![bool(), bool(), bool()]),
+        "fix" => fun(vec![fun(vec![fun(vec![a(), b()]), a(), b()]), a(), b()]),
+        "newArray" => fun(vec![int(), a(), array_ty(a())]),
+        "readArray" => fun(vec![array_ty(a()), int(), a()]),
+        "writeArray" | "writeArray!" => fun(vec![array_ty(a()), int(), a(), array_ty(a())]),
+        "modifyArray" => fun(vec![
+            array_ty(a()),
+            int(),
+            fun(vec![a(), a()]),
+            array_ty(a()),
+        ]),
+        "eqArray" => fun(vec![array_ty(a()), array_ty(a()), bool()]),
+        "loop" => fun(vec![a(), fun(vec![a(), bool()]), fun(vec![a(), a()]), a()]),
+        "takeArray" | "dropArray" => fun(vec![int(), array_ty(a()), array_ty(a())]),
+        "splitAtArray" => fun(vec![
+            int(),
+            array_ty(a()),
+            pair_ty(array_ty(a()), array_ty(a())),
+        ]),
+        "fst" => fun(vec![pair_ty(a(), b()), a()]),
+        "snd" => fun(vec![pair_ty(a(), b()), b()]),
+        "pair" => fun(vec![a(), b(), pair_ty(a(), b())]),
+        "firstP" => fun(vec![
+            fun(vec![a(), c()]),
+            pair_ty(a(), b()),
+            pair_ty(c(), b()),
+        ]),
+        "secondP" => fun(vec![
+            fun(vec![b(), c()]),
+            pair_ty(a(), b()),
+            pair_ty(a(), c()),
+        ]),
+        "lenArray" => fun(vec![array_ty(a()), int()]),
+        "none" => option_ty(a()),
+        "some" => fun(vec![a(), option_ty(a())]),
+        "isSome" => fun(vec![option_ty(a()), bool()]),
+        "unwrap" => fun(vec![option_ty(a()), a()]),
+        "fromOption" => fun(vec![a(), option_ty(a()), a()]),
+        "mapOption" => fun(vec![fun(vec![a(), b()]), option_ty(a()), option_ty(b())]),
+        "makeWeak" => fun(vec![a(), weak_ty(a())]),
+        "derefWeak" => fun(vec![weak_ty(a()), option_ty(a())]),
+        "findIndexArray" => fun(vec![
+            array_ty(a()),
+            fun(vec![a(), bool()]),
+            option_ty(int()),
+        ]),
+        "foldArray" => fun(vec![array_ty(a()), fun(vec![b(), a(), b()]), b(), b()]),
+        "foldRightArray" => fun(vec![array_ty(a()), fun(vec![a(), b(), b()]), b(), b()]),
+        "copyArray" => fun(vec![array_ty(a()), array_ty(a())]),
+        "rotateArray" => fun(vec![int(), array_ty(a()), array_ty(a())]),
+        "deepCopyArray" => fun(vec![array_ty(a()), fun(vec![a(), b()]), array_ty(b())]),
+        "concatStr" => fun(vec![string(), string(), string()]),
+        "lengthStr" => fun(vec![string(), int()]),
+        "print" => fun(vec![string(), int()]),
+        "error" => fun(vec![int(), a()]),
+        "getEnvInt" => fun(vec![string(), int(), int()]),
+        "sliceView" => fun(vec![array_ty(a()), int(), int(), view_ty(a())]),
+        "readView" => fun(vec![view_ty(a()), int(), a()]),
+        "writeView" => fun(vec![view_ty(a()), int(), a(), view_ty(a())]),
+        "liveObjects" => fun(vec![a(), int()]),
+        "trace" => fun(vec![a(), a()]),
+        "newArrayLazy" => fun(vec![int(), fun(vec![int(), a()]), array_ty(a())]),
+        "parseInt" => fun(vec![string(), option_ty(int())]),
+        "readLineInt" => fun(vec![a(), option_ty(int())]),
+        "isUnique" => fun(vec![a(), bool()]),
+        "seq" => fun(vec![a(), b(), b()]),
+        "clz" | "popcount" => fun(vec![int(), int()]),
+        "iterate" => fun(vec![int(), fun(vec![a(), a()]), a(), a()]),
+        ITERATE_ADD_NAME => fun(vec![int(), int(), int(), int()]),
+        "addF" | "mulF" => fun(vec![float(), float(), float()]),
+        FLOAT_BITS_NAME => fun(vec![float(), int()]),
+        _ => return None,
+    };
+    Some(ty)
+}
@@ -1,22 +1,46 @@
 use super::*;
+use inkwell::targets::TargetData;
 
 #[derive(Eq, Hash, PartialEq, Clone)]
 pub enum ObjectFieldType {
     ControlBlock,
     LambdaFunction,
     SubObject,
+    OptionalSubObject, // SubObject which may be null.
+    WeakCell, // Pointer to the weak cell of an object held by a weak reference, which doesn't keep it alive.
     Int,
+    Float,
     Bool,
     Array,
+    String,
 }
 
 impl ObjectFieldType {
+    // Name of the field type used in descriptions of layouts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectFieldType::ControlBlock => "control_block",
+            ObjectFieldType::LambdaFunction => "lambda_function",
+            ObjectFieldType::SubObject => "sub_object",
+            ObjectFieldType::OptionalSubObject => "optional_sub_object",
+            ObjectFieldType::WeakCell => "weak_cell",
+            ObjectFieldType::Int => "int",
+            ObjectFieldType::Float => "float",
+            ObjectFieldType::Bool => "bool",
+            ObjectFieldType::Array => "array",
+            ObjectFieldType::String => "string",
+        }
+    }
+
     pub fn to_basic_type<'ctx>(&self, context: &'ctx Context) -> BasicTypeEnum<'ctx> {
         match self {
             ObjectFieldType::ControlBlock => control_block_type(context).into(),
             ObjectFieldType::LambdaFunction => ptr_to_lambda_function_type(context).into(),
             ObjectFieldType::SubObject => ptr_to_object_type(context).into(),
+            ObjectFieldType::OptionalSubObject => ptr_to_object_type(context).into(),
+            ObjectFieldType::WeakCell => ptr_to_weak_cell_type(context).into(),
             ObjectFieldType::Int => context.i64_type().into(),
+            ObjectFieldType::Float => context.f64_type().into(),
             ObjectFieldType::Bool => context.i8_type().into(),
             ObjectFieldType::Array => context
                 .struct_type(
@@ -29,9 +53,63 @@ impl ObjectFieldType {
                     false,
                 )
                 .into(),
+            ObjectFieldType::String => context
+                .struct_type(
+                    &[
+                        context.i64_type().into(),                                // size
+                        context.i8_type().ptr_type(AddressSpace::Generic).into(), // ptr to buffer
+                    ],
+                    false,
+                )
+                .into(),
         }
     }
 
+    // Get fields (size and buffer) from string.
+    pub fn get_size_and_buffer_of_string<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        string: PointerValue<'c>,
+    ) -> (IntValue<'c>, PointerValue<'c>) {
+        let string_struct = ObjectFieldType::String
+            .to_basic_type(gc.context)
+            .into_struct_type();
+        let size = gc.load_obj_field(string, string_struct, 0).into_int_value();
+        let buffer = gc
+            .load_obj_field(string, string_struct, 1)
+            .into_pointer_value();
+        (size, buffer)
+    }
+
+    // Allocate buffer of string of a given size and set it (and size) to the string field.
+    // The buffer has an extra null terminator after the content, so that it can be passed to C functions.
+    // Returns the buffer.
+    pub fn allocate_string_buffer<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        string: PointerValue<'c>,
+        size: IntValue<'c>,
+    ) -> PointerValue<'c> {
+        let string_struct = ObjectFieldType::String
+            .to_basic_type(gc.context)
+            .into_struct_type();
+        gc.store_obj_field(string, string_struct, 0, size);
+        let one = size.get_type().const_int(1, false);
+        let size_with_terminator = gc
+            .builder()
+            .build_int_add(size, one, "size_with_terminator");
+        let buffer = gc
+            .builder()
+            .build_array_malloc(gc.context.i8_type(), size_with_terminator, "string_buffer")
+            .unwrap();
+        let terminator = unsafe {
+            gc.builder()
+                .build_gep(buffer, &[size.into()], "string_terminator")
+        };
+        gc.builder()
+            .build_store(terminator, gc.context.i8_type().const_zero());
+        gc.store_obj_field(string, string_struct, 1, buffer);
+        buffer
+    }
+
     // Get fields (size and buffer) from array.
     pub fn get_size_and_buffer_of_array<'c, 'm>(
         gc: &mut GenerationContext<'c, 'm>,
@@ -210,6 +288,112 @@ impl ObjectFieldType {
         }
     }
 
+    // Initialize an array with the given elements. The ownership of each element is moved to the array.
+    pub fn initialize_array_with_elements<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        array_ptr: PointerValue<'c>,
+        elems: &[PointerValue<'c>],
+    ) {
+        let array_struct = ObjectFieldType::Array
+            .to_basic_type(gc.context)
+            .into_struct_type();
+
+        // Set size.
+        let size = gc.context.i64_type().const_int(elems.len() as u64, false);
+        gc.store_obj_field(array_ptr, array_struct, 0, size);
+
+        // Allocate buffer and set it to array.
+        let buffer_ptr = gc
+            .builder()
+            .build_array_malloc(ptr_to_object_type(gc.context), size, "buffer_ptr")
+            .unwrap();
+        gc.store_obj_field(array_ptr, array_struct, 1, buffer_ptr);
+
+        // Store elements.
+        for (i, elem) in elems.iter().enumerate() {
+            let idx = gc.context.i64_type().const_int(i as u64, false);
+            let ptr_to_obj_ptr = unsafe {
+                gc.builder()
+                    .build_gep(buffer_ptr, &[idx.into()], "ptr_to_elem_of_array")
+            };
+            let elem = gc.cast_pointer(*elem, ptr_to_object_type(gc.context));
+            gc.builder().build_store(ptr_to_obj_ptr, elem);
+        }
+    }
+
+    // Load an element of array without bounds check.
+    // Returned object is not retained.
+    pub fn load_array_element_unchecked<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        array: PointerValue<'c>,
+        idx: u64,
+    ) -> PointerValue<'c> {
+        let (_size, ptr_to_buffer) = Self::get_size_and_buffer_of_array(gc, array);
+        let idx = gc.context.i64_type().const_int(idx, false);
+        let ptr_to_elem = unsafe {
+            gc.builder()
+                .build_gep(ptr_to_buffer, &[idx.into()], "ptr_to_elem_of_array")
+        };
+        gc.builder()
+            .build_load(ptr_to_elem, "elem")
+            .into_pointer_value()
+    }
+
+    // Copy int values of elements of an array of ints into a buffer of i64, up to out_len elements.
+    // Returns the size of the array.
+    pub fn copy_int_array_to_buffer<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        array_ptr: PointerValue<'c>,
+        out: PointerValue<'c>,
+        out_len: IntValue<'c>,
+    ) -> IntValue<'c> {
+        // In loop body, store the value of the element at idx if idx < out_len.
+        let loop_body = |gc: &mut GenerationContext<'c, 'm>,
+                         idx: IntValue<'c>,
+                         _size: IntValue<'c>,
+                         ptr_to_buffer: PointerValue<'c>| {
+            let current_bb = gc.builder().get_insert_block().unwrap();
+            let func = current_bb.get_parent().unwrap();
+            let store_bb = gc.context.append_basic_block(func, "store_elem_to_out");
+            let cont_bb = gc
+                .context
+                .append_basic_block(func, "after_store_elem_to_out");
+            let in_out = gc
+                .builder()
+                .build_int_compare(IntPredicate::SLT, idx, out_len, "in_out");
+            gc.builder()
+                .build_conditional_branch(in_out, store_bb, cont_bb);
+
+            gc.builder().position_at_end(store_bb);
+            let ptr_to_obj_ptr = unsafe {
+                gc.builder()
+                    .build_gep(ptr_to_buffer, &[idx.into()], "ptr_to_elem_of_array")
+            };
+            let obj_ptr = gc
+                .builder()
+                .build_load(ptr_to_obj_ptr, "elem_of_array")
+                .into_pointer_value();
+            let value = gc.load_obj_field(obj_ptr, int_type(gc.context), 1);
+            let ptr_to_out_elem = unsafe {
+                gc.builder()
+                    .build_gep(out, &[idx.into()], "ptr_to_elem_of_out")
+            };
+            gc.builder().build_store(ptr_to_out_elem, value);
+            gc.builder().build_unconditional_branch(cont_bb);
+
+            gc.builder().position_at_end(cont_bb);
+        };
+
+        // Generate loop.
+        let after_loop = |_gc: &mut GenerationContext<'c, 'm>,
+                          _size: IntValue<'c>,
+                          _ptr_to_buffer: PointerValue<'c>| {};
+        Self::loop_over_array(gc, array_ptr, loop_body, after_loop);
+
+        let (size, _buffer) = Self::get_size_and_buffer_of_array(gc, array_ptr);
+        size
+    }
+
     // Panic if idx is out_of_range for the array.
     pub fn panic_if_out_of_array<'c, 'm>(
         gc: &mut GenerationContext<'c, 'm>,
@@ -221,7 +405,7 @@ impl ObjectFieldType {
         let curr_func = curr_bb.get_parent().unwrap();
         let is_out_of_range =
             gc.builder()
-                .build_int_compare(IntPredicate::UGE, idx, size, "is_out_of_ramge");
+                .build_int_compare(IntPredicate::UGE, idx, size, "is_out_of_range");
         let out_of_range_bb = gc.context.append_basic_block(curr_func, "out_of_range_bb");
         let in_range_bb = gc.context.append_basic_block(curr_func, "in_range_bb");
         gc.builder()
@@ -284,12 +468,64 @@ impl ObjectFieldType {
 
         // Release element that is already at the place.
         let elem = gc.builder().build_load(place, "elem").into_pointer_value();
+        if SANITIZE_MEMORY {
+            Self::check_same_kind(gc, elem, value);
+        }
         gc.release(elem);
 
         // Insert the given value to the place.
         gc.builder().build_store(place, value);
     }
 
+    // Report to sanitizer if the kind of value differs from the kind of the element of array which it replaces.
+    // All elements of an array are of the kind of the initial value given at creation, since writes are checked by this.
+    fn check_same_kind<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        elem: PointerValue<'c>,
+        value: PointerValue<'c>,
+    ) {
+        let mut kinds = vec![];
+        for obj in [elem, value] {
+            let obj_id = gc.get_obj_id(obj);
+            let kind = gc
+                .call_runtime(RuntimeFunctions::GetKind, &[obj_id.into()])
+                .try_as_basic_value()
+                .unwrap_left()
+                .into_int_value();
+            kinds.push(kind);
+        }
+        let unknown = gc
+            .context
+            .i64_type()
+            .const_int(ObjectKind::Unknown as u64, false);
+        let is_ne =
+            gc.builder()
+                .build_int_compare(IntPredicate::NE, kinds[0], kinds[1], "is_kind_ne");
+        let is_known_elem =
+            gc.builder()
+                .build_int_compare(IntPredicate::NE, kinds[0], unknown, "is_known_elem");
+        let is_known_value =
+            gc.builder()
+                .build_int_compare(IntPredicate::NE, kinds[1], unknown, "is_known_value");
+        let is_known = gc
+            .builder()
+            .build_and(is_known_elem, is_known_value, "is_known");
+        let is_mismatch = gc.builder().build_and(is_ne, is_known, "is_kind_mismatch");
+        let curr_bb = gc.builder().get_insert_block().unwrap();
+        let curr_func = curr_bb.get_parent().unwrap();
+        let mismatch_bb = gc.context.append_basic_block(curr_func, "kind_mismatch_bb");
+        let cont_bb = gc.context.append_basic_block(curr_func, "cont_bb");
+        gc.builder()
+            .build_conditional_branch(is_mismatch, mismatch_bb, cont_bb);
+        gc.builder().position_at_end(mismatch_bb);
+        gc.call_runtime(
+            RuntimeFunctions::ReportKindMismatch,
+            &[kinds[0].into(), kinds[1].into()],
+        );
+        gc.builder().build_unconditional_branch(cont_bb);
+        gc.builder().position_at_end(cont_bb);
+    }
+
     // Clone an array
     pub fn clone_array<'c, 'm>(
         gc: &mut GenerationContext<'c, 'm>,
@@ -344,30 +580,380 @@ impl ObjectFieldType {
             Self::loop_over_array(gc, src, loop_body, after_loop);
         }
     }
+
+    // Clone an array with elements rotated left by shift positions (modulo the size of the array).
+    pub fn rotate_array<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        src: PointerValue<'c>,
+        dst: PointerValue<'c>,
+        shift: IntValue<'c>,
+    ) {
+        let array_struct = ObjectFieldType::Array
+            .to_basic_type(gc.context)
+            .into_struct_type();
+        let i64_type = gc.context.i64_type();
+
+        // Get fields (size, ptr_to_buffer) of src.
+        let (src_size, src_buffer) = Self::get_size_and_buffer_of_array(gc, src);
+
+        // Normalize shift into [0, size). Shift is zero for the empty array.
+        let is_empty = gc.builder().build_int_compare(
+            IntPredicate::EQ,
+            src_size,
+            i64_type.const_zero(),
+            "is_empty",
+        );
+        let divisor = gc
+            .builder()
+            .build_select(is_empty, i64_type.const_int(1, false), src_size, "divisor")
+            .into_int_value();
+        let rem = gc.builder().build_int_signed_rem(shift, divisor, "rem");
+        let is_negative = gc.builder().build_int_compare(
+            IntPredicate::SLT,
+            rem,
+            i64_type.const_zero(),
+            "is_negative",
+        );
+        let rem_plus_divisor = gc.builder().build_int_add(rem, divisor, "rem_plus_divisor");
+        let shift = gc
+            .builder()
+            .build_select(is_negative, rem_plus_divisor, rem, "shift")
+            .into_int_value();
+
+        // Copy size.
+        gc.store_obj_field(dst, array_struct, 0, src_size);
+
+        // Allocate buffer and set it to dst.
+        let dst_buffer = gc
+            .builder()
+            .build_array_malloc(ptr_to_object_type(gc.context), src_size, "dst_buffer")
+            .unwrap();
+        gc.store_obj_field(dst, array_struct, 1, dst_buffer);
+
+        // Clone each elements.
+        {
+            // In loop body, retain the element at (idx + shift) % size of src and store it at idx of dst.
+            let loop_body = |gc: &mut GenerationContext<'c, 'm>,
+                             idx: IntValue<'c>,
+                             size: IntValue<'c>,
+                             _ptr_to_buffer: PointerValue<'c>| {
+                let src_idx = gc.builder().build_int_add(idx, shift, "src_idx");
+                let is_over =
+                    gc.builder()
+                        .build_int_compare(IntPredicate::SGE, src_idx, size, "is_over");
+                let wrapped_idx = gc.builder().build_int_sub(src_idx, size, "wrapped_idx");
+                let src_idx = gc
+                    .builder()
+                    .build_select(is_over, wrapped_idx, src_idx, "src_idx")
+                    .into_int_value();
+                let ptr_to_src_elem = unsafe {
+                    gc.builder()
+                        .build_gep(src_buffer, &[src_idx.into()], "ptr_to_src_elem")
+                };
+                let ptr_to_dst_elem = unsafe {
+                    gc.builder()
+                        .build_gep(dst_buffer, &[idx.into()], "ptr_to_dst_elem")
+                };
+                let src_elem = gc
+                    .builder()
+                    .build_load(ptr_to_src_elem, "src_elem")
+                    .into_pointer_value();
+                gc.retain(src_elem);
+                gc.builder().build_store(ptr_to_dst_elem, src_elem);
+            };
+
+            // After loop, do nothing.
+            let after_loop = |_gc: &mut GenerationContext<'c, 'm>,
+                              _size: IntValue<'c>,
+                              _ptr_to_buffer: PointerValue<'c>| {};
+
+            Self::loop_over_array(gc, src, loop_body, after_loop);
+        }
+    }
+
+    // Clamp an index into the range [0, size] of the array.
+    pub fn clamp_index_of_array<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        array: PointerValue<'c>,
+        idx: IntValue<'c>,
+    ) -> IntValue<'c> {
+        let (size, _ptr_to_buffer) = Self::get_size_and_buffer_of_array(gc, array);
+        let zero = gc.context.i64_type().const_zero();
+        let is_negative =
+            gc.builder()
+                .build_int_compare(IntPredicate::SLT, idx, zero, "is_negative");
+        let idx = gc
+            .builder()
+            .build_select(is_negative, zero, idx, "idx_clamped_below")
+            .into_int_value();
+        let is_over = gc
+            .builder()
+            .build_int_compare(IntPredicate::SGT, idx, size, "is_over");
+        gc.builder()
+            .build_select(is_over, size, idx, "idx_clamped")
+            .into_int_value()
+    }
+
+    // Copy elements of src in range [begin, end) into dst, which is not initialized yet.
+    // Copied elements are retained.
+    pub fn slice_array<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        src: PointerValue<'c>,
+        dst: PointerValue<'c>,
+        begin: IntValue<'c>,
+        end: IntValue<'c>,
+    ) {
+        let array_struct = ObjectFieldType::Array
+            .to_basic_type(gc.context)
+            .into_struct_type();
+
+        // Get fields (size, ptr_to_buffer) of src.
+        let (_src_size, src_buffer) = Self::get_size_and_buffer_of_array(gc, src);
+
+        // Set size.
+        let size = gc.builder().build_int_sub(end, begin, "size");
+        gc.store_obj_field(dst, array_struct, 0, size);
+
+        // Allocate buffer and set it to dst.
+        let dst_buffer = gc
+            .builder()
+            .build_array_malloc(ptr_to_object_type(gc.context), size, "dst_buffer")
+            .unwrap();
+        gc.store_obj_field(dst, array_struct, 1, dst_buffer);
+
+        // Copy elements.
+        {
+            // In loop body, retain element at begin + idx of src and store it at idx of dst.
+            let loop_body = |gc: &mut GenerationContext<'c, 'm>,
+                             idx: IntValue<'c>,
+                             _size: IntValue<'c>,
+                             dst_buffer: PointerValue<'c>| {
+                let src_idx = gc.builder().build_int_add(begin, idx, "src_idx");
+                let ptr_to_src_elem = unsafe {
+                    gc.builder()
+                        .build_gep(src_buffer, &[src_idx.into()], "ptr_to_src_elem")
+                };
+                let ptr_to_dst_elem = unsafe {
+                    gc.builder()
+                        .build_gep(dst_buffer, &[idx.into()], "ptr_to_dst_elem")
+                };
+                let src_elem = gc
+                    .builder()
+                    .build_load(ptr_to_src_elem, "src_elem")
+                    .into_pointer_value();
+                gc.retain(src_elem);
+                gc.builder().build_store(ptr_to_dst_elem, src_elem);
+            };
+
+            // After loop, do nothing.
+            let after_loop = |_gc: &mut GenerationContext<'c, 'm>,
+                              _size: IntValue<'c>,
+                              _ptr_to_buffer: PointerValue<'c>| {};
+
+            Self::loop_over_array(gc, dst, loop_body, after_loop);
+        }
+    }
+
+    // Compare two arrays elementwise and return the result as a value of bool field.
+    // NOTE: elements are assumed to be int objects for now.
+    pub fn eq_array<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        lhs: PointerValue<'c>,
+        rhs: PointerValue<'c>,
+    ) -> IntValue<'c> {
+        let bool_ty = ObjectFieldType::Bool
+            .to_basic_type(gc.context)
+            .into_int_type();
+
+        // Get fields (size, ptr_to_buffer) of both arrays.
+        let (lhs_size, lhs_buffer) = Self::get_size_and_buffer_of_array(gc, lhs);
+        let (rhs_size, rhs_buffer) = Self::get_size_and_buffer_of_array(gc, rhs);
+
+        // Allocate and initialize loop counter.
+        let counter_type = gc.context.i64_type();
+        let counter_ptr = gc.builder().build_alloca(counter_type, "eq_loop_counter");
+        gc.builder()
+            .build_store(counter_ptr, counter_type.const_zero());
+
+        // Append blocks: loop_check, loop_body and cont.
+        let current_bb = gc.builder().get_insert_block().unwrap();
+        let current_func = current_bb.get_parent().unwrap();
+        let loop_check_bb = gc
+            .context
+            .append_basic_block(current_func, "loop_compare_array_elements");
+        let loop_body_bb = gc.context.append_basic_block(current_func, "loop_body");
+        let cont_bb = gc.context.append_basic_block(current_func, "cont_bb");
+
+        // Arrays of different sizes are unequal.
+        let is_same_size =
+            gc.builder()
+                .build_int_compare(IntPredicate::EQ, lhs_size, rhs_size, "is_same_size");
+        gc.builder()
+            .build_conditional_branch(is_same_size, loop_check_bb, cont_bb);
+
+        // Implement loop_check bb: arrays are equal if all elements are checked.
+        gc.builder().position_at_end(loop_check_bb);
+        let counter_val = gc
+            .builder()
+            .build_load(counter_ptr, "counter_val")
+            .into_int_value();
+        let is_end =
+            gc.builder()
+                .build_int_compare(IntPredicate::EQ, counter_val, lhs_size, "is_end");
+        gc.builder()
+            .build_conditional_branch(is_end, cont_bb, loop_body_bb);
+
+        // Implement loop_body bb: exit early on the first mismatch.
+        gc.builder().position_at_end(loop_body_bb);
+        let mut elem_vals = vec![];
+        for buffer in [lhs_buffer, rhs_buffer] {
+            let ptr_to_elem = unsafe {
+                gc.builder()
+                    .build_gep(buffer, &[counter_val.into()], "ptr_to_elem_of_array")
+            };
+            let elem = gc
+                .builder()
+                .build_load(ptr_to_elem, "elem")
+                .into_pointer_value();
+            let elem_val = gc
+                .load_obj_field(elem, int_type(gc.context), 1)
+                .into_int_value();
+            elem_vals.push(elem_val);
+        }
+        let is_elem_eq = gc.builder().build_int_compare(
+            IntPredicate::EQ,
+            elem_vals[0],
+            elem_vals[1],
+            "is_elem_eq",
+        );
+        let incremented_counter_val = gc.builder().build_int_add(
+            counter_val,
+            counter_type.const_int(1, false),
+            "incremented_counter_val",
+        );
+        gc.builder()
+            .build_store(counter_ptr, incremented_counter_val);
+        gc.builder()
+            .build_conditional_branch(is_elem_eq, loop_check_bb, cont_bb);
+
+        // Implement cont bb.
+        gc.builder().position_at_end(cont_bb);
+        let phi = gc.builder().build_phi(bool_ty, "eq_array_phi");
+        let true_val = bool_ty.const_int(1, false);
+        let false_val = bool_ty.const_zero();
+        phi.add_incoming(&[
+            (&false_val, current_bb),
+            (&true_val, loop_check_bb),
+            (&false_val, loop_body_bb),
+        ]);
+        phi.as_basic_value().into_int_value()
+    }
+}
+
+// Kinds of objects, which are reported to sanitizer to detect type confusions.
+// Keep numbering in sync with kind_name in libfixsanitizer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ObjectKind {
+    Unknown = 0,
+    Int = 1,
+    Bool = 2,
+    Array = 3,
+    String = 4,
+    Option = 5,
+    Pair = 6,
+    Lambda = 7,
+    ArrayView = 8,
+    Float = 9,
+    Weak = 10,
 }
 
 #[derive(Eq, Hash, PartialEq, Clone)]
 pub struct ObjectType {
     pub field_types: Vec<ObjectFieldType>,
+    // Name of a function of type `void(i8*)` called by dtor before fields are released.
+    pub finalizer: Option<String>,
+    // Physical index (in the struct type) of each field, indexed by the logical index (in field_types).
+    // None means fields are laid out in the logical order.
+    pub field_order: Option<Vec<u32>>,
 }
 
 impl ObjectType {
     pub fn to_struct_type<'ctx>(&self, context: &'ctx Context) -> StructType<'ctx> {
-        let mut fields: Vec<BasicTypeEnum<'ctx>> = vec![];
-        for field_type in &self.field_types {
-            fields.push(field_type.to_basic_type(context));
+        let mut fields: Vec<Option<BasicTypeEnum<'ctx>>> = vec![None; self.field_types.len()];
+        for (i, field_type) in self.field_types.iter().enumerate() {
+            fields[self.physical_index(i as u32) as usize] =
+                Some(field_type.to_basic_type(context));
         }
+        let fields: Vec<BasicTypeEnum<'ctx>> = fields.into_iter().map(|f| f.unwrap()).collect();
         context.struct_type(&fields, false)
     }
 
+    // Lay out fields in memory in a given order, e.g., to put fields accessed together close.
+    // `field_order[i]` is the physical index of the field of logical index i.
+    // The control block has to stay at the head of the object.
+    pub fn with_field_order(mut self, field_order: Vec<u32>) -> Self {
+        assert_eq!(field_order.len(), self.field_types.len());
+        assert_eq!(field_order[0], 0, "control block has to be the first field");
+        let mut sorted = field_order.clone();
+        sorted.sort();
+        assert!(
+            sorted.iter().enumerate().all(|(i, idx)| i as u32 == *idx),
+            "field order is not a permutation"
+        );
+        self.field_order = Some(field_order);
+        self
+    }
+
+    // Get the physical index of the field of a logical index.
+    pub fn physical_index(&self, logical_index: u32) -> u32 {
+        match &self.field_order {
+            Some(order) => order[logical_index as usize],
+            None => logical_index,
+        }
+    }
+
+    // Load the field of a logical index from an object of this type.
+    pub fn load_field<'c, 'm>(
+        &self,
+        gc: &GenerationContext<'c, 'm>,
+        obj: PointerValue<'c>,
+        logical_index: u32,
+    ) -> BasicValueEnum<'c> {
+        let struct_type = self.to_struct_type(gc.context);
+        gc.load_obj_field(obj, struct_type, self.physical_index(logical_index))
+    }
+
+    // Store a value into the field of a logical index of an object of this type.
+    pub fn store_field<'c, 'm, V>(
+        &self,
+        gc: &GenerationContext<'c, 'm>,
+        obj: PointerValue<'c>,
+        logical_index: u32,
+        value: V,
+    ) where
+        V: BasicValue<'c>,
+    {
+        let struct_type = self.to_struct_type(gc.context);
+        gc.store_obj_field(obj, struct_type, self.physical_index(logical_index), value);
+    }
+
     fn shared_obj_type(mut field_types: Vec<ObjectFieldType>) -> Self {
         let mut fields = vec![ObjectFieldType::ControlBlock];
         fields.append(&mut field_types);
         Self {
             field_types: fields,
+            finalizer: None,
+            field_order: None,
         }
     }
 
+    // Attach a finalizer to this object type.
+    // The finalizer is called with the pointer to object before fields are released in dtor.
+    pub fn with_finalizer(mut self, finalizer: &str) -> Self {
+        self.finalizer = Some(String::from(finalizer));
+        self
+    }
+
     pub fn lam_obj_type() -> Self {
         Self::shared_obj_type(vec![ObjectFieldType::LambdaFunction]) // Other fields for captured objects may exist but omitted here.
     }
@@ -376,6 +962,10 @@ impl ObjectType {
         Self::shared_obj_type(vec![ObjectFieldType::Int])
     }
 
+    pub fn float_obj_type() -> Self {
+        Self::shared_obj_type(vec![ObjectFieldType::Float])
+    }
+
     pub fn bool_obj_type() -> Self {
         Self::shared_obj_type(vec![ObjectFieldType::Bool])
     }
@@ -385,6 +975,92 @@ impl ObjectType {
         Self::shared_obj_type(fields)
     }
 
+    // String = [ControlBlock, StringField] where StringField = [Size, PtrToBuffer].
+    pub fn string_type() -> Self {
+        Self::shared_obj_type(vec![ObjectFieldType::String])
+    }
+
+    // Option = [ControlBlock, OptionalSubObject], where null represents an absent value.
+    pub fn option_type() -> Self {
+        Self::shared_obj_type(vec![ObjectFieldType::OptionalSubObject])
+    }
+
+    // Weak = [ControlBlock, WeakCell].
+    pub fn weak_type() -> Self {
+        Self::shared_obj_type(vec![ObjectFieldType::WeakCell])
+    }
+
+    pub fn pair_type() -> Self {
+        Self::shared_obj_type(vec![ObjectFieldType::SubObject, ObjectFieldType::SubObject])
+    }
+
+    // ArrayView = [ControlBlock, Parent, Offset, Len], where Parent is the array whose buffer is shared.
+    pub fn array_view_type() -> Self {
+        Self::shared_obj_type(vec![
+            ObjectFieldType::SubObject,
+            ObjectFieldType::Int,
+            ObjectFieldType::Int,
+        ])
+    }
+
+    // Get kind of objects of this type.
+    // Closures are of the same kind regardless of captured objects.
+    pub fn kind(&self) -> ObjectKind {
+        let field_types = &self.field_types;
+        if field_types.get(1) == Some(&ObjectFieldType::LambdaFunction) {
+            return ObjectKind::Lambda;
+        }
+        let kinds = [
+            (Self::int_obj_type(), ObjectKind::Int),
+            (Self::bool_obj_type(), ObjectKind::Bool),
+            (Self::array_type(), ObjectKind::Array),
+            (Self::string_type(), ObjectKind::String),
+            (Self::option_type(), ObjectKind::Option),
+            (Self::pair_type(), ObjectKind::Pair),
+            (Self::array_view_type(), ObjectKind::ArrayView),
+            (Self::float_obj_type(), ObjectKind::Float),
+            (Self::weak_type(), ObjectKind::Weak),
+        ];
+        kinds
+            .into_iter()
+            .find(|(ty, _)| ty.field_types == *field_types)
+            .map(|(_, kind)| kind)
+            .unwrap_or(ObjectKind::Unknown)
+    }
+
+    // Describe the lowered layout of this object type under a given data layout.
+    // The control block is expanded into its fields (refcnt, dtor and, if sanitized, obj_id).
+    pub fn layout(&self, context: &Context, target_data: &TargetData) -> ObjectLayout {
+        let struct_type = self.to_struct_type(context);
+        let cb_type = control_block_type(context);
+        let cb_field_names = ["refcnt", "dtor", "obj_id"];
+        let mut fields = vec![];
+        for (i, ft) in self.field_types.iter().enumerate() {
+            let offset = target_data
+                .offset_of_element(&struct_type, self.physical_index(i as u32))
+                .unwrap();
+            if *ft == ObjectFieldType::ControlBlock {
+                for (j, cb_field_ty) in cb_type.get_field_types().iter().enumerate() {
+                    fields.push(FieldLayout {
+                        name: format!("{}.{}", ft.name(), cb_field_names[j]),
+                        offset: offset + target_data.offset_of_element(&cb_type, j as u32).unwrap(),
+                        size: target_data.get_abi_size(cb_field_ty),
+                    });
+                }
+            } else {
+                fields.push(FieldLayout {
+                    name: String::from(ft.name()),
+                    offset,
+                    size: target_data.get_abi_size(&ft.to_basic_type(context)),
+                });
+            }
+        }
+        ObjectLayout {
+            size: target_data.get_abi_size(&struct_type),
+            fields,
+        }
+    }
+
     fn generate_func_dtor<'c, 'm>(&self, gc: &mut GenerationContext<'c, 'm>) -> FunctionValue<'c> {
         if gc
             .runtimes
@@ -398,32 +1074,74 @@ impl ObjectType {
         let struct_type = self.to_struct_type(gc.context);
         let func_type = dtor_type(gc.context);
         let func = gc.module.add_function("dtor", func_type, None);
+        // The lowest bit of the address of a dtor is zero, so that it is distinguished from a tagged weak cell.
+        func.as_global_value().set_alignment(2);
         let bb = gc.context.append_basic_block(func, "entry");
 
         let _builder_guard = gc.push_builder();
 
         gc.builder().position_at_end(bb);
         let ptr_to_obj = func.get_first_param().unwrap().into_pointer_value();
+        if let Some(finalizer) = &self.finalizer {
+            let finalizer_func = match gc.module.get_function(finalizer) {
+                Some(func) => func,
+                None => gc.module.add_function(finalizer, func_type, None),
+            };
+            gc.builder()
+                .build_call(finalizer_func, &[ptr_to_obj.into()], "call_finalizer");
+        }
         for (i, ft) in self.field_types.iter().enumerate() {
+            let i = self.physical_index(i as u32);
             match ft {
                 ObjectFieldType::SubObject => {
                     let ptr_to_subobj = gc
-                        .load_obj_field(ptr_to_obj, struct_type, i as u32)
+                        .load_obj_field(ptr_to_obj, struct_type, i)
                         .into_pointer_value();
                     gc.release(ptr_to_subobj);
                 }
                 ObjectFieldType::ControlBlock => {}
+                ObjectFieldType::OptionalSubObject => {
+                    let ptr_to_subobj = gc
+                        .load_obj_field(ptr_to_obj, struct_type, i)
+                        .into_pointer_value();
+                    let is_null = gc.builder().build_is_null(ptr_to_subobj, "is_null");
+                    let nonnull_bb = gc.context.append_basic_block(func, "nonnull_bb");
+                    let cont_bb = gc.context.append_basic_block(func, "cont_bb");
+                    gc.builder()
+                        .build_conditional_branch(is_null, cont_bb, nonnull_bb);
+                    gc.builder().position_at_end(nonnull_bb);
+                    gc.release(ptr_to_subobj);
+                    gc.builder().build_unconditional_branch(cont_bb);
+                    gc.builder().position_at_end(cont_bb);
+                }
+                ObjectFieldType::WeakCell => {
+                    let ptr_to_cell = gc
+                        .load_obj_field(ptr_to_obj, struct_type, i)
+                        .into_pointer_value();
+                    gc.release_weak_cell(ptr_to_cell);
+                }
                 ObjectFieldType::Int => {}
+                ObjectFieldType::Float => {}
                 ObjectFieldType::LambdaFunction => {}
                 ObjectFieldType::Bool => {}
                 ObjectFieldType::Array => {
                     let ptr_to_struct = gc.cast_pointer(ptr_to_obj, ptr_type(struct_type));
                     let ptr_to_array = gc
                         .builder()
-                        .build_struct_gep(ptr_to_struct, i as u32, "ptr_to_array")
+                        .build_struct_gep(ptr_to_struct, i, "ptr_to_array")
                         .unwrap();
                     ObjectFieldType::destruct_array(gc, ptr_to_array);
                 }
+                ObjectFieldType::String => {
+                    let ptr_to_struct = gc.cast_pointer(ptr_to_obj, ptr_type(struct_type));
+                    let ptr_to_string = gc
+                        .builder()
+                        .build_struct_gep(ptr_to_struct, i, "ptr_to_string")
+                        .unwrap();
+                    let (_size, buffer) =
+                        ObjectFieldType::get_size_and_buffer_of_string(gc, ptr_to_string);
+                    gc.builder().build_free(buffer);
+                }
             }
         }
         gc.builder().build_return(None);
@@ -435,26 +1153,24 @@ impl ObjectType {
     }
 
     // Create an object.
+    // The name is used in reports of sanitizer to identify the object.
     pub fn create_obj<'c, 'm>(
         &self,
         gc: &mut GenerationContext<'c, 'm>,
-        name: Option<&str>,
+        name: &str,
     ) -> PointerValue<'c> {
         let context = gc.context;
         let struct_type = self.to_struct_type(context);
-        // NOTE: Only once allocation is needed since we don't implement weak_ptr
+        // NOTE: Only once allocation is needed: weak references to an object are counted in its weak cell,
+        // which is allocated separately only when a weak reference is made (see `weak_cell_type`).
         let ptr_to_obj = gc
             .builder()
             .build_malloc(struct_type, "ptr_to_obj")
             .unwrap();
 
-        let mut object_id = obj_id_type(gc.context).const_int(0, false);
-
-        if SANITIZE_MEMORY {
-            let string_ptr = name.unwrap_or("N/A");
-            let string_ptr = gc
-                .builder()
-                .build_global_string_ptr(string_ptr, "name_of_obj");
+        // Report allocation to sanitizer and get the id of the object (None if not sanitizing).
+        let object_id = if SANITIZE_MEMORY {
+            let string_ptr = gc.builder().build_global_string_ptr(name, "name_of_obj");
             let string_ptr = string_ptr.as_pointer_value();
             let string_ptr = gc.builder().build_pointer_cast(
                 string_ptr,
@@ -462,19 +1178,23 @@ impl ObjectType {
                 "name_of_obj_i8ptr",
             );
             let ptr = gc.cast_pointer(ptr_to_obj, ptr_to_object_type(gc.context));
+            let kind = gc.context.i64_type().const_int(self.kind() as u64, false);
             let obj_id = gc.call_runtime(
                 RuntimeFunctions::ReportMalloc,
-                &[ptr.into(), string_ptr.into()],
+                &[ptr.into(), string_ptr.into(), kind.into()],
             );
-            object_id = obj_id.try_as_basic_value().unwrap_left().into_int_value();
-        }
+            Some(obj_id.try_as_basic_value().unwrap_left().into_int_value())
+        } else {
+            None
+        };
 
         for (i, ft) in self.field_types.iter().enumerate() {
+            let i = self.physical_index(i as u32);
             match ft {
                 ObjectFieldType::ControlBlock => {
                     let ptr_to_control_block = gc
                         .builder()
-                        .build_struct_gep(ptr_to_obj, i as u32, "ptr_to_control_block")
+                        .build_struct_gep(ptr_to_obj, i, "ptr_to_control_block")
                         .unwrap();
                     let ptr_to_refcnt = gc
                         .builder()
@@ -491,7 +1211,7 @@ impl ObjectType {
                     gc.builder()
                         .build_store(ptr_to_dtor_field, dtor.as_global_value().as_pointer_value());
 
-                    if SANITIZE_MEMORY {
+                    if let Some(object_id) = object_id {
                         let ptr_to_obj_id = gc
                             .builder()
                             .build_struct_gep(ptr_to_control_block, 2, "ptr_to_obj_id")
@@ -500,10 +1220,14 @@ impl ObjectType {
                     }
                 }
                 ObjectFieldType::Int => {}
+                ObjectFieldType::Float => {}
                 ObjectFieldType::SubObject => {}
+                ObjectFieldType::OptionalSubObject => {}
+                ObjectFieldType::WeakCell => {}
                 ObjectFieldType::LambdaFunction => {}
                 ObjectFieldType::Bool => {}
                 ObjectFieldType::Array => {}
+                ObjectFieldType::String => {}
             }
         }
         ptr_to_obj
@@ -532,10 +1256,58 @@ fn dtor_type<'ctx>(context: &'ctx Context) -> FunctionType<'ctx> {
         .fn_type(&[ptr_to_object_type(context).into()], false)
 }
 
-fn ptr_to_dtor_type<'ctx>(context: &'ctx Context) -> PointerType<'ctx> {
+pub fn ptr_to_dtor_type<'ctx>(context: &'ctx Context) -> PointerType<'ctx> {
     dtor_type(context).ptr_type(AddressSpace::Generic)
 }
 
+// Weak cell of an object, which is allocated when the first weak reference to the object is made and shared by weak references to it.
+// Its fields are the number of weak references, the dtor of the object, and the pointer to the object (null after it is destructed).
+// While an object has a weak cell, the dtor field of its control block holds the pointer to the cell tagged by the lowest bit,
+// so that objects without weak references have no field for them (see `GenerationContext::call_dtor`).
+pub fn weak_cell_type<'ctx>(context: &'ctx Context) -> StructType<'ctx> {
+    context.struct_type(
+        &[
+            refcnt_type(context).into(),
+            ptr_to_dtor_type(context).into(),
+            ptr_to_object_type(context).into(),
+        ],
+        false,
+    )
+}
+
+pub fn ptr_to_weak_cell_type<'ctx>(context: &'ctx Context) -> PointerType<'ctx> {
+    weak_cell_type(context).ptr_type(AddressSpace::Generic)
+}
+
+// Lowered layout of a field of an object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+// Lowered layout of an object, for inspection of memory representation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectLayout {
+    pub size: u64,
+    pub fields: Vec<FieldLayout>,
+}
+
+impl std::fmt::Display for ObjectLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "size: {}", self.size)?;
+        for field in &self.fields {
+            writeln!(
+                f,
+                "  offset {:>3}, size {:>3}: {}",
+                field.offset, field.size, field.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
 pub fn control_block_type<'ctx>(context: &'ctx Context) -> StructType<'ctx> {
     let mut fields = vec![
         refcnt_type(context).into(),
@@ -574,6 +1346,10 @@ pub fn int_type<'c>(context: &'c Context) -> StructType<'c> {
     ObjectType::int_obj_type().to_struct_type(context)
 }
 
+pub fn float_type<'c>(context: &'c Context) -> StructType<'c> {
+    ObjectType::float_obj_type().to_struct_type(context)
+}
+
 pub fn bool_type<'c>(context: &'c Context) -> StructType<'c> {
     ObjectType::bool_obj_type().to_struct_type(context)
 }